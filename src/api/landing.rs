@@ -0,0 +1,333 @@
+use serde::{de::DeserializeOwned, Deserialize};
+use tracing::debug;
+use yandex_music::{model::track_model::track::Track, YandexMusicClient};
+
+use super::{
+    get,
+    library::{LibraryAlbum, Playlist},
+    post,
+};
+
+/// The landing blocks shown on the home view.
+const BLOCKS: &str = "personalplaylists,chart,new-releases,new-playlists,mixes";
+
+/// Something shown in a landing block.
+#[derive(Clone, Debug)]
+pub enum LandingEntity {
+    Track {
+        position: Option<i32>,
+        track: Track,
+    },
+    Album(LibraryAlbum),
+    Playlist(Playlist),
+    /// A collection of editorial playlists around a tag, e.g. a mood.
+    Mix {
+        title: String,
+        tag: String,
+    },
+}
+
+/// Where the full list of a block comes from. Landing blocks only contain
+/// the first few entities.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum FeedSource {
+    Chart,
+    NewReleases,
+    NewPlaylists,
+    Tag(String),
+}
+
+impl FeedSource {
+    /// Identifies the feed among the pages that are open.
+    pub fn key(&self) -> String {
+        match self {
+            Self::Chart => "chart".to_string(),
+            Self::NewReleases => "new-releases".to_string(),
+            Self::NewPlaylists => "new-playlists".to_string(),
+            Self::Tag(tag) => format!("tag:{}", tag),
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct LandingBlock {
+    pub title: String,
+    pub source: Option<FeedSource>,
+    pub entities: Vec<LandingEntity>,
+}
+
+/// An entity of a feed whose details are only fetched when it is shown.
+#[derive(Clone, Copy, Debug)]
+pub enum FeedId {
+    Album(i32),
+    Playlist { uid: i32, kind: i32 },
+}
+
+/// The full list of a feed. `ids` follow `entities` and are fetched page by
+/// page.
+pub struct Feed {
+    pub entities: Vec<LandingEntity>,
+    pub ids: Vec<FeedId>,
+}
+
+#[derive(Deserialize)]
+struct Landing {
+    #[serde(default)]
+    blocks: Vec<RawBlock>,
+}
+
+#[derive(Deserialize)]
+struct RawBlock {
+    #[serde(rename = "type")]
+    block_type: String,
+    title: String,
+    #[serde(default)]
+    entities: Vec<RawEntity>,
+}
+
+#[derive(Deserialize)]
+struct RawEntity {
+    #[serde(rename = "type")]
+    entity_type: String,
+    data: serde_json::Value,
+}
+
+#[derive(Deserialize)]
+struct ChartItem {
+    track: Track,
+    chart: Option<ChartPosition>,
+}
+
+#[derive(Deserialize)]
+struct ChartPosition {
+    position: i32,
+}
+
+#[derive(Deserialize)]
+struct PersonalPlaylist {
+    data: Playlist,
+}
+
+#[derive(Deserialize)]
+struct MixLink {
+    title: String,
+    url: String,
+}
+
+#[derive(Deserialize)]
+struct ChartFeed {
+    chart: ChartTracks,
+}
+
+#[derive(Deserialize)]
+struct ChartTracks {
+    #[serde(default)]
+    tracks: Vec<ChartItem>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct NewReleases {
+    #[serde(default)]
+    new_releases: Vec<i32>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct NewPlaylists {
+    #[serde(default)]
+    new_playlists: Vec<PlaylistId>,
+}
+
+#[derive(Deserialize)]
+struct TagPlaylists {
+    #[serde(default)]
+    ids: Vec<PlaylistId>,
+}
+
+#[derive(Deserialize)]
+struct PlaylistId {
+    uid: i32,
+    kind: i32,
+}
+
+impl From<ChartItem> for LandingEntity {
+    fn from(item: ChartItem) -> Self {
+        Self::Track {
+            position: item.chart.map(|chart| chart.position),
+            track: item.track,
+        }
+    }
+}
+
+impl RawEntity {
+    /// Converts the entity, skipping types that can't be shown, such as
+    /// promotions.
+    fn parse(self) -> Option<LandingEntity> {
+        fn data<T: DeserializeOwned>(value: serde_json::Value) -> Option<T> {
+            serde_json::from_value(value)
+                .map_err(|err| debug!("Skipping landing entity: {}", err))
+                .ok()
+        }
+
+        Some(match self.entity_type.as_str() {
+            "chart-item" => data::<ChartItem>(self.data)?.into(),
+            "album" => LandingEntity::Album(data(self.data)?),
+            "playlist" => LandingEntity::Playlist(data(self.data)?),
+            "personal-playlist" => LandingEntity::Playlist(
+                data::<PersonalPlaylist>(self.data)?.data,
+            ),
+            "mix-link" => {
+                let link = data::<MixLink>(self.data)?;
+                let tag = link
+                    .url
+                    .trim_matches('/')
+                    .strip_prefix("tag/")?
+                    .to_string();
+                LandingEntity::Mix {
+                    title: link.title,
+                    tag,
+                }
+            }
+            _ => return None,
+        })
+    }
+}
+
+pub async fn fetch_landing(
+    client: &YandexMusicClient,
+) -> anyhow::Result<Vec<LandingBlock>> {
+    let landing: Landing =
+        get(client, "/landing3", &[("blocks", BLOCKS.to_string())]).await?;
+
+    Ok(landing
+        .blocks
+        .into_iter()
+        .map(|block| LandingBlock {
+            title: block.title,
+            source: match block.block_type.as_str() {
+                "chart" => Some(FeedSource::Chart),
+                "new-releases" => Some(FeedSource::NewReleases),
+                "new-playlists" => Some(FeedSource::NewPlaylists),
+                _ => None,
+            },
+            entities: block
+                .entities
+                .into_iter()
+                .filter_map(RawEntity::parse)
+                .collect(),
+        })
+        .filter(|block| !block.entities.is_empty())
+        .collect())
+}
+
+pub async fn fetch_feed(
+    client: &YandexMusicClient,
+    source: &FeedSource,
+) -> anyhow::Result<Feed> {
+    let playlist_ids = |ids: Vec<PlaylistId>| {
+        ids.into_iter()
+            .map(|id| FeedId::Playlist {
+                uid: id.uid,
+                kind: id.kind,
+            })
+            .collect()
+    };
+
+    Ok(match source {
+        FeedSource::Chart => {
+            let chart: ChartFeed = get(client, "/landing3/chart", &[]).await?;
+            Feed {
+                entities: chart
+                    .chart
+                    .tracks
+                    .into_iter()
+                    .map(Into::into)
+                    .collect(),
+                ids: Vec::new(),
+            }
+        }
+        FeedSource::NewReleases => {
+            let releases: NewReleases =
+                get(client, "/landing3/new-releases", &[]).await?;
+            Feed {
+                entities: Vec::new(),
+                ids: releases
+                    .new_releases
+                    .into_iter()
+                    .map(FeedId::Album)
+                    .collect(),
+            }
+        }
+        FeedSource::NewPlaylists => {
+            let playlists: NewPlaylists =
+                get(client, "/landing3/new-playlists", &[]).await?;
+            Feed {
+                entities: Vec::new(),
+                ids: playlist_ids(playlists.new_playlists),
+            }
+        }
+        FeedSource::Tag(tag) => {
+            let playlists: TagPlaylists =
+                get(client, &format!("/tags/{}/playlist-ids", tag), &[])
+                    .await?;
+            Feed {
+                entities: Vec::new(),
+                ids: playlist_ids(playlists.ids),
+            }
+        }
+    })
+}
+
+/// Fetches the albums and playlists behind `ids`, in the same order.
+pub async fn fetch_feed_page(
+    client: &YandexMusicClient,
+    ids: &[FeedId],
+) -> anyhow::Result<Vec<LandingEntity>> {
+    let album_ids = ids
+        .iter()
+        .filter_map(|id| match id {
+            FeedId::Album(id) => Some(id.to_string()),
+            _ => None,
+        })
+        .collect::<Vec<_>>();
+    let playlist_ids = ids
+        .iter()
+        .filter_map(|id| match id {
+            FeedId::Playlist { uid, kind } => Some(format!("{}:{}", uid, kind)),
+            _ => None,
+        })
+        .collect::<Vec<_>>();
+
+    let mut albums: Vec<LibraryAlbum> = if album_ids.is_empty() {
+        Vec::new()
+    } else {
+        post(client, "/albums", &[("album-ids", album_ids.join(","))]).await?
+    };
+    let mut playlists: Vec<Playlist> = if playlist_ids.is_empty() {
+        Vec::new()
+    } else {
+        post(
+            client,
+            "/playlists/list",
+            &[("playlist-ids", playlist_ids.join(","))],
+        )
+        .await?
+    };
+
+    Ok(ids
+        .iter()
+        .filter_map(|id| match *id {
+            FeedId::Album(id) => albums
+                .iter()
+                .position(|album| album.id == id)
+                .map(|index| LandingEntity::Album(albums.swap_remove(index))),
+            FeedId::Playlist { uid, kind } => playlists
+                .iter()
+                .position(|p| p.uid == uid && p.kind == kind)
+                .map(|index| {
+                    LandingEntity::Playlist(playlists.swap_remove(index))
+                }),
+        })
+        .collect())
+}
@@ -0,0 +1,365 @@
+use serde::Deserialize;
+use yandex_music::{model::track_model::track::Track, YandexMusicClient};
+
+use super::{get, post};
+
+#[derive(Clone, Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Playlist {
+    pub uid: i32,
+    pub kind: i32,
+    pub title: String,
+    pub revision: i32,
+    #[serde(default)]
+    pub track_count: i32,
+    pub visibility: Option<String>,
+    #[serde(default)]
+    pub tracks: Vec<PlaylistTrack>,
+}
+
+impl Playlist {
+    pub fn is_public(&self) -> bool {
+        self.visibility.as_deref() == Some("public")
+    }
+}
+
+#[derive(Clone, Debug, Deserialize)]
+pub struct PlaylistTrack {
+    pub track: Option<Track>,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+pub struct AlbumWithTracks {
+    pub id: i32,
+    pub title: Option<String>,
+    #[serde(rename = "type")]
+    pub album_type: Option<String>,
+    pub year: Option<i32>,
+    #[serde(default)]
+    pub labels: Vec<Label>,
+    #[serde(default)]
+    pub artists: Vec<LibraryArtist>,
+    /// Tracks grouped by disc.
+    #[serde(default)]
+    pub volumes: Vec<Vec<Track>>,
+}
+
+impl AlbumWithTracks {
+    pub fn is_long_form(&self) -> bool {
+        matches!(self.album_type.as_deref(), Some("podcast" | "audiobook"))
+    }
+}
+
+/// A podcast or audiobook, whose tracks are episodes or chapters.
+#[derive(Clone, Debug, Deserialize)]
+pub struct Podcast {
+    pub id: i32,
+    pub title: Option<String>,
+    #[serde(default)]
+    pub artists: Vec<LibraryArtist>,
+    #[serde(default)]
+    pub volumes: Vec<Vec<Episode>>,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Episode {
+    #[serde(flatten)]
+    pub track: Track,
+    /// When the episode was released, as an ISO 8601 timestamp.
+    pub pub_date: Option<String>,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+pub struct Label {
+    pub name: String,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct LibraryAlbum {
+    pub id: i32,
+    pub title: Option<String>,
+    #[serde(rename = "type")]
+    pub album_type: Option<String>,
+    pub year: Option<i32>,
+    #[serde(default)]
+    pub artists: Vec<LibraryArtist>,
+    #[serde(default)]
+    pub track_count: i32,
+}
+
+impl LibraryAlbum {
+    pub fn is_podcast(&self) -> bool {
+        self.album_type.as_deref() == Some("podcast")
+    }
+
+    pub fn is_audiobook(&self) -> bool {
+        self.album_type.as_deref() == Some("audiobook")
+    }
+
+    /// Podcasts and audiobooks, which are listened to in order and resumed
+    /// where they were left off.
+    pub fn is_long_form(&self) -> bool {
+        self.is_podcast() || self.is_audiobook()
+    }
+
+    pub fn is_single(&self) -> bool {
+        self.album_type.as_deref() == Some("single")
+    }
+
+    pub fn is_compilation(&self) -> bool {
+        self.album_type.as_deref() == Some("compilation")
+    }
+}
+
+#[derive(Clone, Debug, Deserialize)]
+pub struct LibraryArtist {
+    pub id: i32,
+    pub name: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct LikedAlbum {
+    album: Option<LibraryAlbum>,
+}
+
+#[derive(Deserialize)]
+struct DislikedTracks {
+    library: TrackIds,
+}
+
+#[derive(Deserialize)]
+struct TrackIds {
+    #[serde(default)]
+    tracks: Vec<TrackId>,
+}
+
+#[derive(Deserialize)]
+struct TrackId {
+    id: String,
+}
+
+/// An artist's page, as returned by the `brief-info` endpoint.
+#[derive(Clone, Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ArtistBriefInfo {
+    pub artist: LibraryArtist,
+    #[serde(default)]
+    pub popular_tracks: Vec<Track>,
+    #[serde(default)]
+    pub albums: Vec<LibraryAlbum>,
+    /// Albums of other artists the artist appears on.
+    #[serde(default)]
+    pub also_albums: Vec<LibraryAlbum>,
+    #[serde(default)]
+    pub similar_artists: Vec<LibraryArtist>,
+}
+
+#[derive(Deserialize)]
+struct ArtistTracks {
+    #[serde(default)]
+    tracks: Vec<Track>,
+}
+
+pub async fn fetch_uid(client: &YandexMusicClient) -> anyhow::Result<i32> {
+    Ok(client.get_account_settings().await?.uid)
+}
+
+pub async fn fetch_liked_tracks(
+    client: &YandexMusicClient,
+    uid: i32,
+) -> anyhow::Result<Vec<Track>> {
+    let tracks = client.get_liked_tracks(uid).await?.tracks;
+    let track_ids = tracks.iter().map(|t| t.id).collect::<Vec<_>>();
+
+    Ok(client.get_tracks(&track_ids, true).await?)
+}
+
+pub async fn fetch_playlist(
+    client: &YandexMusicClient,
+    uid: i32,
+    kind: i32,
+) -> anyhow::Result<Playlist> {
+    get(
+        client,
+        &format!("/users/{}/playlists/{}", uid, kind),
+        &[("rich-tracks", "true".to_string())],
+    )
+    .await
+}
+
+pub async fn fetch_playlists(
+    client: &YandexMusicClient,
+    uid: i32,
+) -> anyhow::Result<Vec<Playlist>> {
+    get(client, &format!("/users/{}/playlists/list", uid), &[]).await
+}
+
+/// Fetches liked albums, including podcasts and audiobooks.
+pub async fn fetch_liked_albums(
+    client: &YandexMusicClient,
+    uid: i32,
+) -> anyhow::Result<Vec<LibraryAlbum>> {
+    let albums: Vec<LikedAlbum> = get(
+        client,
+        &format!("/users/{}/likes/albums", uid),
+        &[("rich", "true".to_string())],
+    )
+    .await?;
+
+    Ok(albums.into_iter().filter_map(|a| a.album).collect())
+}
+
+pub async fn fetch_liked_artists(
+    client: &YandexMusicClient,
+    uid: i32,
+) -> anyhow::Result<Vec<LibraryArtist>> {
+    get(
+        client,
+        &format!("/users/{}/likes/artists", uid),
+        &[("with-timestamps", "false".to_string())],
+    )
+    .await
+}
+
+/// Fetches the most popular tracks of an artist.
+pub async fn fetch_artist_tracks(
+    client: &YandexMusicClient,
+    artist_id: i32,
+) -> anyhow::Result<Vec<Track>> {
+    let tracks: ArtistTracks = get(
+        client,
+        &format!("/artists/{}/tracks", artist_id),
+        &[("page-size", "100".to_string())],
+    )
+    .await?;
+
+    Ok(tracks.tracks)
+}
+
+pub async fn fetch_artist_brief_info(
+    client: &YandexMusicClient,
+    artist_id: i32,
+) -> anyhow::Result<ArtistBriefInfo> {
+    get(client, &format!("/artists/{}/brief-info", artist_id), &[]).await
+}
+
+pub async fn fetch_album(
+    client: &YandexMusicClient,
+    album_id: i32,
+) -> anyhow::Result<AlbumWithTracks> {
+    get(client, &format!("/albums/{}/with-tracks", album_id), &[]).await
+}
+
+pub async fn fetch_podcast(
+    client: &YandexMusicClient,
+    album_id: i32,
+) -> anyhow::Result<Podcast> {
+    get(client, &format!("/albums/{}/with-tracks", album_id), &[]).await
+}
+
+pub async fn like_tracks(
+    client: &YandexMusicClient,
+    uid: i32,
+    track_ids: &[i32],
+) -> anyhow::Result<()> {
+    post::<serde_json::Value>(
+        client,
+        &format!("/users/{}/likes/tracks/add-multiple", uid),
+        &[("track-ids", join_ids(track_ids))],
+    )
+    .await?;
+
+    Ok(())
+}
+
+pub async fn unlike_tracks(
+    client: &YandexMusicClient,
+    uid: i32,
+    track_ids: &[i32],
+) -> anyhow::Result<()> {
+    post::<serde_json::Value>(
+        client,
+        &format!("/users/{}/likes/tracks/remove", uid),
+        &[("track-ids", join_ids(track_ids))],
+    )
+    .await?;
+
+    Ok(())
+}
+
+pub async fn fetch_disliked_track_ids(
+    client: &YandexMusicClient,
+    uid: i32,
+) -> anyhow::Result<Vec<i32>> {
+    let disliked: DislikedTracks =
+        get(client, &format!("/users/{}/dislikes/tracks", uid), &[]).await?;
+
+    Ok(disliked
+        .library
+        .tracks
+        .iter()
+        .filter_map(|track| track.id.parse().ok())
+        .collect())
+}
+
+pub async fn dislike_tracks(
+    client: &YandexMusicClient,
+    uid: i32,
+    track_ids: &[i32],
+) -> anyhow::Result<()> {
+    post::<serde_json::Value>(
+        client,
+        &format!("/users/{}/dislikes/tracks/add-multiple", uid),
+        &[("track-ids", join_ids(track_ids))],
+    )
+    .await?;
+
+    Ok(())
+}
+
+pub async fn undislike_tracks(
+    client: &YandexMusicClient,
+    uid: i32,
+    track_ids: &[i32],
+) -> anyhow::Result<()> {
+    post::<serde_json::Value>(
+        client,
+        &format!("/users/{}/dislikes/tracks/remove", uid),
+        &[("track-ids", join_ids(track_ids))],
+    )
+    .await?;
+
+    Ok(())
+}
+
+/// Applies a list of `insert`/`delete` operations to a playlist.
+///
+/// The request is rejected if `revision` is not the latest revision of the
+/// playlist.
+pub async fn change_playlist(
+    client: &YandexMusicClient,
+    uid: i32,
+    kind: i32,
+    revision: i32,
+    diff: &serde_json::Value,
+) -> anyhow::Result<Playlist> {
+    post(
+        client,
+        &format!("/users/{}/playlists/{}/change-relative", uid, kind),
+        &[
+            ("diff", diff.to_string()),
+            ("revision", revision.to_string()),
+        ],
+    )
+    .await
+}
+
+fn join_ids(track_ids: &[i32]) -> String {
+    track_ids
+        .iter()
+        .map(|id| id.to_string())
+        .collect::<Vec<_>>()
+        .join(",")
+}
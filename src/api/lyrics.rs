@@ -0,0 +1,58 @@
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use serde::Deserialize;
+use yandex_music::YandexMusicClient;
+
+use super::{get, track::sign};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum LyricsFormat {
+    /// Time-synced lyrics in the LRC format.
+    Lrc,
+    Text,
+}
+
+impl LyricsFormat {
+    fn query(&self) -> &'static str {
+        match self {
+            Self::Lrc => "LRC",
+            Self::Text => "TEXT",
+        }
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct LyricsInfo {
+    download_url: String,
+}
+
+/// Fetches the lyrics of a track. Fails if there are no lyrics in the
+/// requested format. The lyrics file is downloaded with `http`, which
+/// doesn't send the token to the lyrics host.
+pub async fn fetch_lyrics(
+    client: &YandexMusicClient,
+    http: &reqwest::Client,
+    track_id: i32,
+    format: LyricsFormat,
+) -> anyhow::Result<String> {
+    let timestamp = SystemTime::now().duration_since(UNIX_EPOCH)?.as_secs();
+    let info: LyricsInfo = get(
+        client,
+        &format!("/tracks/{}/lyrics", track_id),
+        &[
+            ("format", format.query().to_string()),
+            ("timeStamp", timestamp.to_string()),
+            ("sign", sign(&format!("{}{}", track_id, timestamp))),
+        ],
+    )
+    .await?;
+
+    Ok(http
+        .get(info.download_url)
+        .send()
+        .await?
+        .error_for_status()?
+        .text()
+        .await?)
+}
@@ -0,0 +1,92 @@
+pub mod landing;
+pub mod library;
+pub mod lyrics;
+pub mod playlist;
+pub mod plays;
+pub mod rotor;
+pub mod search;
+pub mod track;
+
+use anyhow::Context;
+use reqwest::header::CONTENT_TYPE;
+use serde::{de::DeserializeOwned, Deserialize};
+use yandex_music::YandexMusicClient;
+
+use crate::config::CONFIG;
+
+#[derive(Deserialize)]
+struct Response<T> {
+    result: T,
+}
+
+/// Sends a `GET` request to an API endpoint that `yandex_music` does not
+/// expose yet and unwraps the `result` field of the response.
+pub async fn get<T: DeserializeOwned>(
+    client: &YandexMusicClient,
+    path: &str,
+    query: &[(&str, String)],
+) -> anyhow::Result<T> {
+    let bytes = client
+        .client
+        .get(format!("{}{}", CONFIG.api_url, path))
+        .query(query)
+        .send()
+        .await?
+        .error_for_status()?
+        .bytes()
+        .await?;
+
+    let response: Response<T> = serde_json::from_slice(&bytes)
+        .with_context(|| format!("Unexpected response from {}", path))?;
+
+    Ok(response.result)
+}
+
+/// Sends a form-encoded `POST` request to an API endpoint and unwraps the
+/// `result` field of the response.
+pub async fn post<T: DeserializeOwned>(
+    client: &YandexMusicClient,
+    path: &str,
+    form: &[(&str, String)],
+) -> anyhow::Result<T> {
+    let bytes = client
+        .client
+        .post(format!("{}{}", CONFIG.api_url, path))
+        .form(form)
+        .send()
+        .await?
+        .error_for_status()?
+        .bytes()
+        .await?;
+
+    let response: Response<T> = serde_json::from_slice(&bytes)
+        .with_context(|| format!("Unexpected response from {}", path))?;
+
+    Ok(response.result)
+}
+
+/// Sends a `POST` request with a JSON body to an API endpoint and unwraps the
+/// `result` field of the response.
+pub async fn post_json<T: DeserializeOwned>(
+    client: &YandexMusicClient,
+    path: &str,
+    query: &[(&str, String)],
+    body: &serde_json::Value,
+) -> anyhow::Result<T> {
+    let bytes = client
+        .client
+        .post(format!("{}{}", CONFIG.api_url, path))
+        .query(query)
+        .header(CONTENT_TYPE, "application/json")
+        .body(body.to_string())
+        .send()
+        .await?
+        .error_for_status()?
+        .bytes()
+        .await?;
+
+    let response: Response<T> = serde_json::from_slice(&bytes)
+        .with_context(|| format!("Unexpected response from {}", path))?;
+
+    Ok(response.result)
+}
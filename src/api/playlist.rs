@@ -0,0 +1,345 @@
+use reqwest::StatusCode;
+use serde_json::json;
+use tracing::info;
+use yandex_music::YandexMusicClient;
+
+use super::{
+    library::{change_playlist, fetch_playlist, Playlist},
+    post,
+};
+
+/// How often an edit is attempted again after the playlist was changed
+/// elsewhere in the meantime.
+const CONFLICT_RETRIES: usize = 3;
+
+/// A change to the tracks of a playlist. Tracks are identified by id, so
+/// that the edit still applies after the playlist was changed elsewhere;
+/// indices are only used to tell duplicates apart.
+#[derive(Clone, Debug)]
+pub enum PlaylistEdit {
+    /// Inserts tracks given as `(track_id, album_id)` at `at`, or at the end.
+    Insert {
+        at: Option<usize>,
+        tracks: Vec<(i32, Option<i32>)>,
+    },
+    Remove {
+        track_id: i32,
+        index: Option<usize>,
+    },
+    Move {
+        track_id: i32,
+        from: usize,
+        to: usize,
+    },
+}
+
+impl PlaylistEdit {
+    /// Builds the diff operations that apply this edit to `playlist`, or
+    /// `None` if there is nothing to change.
+    fn diff(&self, playlist: &Playlist) -> Option<serde_json::Value> {
+        let track_count = playlist.tracks.len();
+        match self {
+            Self::Insert { at, tracks } => {
+                let tracks = tracks
+                    .iter()
+                    .map(|(id, album_id)| {
+                        json!({ "id": id, "albumId": album_id })
+                    })
+                    .collect::<Vec<_>>();
+                let at = at.unwrap_or(track_count).min(track_count);
+
+                Some(json!([{ "op": "insert", "at": at, "tracks": tracks }]))
+            }
+            Self::Remove { track_id, index } => {
+                let index = find_track(playlist, *track_id, *index)?;
+
+                Some(json!([{
+                    "op": "delete",
+                    "from": index,
+                    "to": index + 1,
+                    "tracks": [track_ref(playlist, index)],
+                }]))
+            }
+            Self::Move { track_id, from, to } => {
+                let from = find_track(playlist, *track_id, Some(*from))?;
+                let to = (*to).min(track_count.saturating_sub(1));
+                if from == to {
+                    return None;
+                }
+
+                let track = track_ref(playlist, from);
+                Some(json!([
+                    {
+                        "op": "delete",
+                        "from": from,
+                        "to": from + 1,
+                        "tracks": [track],
+                    },
+                    { "op": "insert", "at": to, "tracks": [track] },
+                ]))
+            }
+        }
+    }
+}
+
+/// Finds `track_id` in `playlist`, preferring the occurrence at `hint`.
+fn find_track(
+    playlist: &Playlist,
+    track_id: i32,
+    hint: Option<usize>,
+) -> Option<usize> {
+    let is_track = |index: usize| {
+        playlist.tracks.get(index).is_some_and(|t| {
+            t.track.as_ref().is_some_and(|track| track.id == track_id)
+        })
+    };
+
+    match hint {
+        Some(hint) if is_track(hint) => Some(hint),
+        _ => (0..playlist.tracks.len()).find(|&index| is_track(index)),
+    }
+}
+
+fn track_ref(playlist: &Playlist, index: usize) -> serde_json::Value {
+    let track = playlist.tracks[index].track.as_ref();
+    json!({
+        "id": track.map(|t| t.id),
+        "albumId": track.and_then(|t| t.albums.first()).and_then(|a| a.id),
+    })
+}
+
+fn is_revision_conflict(err: &anyhow::Error) -> bool {
+    err.downcast_ref::<reqwest::Error>()
+        .and_then(|err| err.status())
+        .is_some_and(|status| status == StatusCode::PRECONDITION_FAILED)
+}
+
+/// Applies `edit` to the latest revision of a playlist and returns the
+/// updated playlist. If the playlist is changed elsewhere before the edit
+/// is applied, the edit is rebuilt against the new revision and retried.
+pub async fn edit_playlist(
+    client: &YandexMusicClient,
+    uid: i32,
+    kind: i32,
+    edit: &PlaylistEdit,
+) -> anyhow::Result<Playlist> {
+    let mut attempt = 0;
+    loop {
+        let playlist = fetch_playlist(client, uid, kind).await?;
+        let Some(diff) = edit.diff(&playlist) else {
+            return Ok(playlist);
+        };
+
+        match change_playlist(client, uid, kind, playlist.revision, &diff).await
+        {
+            Ok(_) => return fetch_playlist(client, uid, kind).await,
+            Err(err)
+                if is_revision_conflict(&err)
+                    && attempt + 1 < CONFLICT_RETRIES =>
+            {
+                info!("Playlist {} was changed elsewhere, retrying", kind);
+                attempt += 1;
+            }
+            Err(err) => return Err(err),
+        }
+    }
+}
+
+pub async fn create_playlist(
+    client: &YandexMusicClient,
+    uid: i32,
+    title: &str,
+) -> anyhow::Result<Playlist> {
+    post(
+        client,
+        &format!("/users/{}/playlists/create", uid),
+        &[
+            ("title", title.to_string()),
+            ("visibility", "private".to_string()),
+        ],
+    )
+    .await
+}
+
+pub async fn rename_playlist(
+    client: &YandexMusicClient,
+    uid: i32,
+    kind: i32,
+    title: &str,
+) -> anyhow::Result<Playlist> {
+    post(
+        client,
+        &format!("/users/{}/playlists/{}/name", uid, kind),
+        &[("value", title.to_string())],
+    )
+    .await
+}
+
+pub async fn delete_playlist(
+    client: &YandexMusicClient,
+    uid: i32,
+    kind: i32,
+) -> anyhow::Result<()> {
+    post::<serde_json::Value>(
+        client,
+        &format!("/users/{}/playlists/{}/delete", uid, kind),
+        &[],
+    )
+    .await?;
+
+    Ok(())
+}
+
+pub async fn set_playlist_visibility(
+    client: &YandexMusicClient,
+    uid: i32,
+    kind: i32,
+    is_public: bool,
+) -> anyhow::Result<Playlist> {
+    let visibility = if is_public { "public" } else { "private" };
+    post(
+        client,
+        &format!("/users/{}/playlists/{}/visibility", uid, kind),
+        &[("value", visibility.to_string())],
+    )
+    .await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A playlist of tracks with the given ids, each on the album `id * 10`.
+    fn playlist(track_ids: &[i32]) -> Playlist {
+        let tracks = track_ids
+            .iter()
+            .map(|id| {
+                json!({ "track": {
+                    "id": id,
+                    "realId": id.to_string(),
+                    "title": "Track",
+                    "available": true,
+                    "artists": [],
+                    "albums": [{ "id": id * 10, "title": "Album" }],
+                }})
+            })
+            .collect::<Vec<_>>();
+
+        serde_json::from_value(json!({
+            "uid": 1,
+            "kind": 2,
+            "title": "Playlist",
+            "revision": 3,
+            "tracks": tracks,
+        }))
+        .unwrap()
+    }
+
+    #[test]
+    fn inserts_at_the_end_by_default() {
+        let edit = PlaylistEdit::Insert {
+            at: None,
+            tracks: vec![(4, Some(40)), (5, None)],
+        };
+
+        assert_eq!(
+            edit.diff(&playlist(&[1, 2])),
+            Some(json!([{
+                "op": "insert",
+                "at": 2,
+                "tracks": [
+                    { "id": 4, "albumId": 40 },
+                    { "id": 5, "albumId": null },
+                ],
+            }]))
+        );
+    }
+
+    #[test]
+    fn clamps_the_insert_position_to_the_end() {
+        let edit = PlaylistEdit::Insert {
+            at: Some(10),
+            tracks: vec![(4, Some(40))],
+        };
+
+        let diff = edit.diff(&playlist(&[1, 2])).unwrap();
+        assert_eq!(diff[0]["at"], 2);
+    }
+
+    #[test]
+    fn deletes_the_track_at_its_index() {
+        let edit = PlaylistEdit::Remove {
+            track_id: 2,
+            index: Some(3),
+        };
+
+        assert_eq!(
+            edit.diff(&playlist(&[1, 2, 3, 2])),
+            Some(json!([{
+                "op": "delete",
+                "from": 3,
+                "to": 4,
+                "tracks": [{ "id": 2, "albumId": 20 }],
+            }]))
+        );
+    }
+
+    #[test]
+    fn finds_a_track_that_was_moved_elsewhere() {
+        let edit = PlaylistEdit::Remove {
+            track_id: 2,
+            index: Some(0),
+        };
+
+        let diff = edit.diff(&playlist(&[1, 3, 2])).unwrap();
+        assert_eq!(diff[0]["from"], 2);
+        assert_eq!(diff[0]["to"], 3);
+    }
+
+    #[test]
+    fn does_nothing_for_a_removed_track() {
+        let edit = PlaylistEdit::Remove {
+            track_id: 4,
+            index: None,
+        };
+
+        assert_eq!(edit.diff(&playlist(&[1, 2])), None);
+    }
+
+    #[test]
+    fn moves_a_track_by_deleting_and_inserting_it() {
+        let edit = PlaylistEdit::Move {
+            track_id: 1,
+            from: 0,
+            to: 2,
+        };
+
+        assert_eq!(
+            edit.diff(&playlist(&[1, 2, 3])),
+            Some(json!([
+                {
+                    "op": "delete",
+                    "from": 0,
+                    "to": 1,
+                    "tracks": [{ "id": 1, "albumId": 10 }],
+                },
+                {
+                    "op": "insert",
+                    "at": 2,
+                    "tracks": [{ "id": 1, "albumId": 10 }],
+                },
+            ]))
+        );
+    }
+
+    #[test]
+    fn does_not_move_a_track_past_the_end() {
+        let edit = PlaylistEdit::Move {
+            track_id: 3,
+            from: 2,
+            to: 5,
+        };
+
+        assert_eq!(edit.diff(&playlist(&[1, 2, 3])), None);
+    }
+}
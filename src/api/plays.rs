@@ -0,0 +1,139 @@
+use std::time::SystemTime;
+
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use yandex_music::{model::track_model::track::Track, YandexMusicClient};
+
+use crate::utils::format_timestamp;
+
+use super::post_json;
+
+/// Where reports say playback was started from.
+const CLIENT: &str = "yatui";
+
+/// What the queue was started from, which lets plays count towards the
+/// history and recommendations of the right playlist, album or station.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub enum PlayContext {
+    Liked,
+    Playlist {
+        uid: i32,
+        kind: i32,
+    },
+    Album {
+        id: i32,
+    },
+    Artist {
+        id: i32,
+    },
+    Radio {
+        station: String,
+    },
+    Search,
+    #[default]
+    Other,
+}
+
+impl PlayContext {
+    fn name(&self) -> &'static str {
+        match self {
+            Self::Liked | Self::Playlist { .. } => "playlist",
+            Self::Album { .. } => "album",
+            Self::Artist { .. } => "artist",
+            Self::Radio { .. } => "radio",
+            Self::Search => "search",
+            Self::Other => "other",
+        }
+    }
+
+    fn item(&self) -> Option<String> {
+        match self {
+            Self::Playlist { uid, kind } => Some(format!("{}:{}", uid, kind)),
+            Self::Album { id } | Self::Artist { id } => Some(id.to_string()),
+            Self::Radio { station } => Some(station.clone()),
+            _ => None,
+        }
+    }
+
+    fn from(&self) -> String {
+        let page = match self {
+            Self::Liked => "collection",
+            Self::Playlist { .. } => "own_playlists",
+            Self::Album { .. } => "album",
+            Self::Artist { .. } => "artist",
+            Self::Radio { .. } => "radio",
+            Self::Search => "search",
+            Self::Other => "other",
+        };
+
+        format!("{}-{}-{}-default", CLIENT, page, self.name())
+    }
+}
+
+/// The state of a play at one point in time. All reports of a play share
+/// its `play_id`, so later ones supersede earlier ones.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PlayReport {
+    pub track_id: String,
+    pub album_id: Option<i32>,
+    pub playlist_id: Option<String>,
+    pub from: String,
+    pub from_cache: bool,
+    pub play_id: String,
+    pub context: String,
+    pub context_item: Option<String>,
+    pub timestamp: String,
+    pub track_length_seconds: f64,
+    pub total_played_seconds: f64,
+    pub end_position_seconds: f64,
+}
+
+impl PlayReport {
+    /// A report of `track` after `played` seconds of listening, stopped at
+    /// `end_position` seconds into the track.
+    pub fn new(
+        track: &Track,
+        context: &PlayContext,
+        play_id: &str,
+        from_cache: bool,
+        played: f64,
+        end_position: f64,
+    ) -> Self {
+        Self {
+            track_id: track.id.to_string(),
+            album_id: track.albums.first().and_then(|album| album.id),
+            playlist_id: match context {
+                PlayContext::Playlist { .. } => context.item(),
+                _ => None,
+            },
+            from: context.from(),
+            from_cache,
+            play_id: play_id.to_string(),
+            context: context.name().to_string(),
+            context_item: context.item(),
+            timestamp: format_timestamp(SystemTime::now()),
+            track_length_seconds: track
+                .duration_ms
+                .map_or(0.0, |ms| ms as f64 / 1000.0),
+            total_played_seconds: played,
+            end_position_seconds: end_position,
+        }
+    }
+}
+
+/// Sends a batch of play reports.
+pub async fn send_plays(
+    client: &YandexMusicClient,
+    reports: &[PlayReport],
+) -> anyhow::Result<()> {
+    post_json::<serde_json::Value>(
+        client,
+        "/plays",
+        &[("client-now", format_timestamp(SystemTime::now()))],
+        &json!({ "plays": reports }),
+    )
+    .await?;
+
+    Ok(())
+}
@@ -0,0 +1,173 @@
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use serde::Deserialize;
+use serde_json::json;
+use yandex_music::{model::track_model::track::Track, YandexMusicClient};
+
+use super::{get, post_json};
+
+#[derive(Clone, Debug, Deserialize)]
+pub struct StationId {
+    #[serde(rename = "type")]
+    pub station_type: String,
+    pub tag: String,
+}
+
+impl StationId {
+    /// The `type:tag` form the rotor endpoints address stations by.
+    pub fn key(&self) -> String {
+        format!("{}:{}", self.station_type, self.tag)
+    }
+}
+
+#[derive(Clone, Debug, Deserialize)]
+pub struct Station {
+    pub id: StationId,
+    pub name: String,
+}
+
+impl Station {
+    /// The personalized "My Wave" station.
+    pub fn my_wave() -> Self {
+        Self {
+            id: StationId {
+                station_type: "user".to_string(),
+                tag: "onyourwave".to_string(),
+            },
+            name: "My Wave".to_string(),
+        }
+    }
+}
+
+#[derive(Deserialize)]
+struct StationEntry {
+    station: Station,
+}
+
+#[derive(Deserialize)]
+struct SequenceItem {
+    track: Option<Track>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct StationTracks {
+    batch_id: String,
+    #[serde(default)]
+    sequence: Vec<SequenceItem>,
+}
+
+/// A batch of tracks returned by a station. Feedback about these tracks is
+/// attributed to `batch_id`.
+pub struct StationBatch {
+    pub batch_id: String,
+    pub tracks: Vec<Track>,
+}
+
+/// Feedback that lets a station adapt its recommendations.
+pub enum RotorFeedback {
+    RadioStarted,
+    TrackStarted { track_id: String },
+    TrackFinished { track_id: String, played: f64 },
+    Skip { track_id: String, played: f64 },
+}
+
+impl RotorFeedback {
+    fn to_json(&self, station: &str) -> anyhow::Result<serde_json::Value> {
+        let timestamp =
+            SystemTime::now().duration_since(UNIX_EPOCH)?.as_secs_f64();
+
+        Ok(match self {
+            Self::RadioStarted => json!({
+                "type": "radioStarted",
+                "timestamp": timestamp,
+                "from": station,
+            }),
+            Self::TrackStarted { track_id } => json!({
+                "type": "trackStarted",
+                "timestamp": timestamp,
+                "trackId": track_id,
+            }),
+            Self::TrackFinished { track_id, played } => json!({
+                "type": "trackFinished",
+                "timestamp": timestamp,
+                "trackId": track_id,
+                "totalPlayedSeconds": played,
+            }),
+            Self::Skip { track_id, played } => json!({
+                "type": "skip",
+                "timestamp": timestamp,
+                "trackId": track_id,
+                "totalPlayedSeconds": played,
+            }),
+        })
+    }
+}
+
+/// Identifies a track in rotor feedback as `trackId:albumId`.
+pub fn feedback_track_id(track: &Track) -> String {
+    match track.albums.first().and_then(|album| album.id) {
+        Some(album_id) => format!("{}:{}", track.id, album_id),
+        None => track.id.to_string(),
+    }
+}
+
+/// Fetches the genre, mood and activity stations.
+pub async fn fetch_stations(
+    client: &YandexMusicClient,
+) -> anyhow::Result<Vec<Station>> {
+    let stations: Vec<StationEntry> =
+        get(client, "/rotor/stations/list", &[]).await?;
+
+    Ok(stations.into_iter().map(|entry| entry.station).collect())
+}
+
+/// Fetches the next batch of tracks of `station`, continuing after
+/// `last_track` if given.
+pub async fn fetch_station_tracks(
+    client: &YandexMusicClient,
+    station: &str,
+    last_track: Option<&str>,
+) -> anyhow::Result<StationBatch> {
+    let mut query = vec![("settings2", "true".to_string())];
+    if let Some(last_track) = last_track {
+        query.push(("queue", last_track.to_string()));
+    }
+
+    let tracks: StationTracks = get(
+        client,
+        &format!("/rotor/station/{}/tracks", station),
+        &query,
+    )
+    .await?;
+
+    Ok(StationBatch {
+        batch_id: tracks.batch_id,
+        tracks: tracks
+            .sequence
+            .into_iter()
+            .filter_map(|s| s.track)
+            .collect(),
+    })
+}
+
+pub async fn send_feedback(
+    client: &YandexMusicClient,
+    station: &str,
+    batch_id: Option<&str>,
+    feedback: &RotorFeedback,
+) -> anyhow::Result<()> {
+    let query = batch_id
+        .map(|batch_id| vec![("batch-id", batch_id.to_string())])
+        .unwrap_or_default();
+
+    post_json::<serde_json::Value>(
+        client,
+        &format!("/rotor/station/{}/feedback", station),
+        &query,
+        &feedback.to_json(station)?,
+    )
+    .await?;
+
+    Ok(())
+}
@@ -0,0 +1,89 @@
+use serde::Deserialize;
+use yandex_music::{model::track_model::track::Track, YandexMusicClient};
+
+use super::{
+    get,
+    library::{LibraryAlbum, LibraryArtist, Playlist},
+};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SearchKind {
+    Tracks,
+    Albums,
+    Artists,
+    Playlists,
+    Podcasts,
+}
+
+impl SearchKind {
+    pub const ALL: [Self; 5] = [
+        Self::Tracks,
+        Self::Albums,
+        Self::Artists,
+        Self::Playlists,
+        Self::Podcasts,
+    ];
+
+    pub fn title(&self) -> &'static str {
+        match self {
+            Self::Tracks => "Tracks",
+            Self::Albums => "Albums",
+            Self::Artists => "Artists",
+            Self::Playlists => "Playlists",
+            Self::Podcasts => "Podcasts",
+        }
+    }
+
+    fn query_type(&self) -> &'static str {
+        match self {
+            Self::Tracks => "track",
+            Self::Albums => "album",
+            Self::Artists => "artist",
+            Self::Playlists => "playlist",
+            Self::Podcasts => "podcast",
+        }
+    }
+}
+
+/// One page of search results of a single type.
+#[derive(Clone, Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SearchResults<T> {
+    #[serde(default)]
+    pub total: usize,
+    #[serde(default)]
+    pub per_page: usize,
+    #[serde(default = "Vec::new")]
+    pub results: Vec<T>,
+}
+
+/// Results of a search request. Only the field of the requested
+/// [`SearchKind`] is filled.
+#[derive(Clone, Debug, Deserialize)]
+pub struct SearchResponse {
+    pub tracks: Option<SearchResults<Track>>,
+    pub albums: Option<SearchResults<LibraryAlbum>>,
+    pub artists: Option<SearchResults<LibraryArtist>>,
+    pub playlists: Option<SearchResults<Playlist>>,
+    pub podcasts: Option<SearchResults<LibraryAlbum>>,
+}
+
+/// Searches the catalog for `text`. Pages are counted from zero.
+pub async fn search(
+    client: &YandexMusicClient,
+    text: &str,
+    kind: SearchKind,
+    page: usize,
+) -> anyhow::Result<SearchResponse> {
+    get(
+        client,
+        "/search",
+        &[
+            ("text", text.to_string()),
+            ("type", kind.query_type().to_string()),
+            ("page", page.to_string()),
+            ("nocorrect", "false".to_string()),
+        ],
+    )
+    .await
+}
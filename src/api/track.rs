@@ -0,0 +1,102 @@
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use base64::{engine::general_purpose::STANDARD, Engine};
+use hmac::{Hmac, Mac};
+use serde::Deserialize;
+use sha2::Sha256;
+use yandex_music::YandexMusicClient;
+
+use super::get;
+
+/// Key the official clients sign `get-file-info` and lyrics requests with.
+const SIGN_KEY: &[u8] = b"p93jhgh689SBReK6ghtw62";
+const CODECS: &[&str] = &[
+    "flac",
+    "aac",
+    "he-aac",
+    "mp3",
+    "flac-mp4",
+    "aac-mp4",
+    "he-aac-mp4",
+];
+const TRANSPORT: &str = "encraw";
+
+/// A direct link returned by the `get-file-info` endpoint. Files sent over
+/// the `encraw` transport are encrypted with `key`.
+#[derive(Clone, Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct FileInfo {
+    pub quality: String,
+    pub codec: String,
+    #[serde(default)]
+    pub bitrate: i32,
+    pub transport: String,
+    pub key: Option<String>,
+    pub url: String,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct FileInfoResponse {
+    download_info: FileInfo,
+}
+
+/// Signs the parameters of a request the way the official clients do.
+pub(super) fn sign(data: &str) -> String {
+    let mut mac = Hmac::<Sha256>::new_from_slice(SIGN_KEY)
+        .expect("HMAC accepts keys of any length");
+    mac.update(data.as_bytes());
+
+    STANDARD.encode(mac.finalize().into_bytes())
+}
+
+pub async fn fetch_file_info(
+    client: &YandexMusicClient,
+    track_id: i32,
+    quality: &str,
+) -> anyhow::Result<FileInfo> {
+    let timestamp = SystemTime::now().duration_since(UNIX_EPOCH)?.as_secs();
+    let sign = sign(&format!(
+        "{}{}{}{}{}",
+        timestamp,
+        track_id,
+        quality,
+        CODECS.concat(),
+        TRANSPORT
+    ));
+
+    let response: FileInfoResponse = get(
+        client,
+        "/get-file-info",
+        &[
+            ("ts", timestamp.to_string()),
+            ("trackId", track_id.to_string()),
+            ("quality", quality.to_string()),
+            ("codecs", CODECS.join(",")),
+            ("transports", TRANSPORT.to_string()),
+            ("sign", sign.trim_end_matches('=').to_string()),
+        ],
+    )
+    .await?;
+
+    Ok(response.download_info)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn signs_like_the_official_clients() {
+        let data = format!(
+            "{}{}{}{}{}",
+            1700000000,
+            123,
+            "lossless",
+            CODECS.concat(),
+            TRANSPORT
+        );
+
+        assert_eq!(sign(&data), "OP95r1NhhhNKJcLPc5mfl377HiYn9sqXs68rpkUSh2M=");
+    }
+}
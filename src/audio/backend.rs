@@ -1,23 +1,54 @@
 use std::{
+    collections::HashSet,
+    io::{Read, Seek},
     sync::{
         atomic::{AtomicBool, Ordering},
-        Arc,
+        Arc, Mutex,
     },
     thread,
     time::Duration,
 };
 
 use crate::{
-    audio::playback::utils::fetch_track_url, event::events::Event,
-    stream::streamer::AudioStreamer, utils::random,
+    api::{
+        library::{fetch_disliked_track_ids, fetch_uid},
+        plays::{PlayContext, PlayReport},
+    },
+    audio::playback::utils::{fetch_track_url, TrackUrl},
+    config::CONFIG,
+    event::events::Event,
+    http::{build_api_client, build_client},
+    metrics::METRICS,
+    offline::{
+        download::download_collections,
+        store::{CollectionSource, OfflineStore},
+        sync::{PendingAction, PendingPlays, PendingQueue},
+    },
+    profile::{Profile, ProfileSettings},
+    stream::{
+        cache::AudioCache,
+        prefetch::Prefetcher,
+        source::{FileSource, TrackLink},
+        streamer::AudioStreamer,
+    },
+    utils::random,
 };
+use color_eyre::eyre::eyre;
 use flume::Sender;
-use rodio::{cpal::StreamConfig, Decoder, OutputStream, Sink, Source};
-use tracing::info;
+use rodio::{
+    cpal::StreamConfig, decoder::Mp4Type, Decoder, OutputStream, Sink, Source,
+};
+use tokio_util::sync::CancellationToken;
+use tracing::{info, warn};
 use yandex_music::{model::track_model::track::Track, YandexMusicClient};
 
 use super::{
-    enums::RepeatMode, playback::player::init, progress::TrackProgress,
+    enums::{PlaybackState, RepeatMode},
+    episodes::EpisodeStore,
+    playback::player::init,
+    plays::PlaySession,
+    progress::TrackProgress,
+    radio::{Batch, Radio},
 };
 
 #[allow(dead_code)]
@@ -26,11 +57,31 @@ pub struct AudioPlayer {
     sink: Arc<Sink>,
     stream_config: StreamConfig,
     client: Arc<YandexMusicClient>,
+    http: reqwest::Client,
+    cache: Arc<AudioCache>,
+    offline_store: Arc<OfflineStore>,
+    pending: Arc<PendingQueue>,
+    plays: Arc<PendingPlays>,
+    play: Option<PlaySession>,
+    episodes: Arc<EpisodeStore>,
+    /// The episode being played, whose position is saved when it stops.
+    episode: Option<i32>,
     event_tx: Sender<Event>,
+    /// Where the playback thread reports finished tracks. Replaced along
+    /// with `event_tx` when switching profiles.
+    ended_tx: Arc<Mutex<Sender<Event>>>,
+    stream_cancel: Option<CancellationToken>,
+    prefetcher: Prefetcher,
+    disliked: HashSet<i32>,
 
     pub track: Option<Track>,
     pub tracks: Vec<Track>,
     pub track_index: usize,
+    pub liked: Vec<Track>,
+    pub context: PlayContext,
+    /// Whether the queue holds podcast episodes or audiobook chapters, which
+    /// are resumed and played in order.
+    pub is_long_form: bool,
     pub volume: u8,
 
     pub track_progress: Arc<TrackProgress>,
@@ -38,40 +89,133 @@ pub struct AudioPlayer {
     pub is_shuffled: bool,
     pub is_muted: bool,
     pub repeat_mode: RepeatMode,
+    pub is_offline: bool,
+    pub is_data_saver: bool,
+    pub radio: Option<Radio>,
+}
+
+/// Everything that belongs to the account of a profile, which is replaced
+/// when switching profiles while the audio output stays open.
+struct Account {
+    client: Arc<YandexMusicClient>,
+    cache: Arc<AudioCache>,
+    offline_store: Arc<OfflineStore>,
+    pending: Arc<PendingQueue>,
+    plays: Arc<PendingPlays>,
+    episodes: Arc<EpisodeStore>,
+    prefetcher: Prefetcher,
+}
+
+impl Account {
+    fn open(
+        profile: &Profile,
+        http: &reqwest::Client,
+        is_offline: bool,
+    ) -> color_eyre::Result<Self> {
+        let token = match profile.token() {
+            Some(token) => token,
+            None if is_offline => String::new(),
+            None => {
+                return Err(eyre!(
+                    "No token for profile {:?}, set YANDEX_MUSIC_TOKEN or \
+                     pass --token",
+                    profile.name
+                ))
+            }
+        };
+        let mut client = YandexMusicClient::new(&token);
+        client.client = build_api_client(&token)?;
+        let client = Arc::new(client);
+
+        let directory = profile.directory();
+        let cache = Arc::new(AudioCache::new(
+            directory.join("cache"),
+            CONFIG.cache_size,
+        )?);
+        let offline_directory = directory.join("offline");
+        let offline_store =
+            Arc::new(OfflineStore::open(offline_directory.clone())?);
+        let pending = Arc::new(PendingQueue::open(
+            offline_directory.join("pending.json"),
+        )?);
+        let plays =
+            Arc::new(PendingPlays::open(offline_directory.join("plays.json"))?);
+        let episodes =
+            Arc::new(EpisodeStore::open(directory.join("episodes.json"))?);
+        let prefetcher =
+            Prefetcher::new(client.clone(), http.clone(), cache.clone());
+
+        Ok(Self {
+            client,
+            cache,
+            offline_store,
+            pending,
+            plays,
+            episodes,
+            prefetcher,
+        })
+    }
 }
 
 impl AudioPlayer {
     pub async fn new(
         event_tx: flume::Sender<Event>,
+        profile: &Profile,
+        is_offline: bool,
     ) -> color_eyre::Result<Self> {
-        let client = Arc::new(YandexMusicClient::new(
-            &std::env::var("YANDEX_MUSIC_TOKEN")
-                .expect("YANDEX_MUSIC_TOKEN environment variable must be set"),
-        ));
         let (stream, sink, stream_config) = init()?;
+        let http = build_client()?;
+        let Account {
+            client,
+            cache,
+            offline_store,
+            pending,
+            plays,
+            episodes,
+            prefetcher,
+        } = Account::open(profile, &http, is_offline)?;
 
         let player = Self {
             stream,
             sink: Arc::new(sink),
             stream_config,
             client,
+            http,
+            cache,
+            offline_store,
+            pending,
+            plays,
+            play: None,
+            episodes,
+            episode: None,
+            ended_tx: Arc::new(Mutex::new(event_tx.clone())),
             event_tx,
+            stream_cancel: None,
+            prefetcher,
+            disliked: HashSet::new(),
 
             track: None,
             tracks: Vec::new(),
             track_index: 0,
-            volume: 100,
+            liked: Vec::new(),
+            context: PlayContext::default(),
+            is_long_form: false,
+            volume: profile.settings.volume,
 
             track_progress: Arc::new(TrackProgress::default()),
             is_playing: Arc::new(AtomicBool::new(false)),
             is_shuffled: false,
             is_muted: false,
             repeat_mode: RepeatMode::None,
+            is_offline,
+            is_data_saver: profile.settings.data_saver,
+            radio: None,
         };
+        player.sink.set_volume(player.volume as f32 / 100.0);
 
         let progress = player.track_progress.clone();
         let sink = player.sink.clone();
-        let event_tx = player.event_tx.clone();
+        let ended_tx = player.ended_tx.clone();
         let playing = player.is_playing.clone();
         thread::spawn(move || loop {
             progress.set_current_position(sink.get_pos());
@@ -79,7 +223,8 @@ impl AudioPlayer {
 
             if is_playing && sink.empty() {
                 playing.store(false, Ordering::Relaxed);
-                let _ = event_tx.send(Event::TrackEnded);
+                progress.set_state(PlaybackState::Stopped);
+                let _ = ended_tx.lock().unwrap().send(Event::TrackEnded);
             }
 
             thread::sleep(Duration::from_secs(1));
@@ -89,11 +234,111 @@ impl AudioPlayer {
     }
 
     pub async fn init(&mut self) -> color_eyre::Result<()> {
+        if self.is_offline {
+            self.tracks = self.offline_store.tracks();
+            let liked_ids = self
+                .offline_store
+                .collections()
+                .into_iter()
+                .find(|c| c.source == CollectionSource::Liked)
+                .map(|c| c.track_ids)
+                .unwrap_or_default();
+            self.liked = self
+                .tracks
+                .iter()
+                .filter(|t| liked_ids.contains(&t.id))
+                .cloned()
+                .collect();
+            return Ok(());
+        }
+
         YandexMusicClient::fetch_tracks(self).await;
+        self.liked = self.tracks.clone();
+        self.context = PlayContext::Liked;
+
+        let client = &self.client;
+        let disliked = async {
+            let uid = fetch_uid(client).await?;
+            fetch_disliked_track_ids(client, uid).await
+        };
+        match disliked.await {
+            Ok(track_ids) => self.disliked = track_ids.into_iter().collect(),
+            Err(err) => warn!("Failed to fetch disliked tracks: {:#}", err),
+        }
+
+        let client = self.client.clone();
+        let http = self.http.clone();
+        let offline_store = self.offline_store.clone();
+        let pending = self.pending.clone();
+        let plays = self.plays.clone();
+        tokio::spawn(async move {
+            if let Err(err) = pending.flush(&client).await {
+                warn!("Failed to sync offline changes: {}", err);
+            }
+            if let Err(err) = plays.flush(&client).await {
+                warn!("Failed to send play reports: {:#}", err);
+            }
+            if let Err(err) =
+                download_collections(client, http, offline_store).await
+            {
+                warn!("Failed to download offline collections: {}", err);
+            }
+        });
+
+        Ok(())
+    }
+
+    /// Switches to the account of another profile. The playing track is
+    /// stopped and the queue cleared, but the audio output is kept open.
+    /// Events are sent to `event_tx` from then on.
+    pub fn switch_profile(
+        &mut self,
+        profile: &Profile,
+        event_tx: Sender<Event>,
+    ) -> color_eyre::Result<()> {
+        let account = Account::open(profile, &self.http, self.is_offline)?;
+
+        let position = self.sink.get_pos();
+        self.end_play(position);
+        self.save_episode_position();
+        self.stop_track();
+        self.prefetcher.clear();
+
+        self.client = account.client;
+        self.cache = account.cache;
+        self.offline_store = account.offline_store;
+        self.pending = account.pending;
+        self.plays = account.plays;
+        self.episodes = account.episodes;
+        self.prefetcher = account.prefetcher;
+        *self.ended_tx.lock().unwrap() = event_tx.clone();
+        self.event_tx = event_tx;
+
+        self.episode = None;
+        self.radio = None;
+        self.disliked.clear();
+        self.track = None;
+        self.tracks.clear();
+        self.track_index = 0;
+        self.liked.clear();
+        self.context = PlayContext::default();
+        self.is_long_form = false;
+        self.track_progress.set_current_position(Duration::ZERO);
+        self.track_progress.set_total_duration(Duration::ZERO);
+        self.set_volume(profile.settings.volume);
+        self.is_data_saver = profile.settings.data_saver;
 
         Ok(())
     }
 
+    /// The settings to remember for the current profile.
+    pub fn settings(&self) -> ProfileSettings {
+        ProfileSettings {
+            volume: self.volume,
+            data_saver: self.is_data_saver,
+        }
+    }
+
     pub fn previous_track(&mut self) {
         if self.track_index != 0 {
             self.track_index -= 1;
@@ -102,13 +347,23 @@ impl AudioPlayer {
     }
 
     pub fn next_track(&mut self) {
-        self.track_index = if self.is_shuffled {
-            random(0, self.tracks.len() as i32 - 1) as usize
-        } else if self.track_index < self.tracks.len() - 1 {
-            self.track_index + 1
-        } else {
-            0
-        };
+        if self.tracks.is_empty() {
+            return;
+        }
+
+        let candidates = (0..self.tracks.len())
+            .filter(|&i| {
+                i != self.track_index
+                    && !self.disliked.contains(&self.tracks[i].id)
+            })
+            .collect::<Vec<_>>();
+
+        self.track_index = next_index(
+            self.track_index,
+            self.tracks.len(),
+            &candidates,
+            self.is_shuffled && !self.is_long_form,
+        );
         self.track = Some(self.tracks[self.track_index].clone());
     }
 
@@ -120,6 +375,99 @@ impl AudioPlayer {
         }
     }
 
+    /// Replaces the queue with `tracks` started from `context` and starts
+    /// playing the one at `index`. This ends the radio if it is playing.
+    pub async fn play_tracks(
+        &mut self,
+        tracks: Vec<Track>,
+        index: usize,
+        context: PlayContext,
+    ) {
+        self.replace_queue(tracks, index, context, false).await
+    }
+
+    /// Like [`Self::play_tracks`], but for podcast episodes or audiobook
+    /// chapters, which resume where they were left off and continue with
+    /// the next unplayed one in order.
+    pub async fn play_episodes(
+        &mut self,
+        tracks: Vec<Track>,
+        index: usize,
+        context: PlayContext,
+    ) {
+        self.replace_queue(tracks, index, context, true).await
+    }
+
+    async fn replace_queue(
+        &mut self,
+        tracks: Vec<Track>,
+        index: usize,
+        context: PlayContext,
+        is_long_form: bool,
+    ) {
+        let played = self.played_seconds();
+        if let Some(mut radio) = self.radio.take() {
+            radio.track_skipped(played);
+        }
+        self.tracks = tracks;
+        self.context = context;
+        self.is_long_form = is_long_form;
+        self.play_nth(index).await
+    }
+
+    /// Starts an endless rotor station. The queue is replaced once the first
+    /// batch of tracks arrives.
+    pub fn start_radio(&mut self, station: String, name: String) {
+        let played = self.played_seconds();
+        if let Some(radio) = self.radio.as_mut() {
+            radio.track_skipped(played);
+        }
+
+        let mut radio = Radio::new(self.client.clone(), station, name);
+        radio.start(self.event_tx.clone());
+        self.radio = Some(radio);
+    }
+
+    pub async fn on_radio_loaded(
+        &mut self,
+        station: String,
+        batch_id: Option<String>,
+        tracks: Vec<Track>,
+    ) {
+        let Some(radio) =
+            self.radio.as_mut().filter(|radio| radio.station == station)
+        else {
+            return;
+        };
+
+        let tracks = tracks
+            .into_iter()
+            .filter(|track| !self.disliked.contains(&track.id))
+            .collect::<Vec<_>>();
+        match radio.on_loaded(batch_id, &tracks) {
+            Batch::First => {
+                self.tracks = tracks;
+                self.context = PlayContext::Radio { station };
+                self.is_long_form = false;
+                self.play_nth(0).await;
+            }
+            Batch::Next => self.enqueue(tracks),
+            Batch::Failed => {
+                warn!(
+                    "Stopped radio {}: no playable tracks could be loaded",
+                    radio.name
+                );
+                self.radio = None;
+            }
+        }
+    }
+
+    /// Adds `tracks` to the end of the queue.
+    pub fn enqueue(&mut self, tracks: Vec<Track>) {
+        self.tracks.extend(tracks);
+        self.prefetch_upcoming();
+    }
+
     pub async fn play_previous(&mut self) {
         self.previous_track(); // todo: keep track of track history and fetch from there
         self.play_track(self.track.as_ref().unwrap().id).await
@@ -131,44 +479,215 @@ impl AudioPlayer {
     }
 
     pub async fn play_track(&mut self, track_id: i32) {
+        let played = self.played_seconds();
+        if let Some(radio) = self.radio.as_mut() {
+            radio.track_skipped(played);
+            if let Some(track) = self.track.as_ref() {
+                radio.track_started(track);
+            }
+            radio.refill(self.event_tx.clone(), &self.tracks, self.track_index);
+        }
+        self.save_episode_position();
+        self.end_play(self.sink.get_pos());
         self.stop_track();
 
+        self.episode = self.is_long_form.then_some(track_id);
+        let start_at = self
+            .episode
+            .and_then(|id| self.episodes.resume_position(id));
+
+        let cancel = CancellationToken::new();
+        self.stream_cancel = Some(cancel.clone());
+        let data_saver = self.is_data_saver;
+        let fetch_amount = if data_saver { 64 * 1024 } else { 256 * 1024 };
+
+        if let Some((file, codec, bitrate)) =
+            self.offline_store.open_audio(track_id)
+        {
+            info!("playing track {} from offline store", track_id);
+            let result = match FileSource::new(file) {
+                Ok(source) => {
+                    start_playback(
+                        &self.sink,
+                        &self.track_progress,
+                        AudioStreamer::from_source(
+                            source,
+                            fetch_amount,
+                            cancel.clone(),
+                        ),
+                        codec,
+                        bitrate,
+                        start_at,
+                        &cancel,
+                    )
+                    .await
+                }
+                Err(err) => Err(err.into()),
+            };
+            if let Err(err) = result {
+                warn!("Failed to play track {}: {:#}", track_id, err);
+                return;
+            }
+            self.is_playing.store(true, Ordering::Relaxed);
+            self.track_progress.set_state(PlaybackState::Playing);
+            self.start_play(track_id, true, start_at);
+            return;
+        } else if self.is_offline {
+            warn!("track {} is not available offline", track_id);
+            return;
+        }
+
+        let prefetched = self.prefetcher.take(track_id);
+        self.prefetch_upcoming();
+
         let client = self.client.clone();
+        let http = self.http.clone();
+        let cache = self.cache.clone();
         let sink = self.sink.clone();
         let track_progress = self.track_progress.clone();
         let playing = self.is_playing.clone();
+        let event_tx = self.event_tx.clone();
         tokio::spawn(async move {
-            let (url, codec, bitrate) =
-                fetch_track_url(&client, track_id).await;
-            let stream = AudioStreamer::new(url, 256 * 1024).unwrap();
-            let total_bytes = stream.total_bytes;
-            let decoder = if codec == "mp3" {
-                Decoder::new_mp3(stream)
-            } else {
-                Decoder::new_aac(stream)
-            }
-            .unwrap();
+            let TrackUrl {
+                url,
+                codec,
+                bitrate,
+                key: track_key,
+            } = match &prefetched {
+                Some(prefetched) => TrackUrl {
+                    url: prefetched.url.clone(),
+                    codec: prefetched.codec.clone(),
+                    bitrate: prefetched.bitrate,
+                    key: prefetched.key,
+                },
+                None => tokio::select! {
+                    info = fetch_track_url(&client, &http, track_id, data_saver) => {
+                        match info {
+                            Ok(info) => info,
+                            Err(err) => {
+                                warn!(
+                                    "Failed to play track {}: {:#}",
+                                    track_id, err
+                                );
+                                return;
+                            }
+                        }
+                    }
+                    _ = cancel.cancelled() => return,
+                },
+            };
+            let key = AudioCache::key(track_id, &codec, bitrate);
 
-            if let Some(total) = decoder.total_duration() {
-                track_progress.set_total_duration(total);
+            let cached = cache.get(&key);
+            let from_cache = cached.is_some();
+            let stream = if let Some(file) = cached {
+                info!("playing track {} from cache", track_id);
+                FileSource::new(file)
+                    .map(|source| {
+                        AudioStreamer::from_source(
+                            source,
+                            fetch_amount,
+                            cancel.clone(),
+                        )
+                    })
+                    .map_err(Into::into)
             } else {
-                info!("total bytes: {}", total_bytes);
-                info!("bitrate: {}", bitrate);
-                track_progress.set_total_duration(Duration::from_secs_f64(
-                    (total_bytes * 8) as f64 / (bitrate * 1000) as f64,
-                ));
+                let link = TrackLink {
+                    client,
+                    track_id,
+                    codec: codec.clone(),
+                    bitrate,
+                    encrypted: track_key.is_some(),
+                };
+                match prefetched {
+                    Some(prefetched) => Ok(AudioStreamer::with_prefetched(
+                        http,
+                        prefetched,
+                        fetch_amount,
+                        Some((cache, key)),
+                        Some(link),
+                        cancel.clone(),
+                    )),
+                    None => {
+                        AudioStreamer::new(
+                            http,
+                            url,
+                            fetch_amount,
+                            Some((cache, key)),
+                            track_key,
+                            Some(link),
+                            cancel.clone(),
+                        )
+                        .await
+                    }
+                }
+            };
+            let result = match stream {
+                Ok(stream) => {
+                    start_playback(
+                        &sink,
+                        &track_progress,
+                        stream,
+                        codec,
+                        bitrate,
+                        start_at,
+                        &cancel,
+                    )
+                    .await
+                }
+                Err(err) => Err(err),
+            };
+
+            if cancel.is_cancelled() {
+                return;
+            }
+            if let Err(err) = result {
+                warn!("Failed to play track {}: {:#}", track_id, err);
+                return;
             }
-            sink.append(decoder);
             playing.store(true, Ordering::Relaxed);
+            track_progress.set_state(PlaybackState::Playing);
+            // Whether the track came from the cache is only known now, so
+            // the play is reported once it has started.
+            let _ = event_tx.send(Event::PlayStarted {
+                track_id,
+                from_cache,
+                start_at,
+            });
         });
     }
 
     pub fn stop_track(&mut self) {
+        if let Some(cancel) = self.stream_cancel.take() {
+            cancel.cancel();
+        }
         self.is_playing.store(false, Ordering::Relaxed);
+        self.track_progress.set_state(PlaybackState::Stopped);
         self.sink.stop();
     }
 
     pub async fn on_track_end(&mut self) {
+        let played = self.played_seconds();
+        if let Some(radio) = self.radio.as_mut() {
+            radio.track_finished(played);
+        }
+        let length = self
+            .track
+            .as_ref()
+            .and_then(|track| track.duration_ms)
+            .map(Duration::from_millis);
+        self.end_play(length.unwrap_or_else(|| self.sink.get_pos()));
+
+        if let Some(track_id) = self.episode.take() {
+            if let Err(err) = self.episodes.set_played(track_id, true) {
+                warn!("Failed to mark episode as played: {}", err);
+            }
+        }
+        if self.is_long_form {
+            self.play_next_episode().await;
+            return;
+        }
+
         match self.repeat_mode {
             RepeatMode::None => self.play_next().await,
             RepeatMode::Single => {
@@ -186,16 +705,83 @@ impl AudioPlayer {
         }
     }
 
+    /// Continues with the next unplayed episode after the current one, or
+    /// stops at the end of the list.
+    async fn play_next_episode(&mut self) {
+        if self.repeat_mode == RepeatMode::Single {
+            return self.play_nth(self.track_index).await;
+        }
+
+        let next = (self.track_index + 1..self.tracks.len())
+            .find(|&i| !self.episodes.is_played(self.tracks[i].id));
+        if let Some(index) = next {
+            self.play_nth(index).await;
+        }
+    }
+
+    /// Saves where the playing episode was left off, so that it can be
+    /// resumed later. Nothing is saved while the episode is still loading or
+    /// failed to start, since the position would then be 0 and overwrite
+    /// the one it was resumed from.
+    pub fn save_episode_position(&self) {
+        let Some(track_id) = self.episode else {
+            return;
+        };
+        if self.track_progress.get_state() == PlaybackState::Stopped {
+            return;
+        }
+
+        let position = self.sink.get_pos();
+        let length = self
+            .track
+            .as_ref()
+            .and_then(|track| track.duration_ms)
+            .map(Duration::from_millis);
+        if let Err(err) = self.episodes.set_position(track_id, position, length)
+        {
+            warn!("Failed to save episode position: {}", err);
+        }
+    }
+
+    /// Marks an episode as played, or as not played if it was already.
+    pub fn toggle_played(&mut self, track_id: i32) {
+        let is_played = !self.episodes.is_played(track_id);
+        if is_played && self.episode == Some(track_id) {
+            self.episode = None;
+        }
+        if let Err(err) = self.episodes.set_played(track_id, is_played) {
+            warn!("Failed to mark episode as played: {}", err);
+        }
+    }
+
+    pub fn episodes(&self) -> &EpisodeStore {
+        &self.episodes
+    }
+
     pub fn play_pause(&mut self) {
         let is_paused = self.sink.is_paused();
+        let position = self.sink.get_pos();
         if is_paused {
             self.sink.play();
+            if let Some(play) = self.play.as_mut() {
+                play.resume(position);
+            }
         } else {
             self.sink.pause();
-            self.track_progress
-                .set_current_position(self.sink.get_pos());
+            self.track_progress.set_current_position(position);
+            if let Some(play) = self.play.as_mut() {
+                play.pause(position);
+                let report = play.report(position);
+                self.report_play(report);
+            }
+            self.save_episode_position();
         }
         self.is_playing.store(is_paused, Ordering::Relaxed);
+        self.track_progress.set_state(if is_paused {
+            PlaybackState::Playing
+        } else {
+            PlaybackState::Paused
+        });
     }
 
     pub fn set_volume(&mut self, volume: u8) {
@@ -217,15 +803,30 @@ impl AudioPlayer {
     }
 
     pub fn seek_backwards(&mut self, seconds: u64) {
-        self.sink
-            .try_seek(self.sink.get_pos() - Duration::from_secs(seconds))
-            .unwrap();
+        self.seek(
+            self.sink
+                .get_pos()
+                .saturating_sub(Duration::from_secs(seconds)),
+        );
     }
 
     pub fn seek_forwards(&mut self, seconds: u64) {
-        self.sink
-            .try_seek(self.sink.get_pos() + Duration::from_secs(seconds))
-            .unwrap();
+        self.seek(self.sink.get_pos() + Duration::from_secs(seconds));
+    }
+
+    fn seek(&mut self, position: Duration) {
+        let from = self.sink.get_pos();
+        if let Err(err) = self.sink.try_seek(position) {
+            warn!("Failed to seek: {}", err);
+            return;
+        }
+
+        if let Some(play) = self.play.as_mut() {
+            let report = play.report(from);
+            play.seek(from, position);
+            self.report_play(report);
+        }
+        self.save_episode_position();
     }
 
     pub fn toggle_repeat_mode(&mut self) {
@@ -236,6 +837,7 @@ impl AudioPlayer {
         };
 
         self.repeat_mode = mode;
+        self.prefetch_upcoming();
     }
 
     pub fn toggle_mute(&mut self) {
@@ -249,9 +851,288 @@ impl AudioPlayer {
 
     pub fn toggle_shuffling(&mut self) {
         self.is_shuffled = !self.is_shuffled;
+        self.prefetch_upcoming();
+    }
+
+    /// Switches to the lowest available bitrate and stops prefetching to
+    /// reduce data usage. Applies from the next track on.
+    pub fn toggle_data_saver(&mut self) {
+        self.is_data_saver = !self.is_data_saver;
+        self.prefetch_upcoming();
+    }
+
+    /// Starts prefetching the tracks that follow the current one. Nothing is
+    /// prefetched when the next track can't be known in advance.
+    fn prefetch_upcoming(&mut self) {
+        if self.is_offline
+            || self.is_data_saver
+            || self.is_shuffled
+            || self.repeat_mode == RepeatMode::Single
+            || self.track.is_none()
+            || self.tracks.is_empty()
+        {
+            self.prefetcher.clear();
+            return;
+        }
+
+        let track_ids = (1..=CONFIG.prefetch_tracks)
+            .map(|offset| {
+                self.tracks[(self.track_index + offset) % self.tracks.len()].id
+            })
+            .filter(|track_id| !self.offline_store.contains(*track_id))
+            .collect();
+        self.prefetcher.prefetch(track_ids);
+    }
+
+    pub fn is_liked(&self, track_id: i32) -> bool {
+        self.liked.iter().any(|track| track.id == track_id)
+    }
+
+    /// Likes the playing track, or removes it from the liked tracks if it
+    /// is liked already.
+    pub fn toggle_like(&mut self) {
+        let Some(track) = self.track.clone() else {
+            return;
+        };
+
+        let track_id = track.id;
+        if self.is_liked(track_id) {
+            self.liked.retain(|t| t.id != track_id);
+            self.sync(PendingAction::Unlike { track_id });
+        } else {
+            self.disliked.remove(&track_id);
+            self.liked.insert(0, track);
+            self.sync(PendingAction::Like { track_id });
+        }
+    }
+
+    /// Dislikes the playing track and skips it, or removes the dislike if it
+    /// is disliked already. Disliked tracks are left out of shuffles and
+    /// radio stations.
+    pub async fn toggle_dislike(&mut self) {
+        let Some(track_id) = self.track.as_ref().map(|t| t.id) else {
+            return;
+        };
+
+        if self.disliked.remove(&track_id) {
+            self.sync(PendingAction::Undislike { track_id });
+            return;
+        }
+
+        self.disliked.insert(track_id);
+        self.liked.retain(|t| t.id != track_id);
+        self.sync(PendingAction::Dislike { track_id });
+        self.play_next().await;
+    }
+
+    /// Queues an account change and sends it unless playing offline, in
+    /// which case it is sent on the next start with a connection.
+    pub fn sync(&self, action: PendingAction) {
+        if let Err(err) = self.pending.push(action) {
+            warn!("Failed to queue account change: {}", err);
+            return;
+        }
+        if self.is_offline {
+            return;
+        }
+
+        let client = self.client.clone();
+        let pending = self.pending.clone();
+        tokio::spawn(async move {
+            if let Err(err) = pending.flush(&client).await {
+                warn!("Failed to sync account changes: {}", err);
+            }
+        });
+    }
+
+    /// Starts reporting a play of the current track from `start_at`, unless
+    /// it is not the one being played.
+    pub fn start_play(
+        &mut self,
+        track_id: i32,
+        from_cache: bool,
+        start_at: Option<Duration>,
+    ) {
+        let Some(track) = self.track.clone().filter(|t| t.id == track_id)
+        else {
+            return;
+        };
+
+        let start_at = start_at.unwrap_or_default();
+        let play =
+            PlaySession::new(track, self.context.clone(), from_cache, start_at);
+        self.report_play(play.report(start_at));
+        self.play = Some(play);
+    }
+
+    /// Reports the end of the current play at `position`.
+    fn end_play(&mut self, position: Duration) {
+        if let Some(play) = self.play.take() {
+            self.report_play(play.report(position));
+        }
+    }
+
+    /// Queues a play report, which is sent with the next batch unless
+    /// playing offline.
+    fn report_play(&self, report: PlayReport) {
+        if let Err(err) = self.plays.push(report) {
+            warn!("Failed to queue play report: {}", err);
+            return;
+        }
+        if !self.is_offline {
+            self.plays.schedule_flush(self.client.clone());
+        }
+    }
+
+    fn played_seconds(&self) -> f64 {
+        self.track_progress.get_progress().0.as_secs_f64()
+    }
+
+    /// The exact playback position, which `track_progress` only follows
+    /// once per second.
+    pub fn position(&self) -> Duration {
+        self.sink.get_pos()
+    }
+
+    pub fn client(&self) -> Arc<YandexMusicClient> {
+        self.client.clone()
+    }
+
+    /// The client for requests that must not carry the token.
+    pub fn http(&self) -> reqwest::Client {
+        self.http.clone()
+    }
+
+    pub fn offline_store(&self) -> &OfflineStore {
+        &self.offline_store
+    }
+
+    /// Collections that are marked for offline playback.
+    pub fn offline_collections(&self) -> Vec<CollectionSource> {
+        self.offline_store
+            .collections()
+            .into_iter()
+            .map(|collection| collection.source)
+            .collect()
+    }
+
+    /// Marks a collection for offline playback and starts downloading it,
+    /// or removes it from the offline store if it is already marked.
+    pub fn toggle_offline(&mut self, source: CollectionSource, title: String) {
+        let result = if self.offline_store.is_marked(&source) {
+            self.offline_store.unmark(&source)
+        } else {
+            self.offline_store.mark(source, title)
+        };
+
+        if let Err(err) = result {
+            warn!("Failed to update offline collections: {}", err);
+            return;
+        }
+
+        if !self.is_offline {
+            let client = self.client.clone();
+            let http = self.http.clone();
+            let offline_store = self.offline_store.clone();
+            tokio::spawn(async move {
+                if let Err(err) =
+                    download_collections(client, http, offline_store).await
+                {
+                    warn!("Failed to download offline collections: {}", err);
+                }
+            });
+        }
     }
 }
 
+fn open_decoder<R>(reader: R, codec: &str) -> anyhow::Result<Decoder<R>>
+where
+    R: Read + Seek + Send + Sync + 'static,
+{
+    Ok(match codec {
+        "mp3" => Decoder::new_mp3(reader),
+        "flac" => Decoder::new_flac(reader),
+        "flac-mp4" | "aac-mp4" | "he-aac-mp4" => {
+            Decoder::new_mp4(reader, Mp4Type::Mp4)
+        }
+        _ => Decoder::new_aac(reader),
+    }?)
+}
+
+/// The index of the track that follows `current` in a queue of `len`
+/// tracks.
+///
+/// Shuffling picks one of the `candidates`, which are the other tracks that
+/// aren't disliked. If there are none, it picks any track of the queue, so
+/// a queue of one track repeats it.
+fn next_index(
+    current: usize,
+    len: usize,
+    candidates: &[usize],
+    is_shuffled: bool,
+) -> usize {
+    if is_shuffled && !candidates.is_empty() {
+        candidates[random(0, candidates.len() as i32) as usize]
+    } else if is_shuffled {
+        random(0, len as i32) as usize
+    } else if current + 1 < len {
+        current + 1
+    } else {
+        0
+    }
+}
+
+/// Opens the decoder off the async runtime, since probing the format
+/// blocks on the stream, and queues it unless playback was cancelled in the
+/// meantime.
+async fn start_playback(
+    sink: &Sink,
+    track_progress: &TrackProgress,
+    stream: AudioStreamer,
+    codec: String,
+    bitrate: i32,
+    start_at: Option<Duration>,
+    cancel: &CancellationToken,
+) -> anyhow::Result<()> {
+    let total_bytes = stream.total_bytes;
+    let format = codec.clone();
+    let decoder =
+        tokio::task::spawn_blocking(move || open_decoder(stream, &codec))
+            .await??;
+    if !cancel.is_cancelled() {
+        METRICS.set_format(format, bitrate);
+        append_source(sink, track_progress, decoder, bitrate, total_bytes);
+        if let Some(position) = start_at {
+            if let Err(err) = sink.try_seek(position) {
+                warn!("Failed to resume at {:?}: {}", position, err);
+            }
+        }
+    }
+
+    Ok(())
+}
+
+fn append_source<R>(
+    sink: &Sink,
+    track_progress: &TrackProgress,
+    decoder: Decoder<R>,
+    bitrate: i32,
+    total_bytes: Option<u64>,
+) where
+    R: Read + Seek + Send + Sync + 'static,
+{
+    if let Some(total) = decoder.total_duration() {
+        track_progress.set_total_duration(total);
+    } else if let Some(total_bytes) = total_bytes.filter(|_| bitrate > 0) {
+        info!("total bytes: {}", total_bytes);
+        info!("bitrate: {}", bitrate);
+        track_progress.set_total_duration(Duration::from_secs_f64(
+            (total_bytes * 8) as f64 / (bitrate * 1000) as f64,
+        ));
+    }
+    sink.append(decoder);
+}
+
 trait Player {
     async fn fetch_tracks(player: &mut AudioPlayer);
 }
@@ -267,3 +1148,28 @@ impl Player for YandexMusicClient {
         player.tracks = tracks;
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn shuffles_a_queue_of_one_track() {
+        assert_eq!(next_index(0, 1, &[], true), 0);
+    }
+
+    #[test]
+    fn shuffles_between_the_candidates() {
+        assert_eq!(next_index(0, 2, &[1], true), 1);
+        for _ in 0..100 {
+            assert!([1, 3].contains(&next_index(0, 4, &[1, 3], true)));
+        }
+    }
+
+    #[test]
+    fn plays_the_queue_in_order_and_wraps_around() {
+        assert_eq!(next_index(0, 3, &[1, 2], false), 1);
+        assert_eq!(next_index(2, 3, &[0, 1], false), 0);
+        assert_eq!(next_index(0, 1, &[], false), 0);
+    }
+}
@@ -4,3 +4,29 @@ pub enum RepeatMode {
     Single,
     All,
 }
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum PlaybackState {
+    #[default]
+    Stopped,
+    Playing,
+    Paused,
+}
+
+impl PlaybackState {
+    pub(crate) fn from_u8(value: u8) -> Self {
+        match value {
+            1 => PlaybackState::Playing,
+            2 => PlaybackState::Paused,
+            _ => PlaybackState::Stopped,
+        }
+    }
+
+    pub(crate) fn as_u8(self) -> u8 {
+        match self {
+            PlaybackState::Stopped => 0,
+            PlaybackState::Playing => 1,
+            PlaybackState::Paused => 2,
+        }
+    }
+}
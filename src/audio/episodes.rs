@@ -0,0 +1,165 @@
+use std::{
+    collections::HashMap, fs, io, path::PathBuf, sync::Mutex, time::Duration,
+};
+
+use serde::{Deserialize, Serialize};
+
+/// Positions closer than this to the start aren't worth resuming from.
+const MIN_RESUME_POSITION: Duration = Duration::from_secs(10);
+
+/// Episodes left off closer than this to their end count as played.
+const MAX_REMAINING: Duration = Duration::from_secs(15);
+
+#[derive(Clone, Copy, Debug, Default, Serialize, Deserialize)]
+pub struct EpisodeState {
+    /// Where listening stopped, in seconds.
+    pub position: f64,
+    pub is_played: bool,
+}
+
+/// Resume positions and played flags of podcast episodes and audiobook
+/// chapters. Positions reach the account as the end position of play
+/// reports, but the API offers no documented way to read them back, so they
+/// are kept here to resume from.
+pub struct EpisodeStore {
+    path: PathBuf,
+    states: Mutex<HashMap<i32, EpisodeState>>,
+}
+
+impl EpisodeStore {
+    pub fn open(path: PathBuf) -> io::Result<Self> {
+        let states = match fs::read(&path) {
+            Ok(bytes) => serde_json::from_slice(&bytes)?,
+            Err(err) if err.kind() == io::ErrorKind::NotFound => HashMap::new(),
+            Err(err) => return Err(err),
+        };
+
+        Ok(Self {
+            path,
+            states: Mutex::new(states),
+        })
+    }
+
+    pub fn state(&self, track_id: i32) -> Option<EpisodeState> {
+        self.states.lock().unwrap().get(&track_id).copied()
+    }
+
+    pub fn is_played(&self, track_id: i32) -> bool {
+        self.state(track_id).is_some_and(|state| state.is_played)
+    }
+
+    /// Where to resume an episode from, unless it was finished or barely
+    /// started.
+    pub fn resume_position(&self, track_id: i32) -> Option<Duration> {
+        self.state(track_id)
+            .filter(|state| !state.is_played)
+            .map(|state| Duration::from_secs_f64(state.position))
+            .filter(|position| *position >= MIN_RESUME_POSITION)
+    }
+
+    /// Saves where an episode of `length` was left off, or marks it as
+    /// played if that was right before its end.
+    pub fn set_position(
+        &self,
+        track_id: i32,
+        position: Duration,
+        length: Option<Duration>,
+    ) -> io::Result<()> {
+        if length.is_some_and(|length| position + MAX_REMAINING >= length) {
+            return self.set_played(track_id, true);
+        }
+
+        let mut states = self.states.lock().unwrap();
+        states.entry(track_id).or_default().position = position.as_secs_f64();
+        self.save(&states)
+    }
+
+    /// Marks an episode as played or not. Either way it starts from the
+    /// beginning the next time.
+    pub fn set_played(&self, track_id: i32, is_played: bool) -> io::Result<()> {
+        let mut states = self.states.lock().unwrap();
+        if is_played {
+            states.insert(
+                track_id,
+                EpisodeState {
+                    position: 0.0,
+                    is_played,
+                },
+            );
+        } else {
+            states.remove(&track_id);
+        }
+        self.save(&states)
+    }
+
+    fn save(&self, states: &HashMap<i32, EpisodeState>) -> io::Result<()> {
+        let temp_path = self.path.with_extension("tmp");
+        fs::write(&temp_path, serde_json::to_vec(states)?)?;
+        fs::rename(temp_path, &self.path)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::TempDir;
+
+    const LENGTH: Option<Duration> = Some(Duration::from_secs(3600));
+
+    fn open(directory: &TempDir) -> EpisodeStore {
+        EpisodeStore::open(directory.join("episodes.json")).unwrap()
+    }
+
+    #[test]
+    fn keeps_positions_across_restarts() {
+        let directory = TempDir::new("episodes-restart");
+        let store = open(&directory);
+        store
+            .set_position(1, Duration::from_secs(600), LENGTH)
+            .unwrap();
+        store.set_played(2, true).unwrap();
+
+        let store = open(&directory);
+        assert_eq!(store.resume_position(1), Some(Duration::from_secs(600)));
+        assert!(!store.is_played(1));
+        assert!(store.is_played(2));
+        assert_eq!(store.resume_position(2), None);
+    }
+
+    #[test]
+    fn does_not_resume_near_the_start() {
+        let directory = TempDir::new("episodes-start");
+        let store = open(&directory);
+        store
+            .set_position(1, Duration::from_secs(5), LENGTH)
+            .unwrap();
+
+        assert_eq!(store.resume_position(1), None);
+        assert!(!store.is_played(1));
+    }
+
+    #[test]
+    fn marks_an_episode_left_near_its_end_as_played() {
+        let directory = TempDir::new("episodes-end");
+        let store = open(&directory);
+        store
+            .set_position(1, Duration::from_secs(3590), LENGTH)
+            .unwrap();
+
+        assert!(store.is_played(1));
+        assert_eq!(store.resume_position(1), None);
+    }
+
+    #[test]
+    fn starts_over_after_being_marked_as_not_played() {
+        let directory = TempDir::new("episodes-unplayed");
+        let store = open(&directory);
+        store
+            .set_position(1, Duration::from_secs(600), LENGTH)
+            .unwrap();
+        store.set_played(1, true).unwrap();
+        store.set_played(1, false).unwrap();
+
+        assert!(store.state(1).is_none());
+    }
+}
@@ -1,4 +1,7 @@
 pub mod backend;
 pub mod enums;
+pub mod episodes;
 pub mod playback;
+pub mod plays;
 pub mod progress;
+pub mod radio;
@@ -1,4 +1,9 @@
-use rodio::{cpal::StreamConfig, OutputStream, Sink};
+use rodio::{
+    cpal::{BufferSize, StreamConfig},
+    DeviceTrait, OutputStream, Sink,
+};
+
+use crate::metrics::METRICS;
 
 use super::utils::setup_device_config;
 
@@ -9,5 +14,19 @@ pub fn init() -> color_eyre::Result<(OutputStream, Sink, StreamConfig)> {
         OutputStream::try_from_device_config(&device, &cfg, &sample_format)?;
     let sink = Sink::try_new(&stream_handle)?;
 
+    METRICS.set_output(format!(
+        "{}, {} Hz, {} ch, {:?}{}",
+        device
+            .name()
+            .unwrap_or_else(|_| "Unknown device".to_string()),
+        cfg.sample_rate.0,
+        cfg.channels,
+        sample_format,
+        match cfg.buffer_size {
+            BufferSize::Fixed(frames) => format!(", {} frames", frames),
+            BufferSize::Default => String::new(),
+        }
+    ));
+
     Ok((stream, sink, cfg))
 }
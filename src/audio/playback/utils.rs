@@ -9,20 +9,118 @@ use rodio::{
 //     traits::{DeviceTrait, HostTrait},
 //     Device, SampleFormat, StreamConfig,
 // };
+use anyhow::Context;
+use tracing::warn;
 use yandex_music::YandexMusicClient;
 
+use crate::{
+    api::track::fetch_file_info, config::CONFIG, stream::decrypt::TrackKey,
+};
+
+/// A resolved direct link to a track.
+pub struct TrackUrl {
+    pub url: String,
+    pub codec: String,
+    pub bitrate: i32,
+    /// Set if the file is encrypted.
+    pub key: Option<TrackKey>,
+}
+
+/// Resolves a direct link to a track. The link is resolved through `http`,
+/// which must not carry the token, since it points to the storage hosts.
 pub async fn fetch_track_url(
     client: &YandexMusicClient,
+    http: &reqwest::Client,
     track_id: i32,
-) -> (String, String, i32) {
-    let download_info = client.get_track_download_info(track_id).await.unwrap();
+    lowest_bitrate: bool,
+) -> anyhow::Result<TrackUrl> {
+    if CONFIG.lossless && !lowest_bitrate {
+        match fetch_lossless_url(client, track_id).await {
+            Ok(url) => return Ok(url),
+            Err(err) => {
+                warn!("No lossless stream for track {}: {:#}", track_id, err)
+            }
+        }
+    }
+
+    let download_info = client.get_track_download_info(track_id).await?;
+    let info = if lowest_bitrate {
+        download_info.iter().min_by_key(|info| info.bitrate_in_kbps)
+    } else {
+        download_info.iter().max_by_key(|info| info.bitrate_in_kbps)
+    }
+    .with_context(|| format!("Track {} has no downloads", track_id))?;
+    let url = info.get_direct_link(http).await?;
+
+    Ok(TrackUrl {
+        url,
+        codec: info.codec.clone(),
+        bitrate: info.bitrate_in_kbps,
+        key: None,
+    })
+}
+
+async fn fetch_lossless_url(
+    client: &YandexMusicClient,
+    track_id: i32,
+) -> anyhow::Result<TrackUrl> {
+    let info = fetch_file_info(client, track_id, "lossless").await?;
+    let key = match info.key.as_deref() {
+        Some(key) if !key.is_empty() => Some(TrackKey::from_hex(key)?),
+        _ => None,
+    };
+
+    Ok(TrackUrl {
+        url: info.url,
+        codec: info.codec,
+        bitrate: info.bitrate,
+        key,
+    })
+}
+
+/// Resolves a new direct link for the given codec and bitrate of a track,
+/// e.g. after the previous one has expired. Encrypted files are resolved
+/// through the lossless endpoint again, which may encrypt them with a new
+/// key.
+pub async fn refresh_track_url(
+    client: &YandexMusicClient,
+    http: &reqwest::Client,
+    track_id: i32,
+    codec: &str,
+    bitrate: i32,
+    encrypted: bool,
+) -> anyhow::Result<TrackUrl> {
+    if encrypted {
+        let url = fetch_lossless_url(client, track_id).await?;
+        anyhow::ensure!(
+            url.codec == codec,
+            "Track {} is no longer available as {}",
+            track_id,
+            codec
+        );
+        anyhow::ensure!(
+            url.key.is_some(),
+            "Track {} is no longer encrypted",
+            track_id
+        );
+
+        return Ok(url);
+    }
+
+    let download_info = client.get_track_download_info(track_id).await?;
     let info = download_info
         .iter()
-        .max_by_key(|info| info.bitrate_in_kbps)
-        .unwrap();
-    let url = info.get_direct_link(&client.client).await.unwrap();
+        .find(|info| info.codec == codec && info.bitrate_in_kbps == bitrate)
+        .with_context(|| {
+            format!("Track {} is no longer available as {}", track_id, codec)
+        })?;
 
-    (url, info.codec.clone(), info.bitrate_in_kbps)
+    Ok(TrackUrl {
+        url: info.get_direct_link(http).await?,
+        codec: info.codec.clone(),
+        bitrate: info.bitrate_in_kbps,
+        key: None,
+    })
 }
 
 pub fn setup_device_config() -> (Device, StreamConfig, SampleFormat) {
@@ -0,0 +1,142 @@
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use yandex_music::model::track_model::track::Track;
+
+use crate::api::plays::{PlayContext, PlayReport};
+
+/// Tracks how long a track has actually been listened to, leaving out
+/// pauses and the parts skipped by seeking.
+pub struct PlaySession {
+    track: Track,
+    context: PlayContext,
+    play_id: String,
+    from_cache: bool,
+    /// Seconds listened before the current stretch.
+    played: f64,
+    /// Where the current stretch of listening started, unless paused.
+    resumed_at: Option<Duration>,
+}
+
+impl PlaySession {
+    /// Starts a session of `track` played from `start_at`.
+    pub fn new(
+        track: Track,
+        context: PlayContext,
+        from_cache: bool,
+        start_at: Duration,
+    ) -> Self {
+        let nanos = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_nanos();
+
+        Self {
+            play_id: format!("{}-{:x}", track.id, nanos),
+            track,
+            context,
+            from_cache,
+            played: 0.0,
+            resumed_at: Some(start_at),
+        }
+    }
+
+    pub fn pause(&mut self, position: Duration) {
+        if let Some(resumed_at) = self.resumed_at.take() {
+            self.played += position.saturating_sub(resumed_at).as_secs_f64();
+        }
+    }
+
+    pub fn resume(&mut self, position: Duration) {
+        self.resumed_at = Some(position);
+    }
+
+    /// Records a seek from `from` to `to`. The skipped part doesn't count as
+    /// listened.
+    pub fn seek(&mut self, from: Duration, to: Duration) {
+        if self.resumed_at.is_some() {
+            self.pause(from);
+            self.resume(to);
+        }
+    }
+
+    /// A report of the play as of `position`.
+    pub fn report(&self, position: Duration) -> PlayReport {
+        let played = self.played
+            + self.resumed_at.map_or(0.0, |resumed_at| {
+                position.saturating_sub(resumed_at).as_secs_f64()
+            });
+
+        PlayReport::new(
+            &self.track,
+            &self.context,
+            &self.play_id,
+            self.from_cache,
+            played,
+            position.as_secs_f64(),
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn track() -> Track {
+        serde_json::from_value(serde_json::json!({
+            "id": 7,
+            "realId": "7",
+            "title": "Track",
+            "available": true,
+            "durationMs": 200_000,
+            "artists": [],
+            "albums": [{ "id": 3, "title": "Album" }],
+        }))
+        .unwrap()
+    }
+
+    fn session(from_cache: bool, start_at: Duration) -> PlaySession {
+        PlaySession::new(
+            track(),
+            PlayContext::Playlist { uid: 1, kind: 2 },
+            from_cache,
+            start_at,
+        )
+    }
+
+    #[test]
+    fn reports_the_track_and_its_context() {
+        let report = session(true, Duration::ZERO).report(Duration::ZERO);
+
+        assert_eq!(report.track_id, "7");
+        assert_eq!(report.album_id, Some(3));
+        assert_eq!(report.playlist_id.as_deref(), Some("1:2"));
+        assert_eq!(report.context, "playlist");
+        assert_eq!(report.from, "yatui-own_playlists-playlist-default");
+        assert!(report.from_cache);
+        assert_eq!(report.track_length_seconds, 200.0);
+        assert!(report.play_id.starts_with("7-"));
+    }
+
+    #[test]
+    fn counts_only_the_time_listened() {
+        let mut play = session(false, Duration::from_secs(10));
+        play.pause(Duration::from_secs(30));
+        play.resume(Duration::from_secs(30));
+        play.seek(Duration::from_secs(40), Duration::from_secs(100));
+
+        let report = play.report(Duration::from_secs(105));
+        assert_eq!(report.total_played_seconds, 35.0);
+        assert_eq!(report.end_position_seconds, 105.0);
+        assert!(!report.from_cache);
+    }
+
+    #[test]
+    fn ignores_seeks_while_paused() {
+        let mut play = session(false, Duration::ZERO);
+        play.pause(Duration::from_secs(20));
+        play.seek(Duration::from_secs(20), Duration::from_secs(50));
+
+        let report = play.report(Duration::from_secs(50));
+        assert_eq!(report.total_played_seconds, 20.0);
+    }
+}
@@ -1,24 +1,55 @@
 use std::{
-    sync::{Arc, RwLock},
+    sync::atomic::{AtomicU64, AtomicU8, Ordering},
     time::Duration,
 };
 
-#[derive(Default)]
+use tokio::sync::watch;
+
+use super::enums::PlaybackState;
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct ProgressSnapshot {
+    pub position: Duration,
+    pub duration: Duration,
+    pub state: PlaybackState,
+}
+
 pub struct TrackProgress {
-    current_position: Arc<RwLock<Duration>>,
-    total_duration: Arc<RwLock<Duration>>,
+    current_position: AtomicU64,
+    total_duration: AtomicU64,
+    state: AtomicU8,
+    notifier: watch::Sender<ProgressSnapshot>,
+}
+
+impl Default for TrackProgress {
+    fn default() -> Self {
+        Self {
+            current_position: AtomicU64::new(0),
+            total_duration: AtomicU64::new(0),
+            state: AtomicU8::new(PlaybackState::default().as_u8()),
+            notifier: watch::Sender::new(ProgressSnapshot::default()),
+        }
+    }
 }
 
 impl TrackProgress {
     pub fn set_current_position(&self, position: Duration) {
-        if let Ok(mut current) = self.current_position.write() {
-            *current = position;
+        let millis = position.as_millis() as u64;
+        if self.current_position.swap(millis, Ordering::Relaxed) != millis {
+            self.notify();
         }
     }
 
     pub fn set_total_duration(&self, duration: Duration) {
-        if let Ok(mut total) = self.total_duration.write() {
-            *total = duration;
+        let millis = duration.as_millis() as u64;
+        if self.total_duration.swap(millis, Ordering::Relaxed) != millis {
+            self.notify();
+        }
+    }
+
+    pub fn set_state(&self, state: PlaybackState) {
+        if self.state.swap(state.as_u8(), Ordering::Relaxed) != state.as_u8() {
+            self.notify();
         }
     }
 
@@ -29,8 +60,41 @@ impl TrackProgress {
 
     pub fn get_progress(&self) -> (Duration, Duration) {
         (
-            *self.current_position.read().unwrap(),
-            *self.total_duration.read().unwrap(),
+            Duration::from_millis(
+                self.current_position.load(Ordering::Relaxed),
+            ),
+            Duration::from_millis(self.total_duration.load(Ordering::Relaxed)),
         )
     }
+
+    pub fn get_state(&self) -> PlaybackState {
+        PlaybackState::from_u8(self.state.load(Ordering::Relaxed))
+    }
+
+    pub fn snapshot(&self) -> ProgressSnapshot {
+        let (position, duration) = self.get_progress();
+        ProgressSnapshot {
+            position,
+            duration,
+            state: self.get_state(),
+        }
+    }
+
+    /// Returns a receiver that is notified whenever the position, duration or
+    /// playback state changes.
+    pub fn subscribe(&self) -> watch::Receiver<ProgressSnapshot> {
+        self.notifier.subscribe()
+    }
+
+    fn notify(&self) {
+        let snapshot = self.snapshot();
+        self.notifier.send_if_modified(|current| {
+            if *current == snapshot {
+                false
+            } else {
+                *current = snapshot;
+                true
+            }
+        });
+    }
 }
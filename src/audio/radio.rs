@@ -0,0 +1,177 @@
+use std::{sync::Arc, time::Duration};
+
+use flume::Sender;
+use tracing::warn;
+use yandex_music::{model::track_model::track::Track, YandexMusicClient};
+
+use crate::{
+    api::rotor::{
+        feedback_track_id, fetch_station_tracks, send_feedback, RotorFeedback,
+    },
+    event::events::Event,
+    http::RetryPolicy,
+};
+
+/// Number of upcoming tracks below which the next batch is requested.
+const REFILL_THRESHOLD: usize = 2;
+/// Nothing plays until the first batch arrives, so it is retried a few
+/// times before the station is given up.
+const FIRST_BATCH_RETRIES: RetryPolicy = RetryPolicy {
+    max_retries: 3,
+    backoff: Duration::from_secs(1),
+};
+
+/// How a loaded batch of tracks changes the queue.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Batch {
+    /// The first batch, which replaces the queue.
+    First,
+    /// A later batch, which extends the queue. It may be empty if the
+    /// request failed, and the next refill tries again.
+    Next,
+    /// The first batch couldn't be loaded or has no playable tracks, so the
+    /// station can't be played.
+    Failed,
+}
+
+/// An endless rotor station whose tracks are fetched in batches.
+pub struct Radio {
+    pub station: String,
+    pub name: String,
+    client: Arc<YandexMusicClient>,
+    batch_id: Option<String>,
+    /// The track that was started but hasn't been reported as finished or
+    /// skipped yet.
+    current: Option<String>,
+    is_started: bool,
+    is_fetching: bool,
+}
+
+impl Radio {
+    pub fn new(
+        client: Arc<YandexMusicClient>,
+        station: String,
+        name: String,
+    ) -> Self {
+        Self {
+            station,
+            name,
+            client,
+            batch_id: None,
+            current: None,
+            is_started: false,
+            is_fetching: false,
+        }
+    }
+
+    /// Requests the first batch of tracks of the station.
+    pub fn start(&mut self, event_tx: Sender<Event>) {
+        self.feedback(RotorFeedback::RadioStarted);
+        self.fetch(event_tx, None);
+    }
+
+    /// Requests the next batch once fewer than [`REFILL_THRESHOLD`] tracks
+    /// are left in the queue after the current one.
+    pub fn refill(
+        &mut self,
+        event_tx: Sender<Event>,
+        tracks: &[Track],
+        track_index: usize,
+    ) {
+        if self.is_fetching
+            || tracks.len().saturating_sub(track_index + 1) >= REFILL_THRESHOLD
+        {
+            return;
+        }
+
+        self.fetch(event_tx, tracks.last().map(feedback_track_id));
+    }
+
+    /// Records a loaded batch with the `tracks` that are going to be
+    /// queued, and returns how it changes the queue.
+    pub fn on_loaded(
+        &mut self,
+        batch_id: Option<String>,
+        tracks: &[Track],
+    ) -> Batch {
+        self.is_fetching = false;
+        if !self.is_started && tracks.is_empty() {
+            return Batch::Failed;
+        }
+        let Some(batch_id) = batch_id else {
+            return Batch::Next;
+        };
+
+        self.batch_id = Some(batch_id);
+        if std::mem::replace(&mut self.is_started, true) {
+            Batch::Next
+        } else {
+            Batch::First
+        }
+    }
+
+    pub fn track_started(&mut self, track: &Track) {
+        let track_id = feedback_track_id(track);
+        self.current = Some(track_id.clone());
+        self.feedback(RotorFeedback::TrackStarted { track_id });
+    }
+
+    pub fn track_finished(&mut self, played: f64) {
+        if let Some(track_id) = self.current.take() {
+            self.feedback(RotorFeedback::TrackFinished { track_id, played });
+        }
+    }
+
+    pub fn track_skipped(&mut self, played: f64) {
+        if let Some(track_id) = self.current.take() {
+            self.feedback(RotorFeedback::Skip { track_id, played });
+        }
+    }
+
+    fn fetch(&mut self, event_tx: Sender<Event>, last_track: Option<String>) {
+        self.is_fetching = true;
+
+        let client = self.client.clone();
+        let station = self.station.clone();
+        let is_started = self.is_started;
+        tokio::spawn(async move {
+            let fetch = || {
+                fetch_station_tracks(&client, &station, last_track.as_deref())
+            };
+            let batch = if is_started {
+                fetch().await
+            } else {
+                FIRST_BATCH_RETRIES.run(fetch, |_| false).await
+            };
+
+            // A failed request is still reported, so that the next refill
+            // can try again or the station is stopped.
+            let (batch_id, tracks) = match batch {
+                Ok(batch) => (Some(batch.batch_id), batch.tracks),
+                Err(err) => {
+                    warn!("Failed to fetch tracks of {}: {:#}", station, err);
+                    (None, Vec::new())
+                }
+            };
+            let _ = event_tx.send(Event::RadioLoaded {
+                station,
+                batch_id,
+                tracks,
+            });
+        });
+    }
+
+    fn feedback(&self, feedback: RotorFeedback) {
+        let client = self.client.clone();
+        let station = self.station.clone();
+        let batch_id = self.batch_id.clone();
+        tokio::spawn(async move {
+            if let Err(err) =
+                send_feedback(&client, &station, batch_id.as_deref(), &feedback)
+                    .await
+            {
+                warn!("Failed to send feedback to {}: {:#}", station, err);
+            }
+        });
+    }
+}
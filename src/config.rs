@@ -0,0 +1,158 @@
+use std::{path::PathBuf, str::FromStr, time::Duration};
+
+use lazy_static::lazy_static;
+
+use crate::ui::log::PROJECT_NAME;
+
+lazy_static! {
+    pub static ref CONFIG: Config = Config::from_env();
+}
+
+/// Command-line arguments.
+#[derive(Default)]
+pub struct Args {
+    /// Play only from the offline store without calling the API.
+    pub offline: bool,
+    /// The profile to use instead of the one used last.
+    pub profile: Option<String>,
+    /// Token to store for the profile.
+    pub token: Option<String>,
+}
+
+impl Args {
+    pub fn parse() -> Self {
+        let mut args = Self::default();
+        let mut iter = std::env::args().skip(1);
+        while let Some(arg) = iter.next() {
+            let (name, value) = match arg.split_once('=') {
+                Some((name, value)) => (name.to_string(), Some(value.into())),
+                None => (arg, None),
+            };
+
+            match name.as_str() {
+                "--offline" => args.offline = true,
+                "--profile" => args.profile = value.or_else(|| iter.next()),
+                "--token" => args.token = value.or_else(|| iter.next()),
+                _ => {}
+            }
+        }
+
+        args
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum IpVersion {
+    V4,
+    V6,
+}
+
+impl FromStr for IpVersion {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "4" | "v4" | "ipv4" => Ok(IpVersion::V4),
+            "6" | "v6" | "ipv6" => Ok(IpVersion::V6),
+            _ => Err(format!("unknown IP version: {}", s)),
+        }
+    }
+}
+
+pub struct Config {
+    /// Base URL of the API, e.g. a local stand-in server for testing.
+    pub api_url: String,
+    /// Maximum size of the on-disk audio cache in bytes. `0` disables it.
+    pub cache_size: u64,
+    /// HTTP or SOCKS5 proxy used for all requests.
+    pub proxy: Option<String>,
+    /// PEM files with additional trusted root certificates.
+    pub ca_certificates: Vec<PathBuf>,
+    pub user_agent: String,
+    /// Restricts connections to IPv4 or IPv6 if set.
+    pub ip_version: Option<IpVersion>,
+    /// Timeout for establishing a connection.
+    pub connect_timeout: Duration,
+    /// Maximum time to wait for data on an open connection.
+    pub read_timeout: Duration,
+    /// How many times a failed range request is retried before the stream
+    /// gives up.
+    pub max_retries: u32,
+    /// Delay before the first retry. It doubles with each attempt.
+    pub retry_backoff: Duration,
+    /// How many upcoming queue items are prefetched.
+    pub prefetch_tracks: usize,
+    /// How much of the beginning of each upcoming track is prefetched.
+    pub prefetch_duration: Duration,
+    /// Maximum amount of memory in bytes used for prefetched audio. `0`
+    /// disables prefetching.
+    pub prefetch_memory: u64,
+    /// Download rate limit for prefetching in bytes per second. `0` means
+    /// unlimited.
+    pub prefetch_bandwidth: u64,
+    /// Download rate limit for all audio in bytes per second. `0` means
+    /// unlimited.
+    pub rate_limit: u64,
+    /// Whether data saver mode is enabled on startup.
+    pub data_saver: bool,
+    /// Whether lossless streams are requested for accounts that have access
+    /// to them.
+    pub lossless: bool,
+}
+
+impl Config {
+    fn from_env() -> Self {
+        Self {
+            api_url: env_var::<String>("API_URL")
+                .map(|url| url.trim_end_matches('/').to_string())
+                .unwrap_or_else(|| "https://api.music.yandex.net".to_string()),
+            cache_size: env_var::<u64>("CACHE_SIZE_MB").unwrap_or(1024)
+                * 1024
+                * 1024,
+            proxy: env_var("PROXY"),
+            ca_certificates: std::env::var_os(format!(
+                "{}_CA_CERTS",
+                PROJECT_NAME.as_str()
+            ))
+            .map(|paths| std::env::split_paths(&paths).collect())
+            .unwrap_or_default(),
+            user_agent: env_var("USER_AGENT").unwrap_or_else(|| {
+                format!(
+                    "{}/{}",
+                    env!("CARGO_PKG_NAME"),
+                    env!("CARGO_PKG_VERSION")
+                )
+            }),
+            ip_version: env_var("IP_VERSION"),
+            connect_timeout: Duration::from_secs(
+                env_var("CONNECT_TIMEOUT_SECS").unwrap_or(10),
+            ),
+            read_timeout: Duration::from_secs(
+                env_var("READ_TIMEOUT_SECS").unwrap_or(30),
+            ),
+            max_retries: env_var("MAX_RETRIES").unwrap_or(5),
+            retry_backoff: Duration::from_millis(
+                env_var("RETRY_BACKOFF_MS").unwrap_or(500),
+            ),
+            prefetch_tracks: env_var("PREFETCH_TRACKS").unwrap_or(2),
+            prefetch_duration: Duration::from_secs(
+                env_var("PREFETCH_SECS").unwrap_or(10),
+            ),
+            prefetch_memory: env_var::<u64>("PREFETCH_MEMORY_MB").unwrap_or(4)
+                * 1024
+                * 1024,
+            prefetch_bandwidth: env_var::<u64>("PREFETCH_RATE_KB")
+                .unwrap_or(256)
+                * 1024,
+            rate_limit: env_var::<u64>("RATE_LIMIT_KB").unwrap_or(0) * 1024,
+            data_saver: env_var("DATA_SAVER").unwrap_or(false),
+            lossless: env_var("LOSSLESS").unwrap_or(false),
+        }
+    }
+}
+
+fn env_var<T: FromStr>(name: &str) -> Option<T> {
+    std::env::var(format!("{}_{}", PROJECT_NAME.as_str(), name))
+        .ok()
+        .and_then(|value| value.trim().parse().ok())
+}
@@ -2,11 +2,62 @@ use std::time::Duration;
 
 use yandex_music::model::track_model::track::Track;
 
+use crate::{
+    api::{landing::FeedId, library::Playlist, search::SearchKind},
+    lyrics::Lyrics,
+    offline::store::CollectionSource,
+    ui::{
+        details::{DetailEntry, DetailPage},
+        library::{LibraryItem, LibrarySection},
+        search::SearchResult,
+    },
+};
+
 pub enum Event {
     // Events
     Initialize,
     TracksFetched(Vec<Track>),
     TrackEnded,
+    /// Playback of a streamed track has started.
+    PlayStarted {
+        track_id: i32,
+        from_cache: bool,
+        start_at: Option<Duration>,
+    },
+    LibraryLoaded(LibrarySection, Vec<LibraryItem>),
+    CollectionLoaded {
+        title: String,
+        source: Option<CollectionSource>,
+        tracks: Vec<Track>,
+        append: bool,
+    },
+    SearchLoaded {
+        query: String,
+        kind: SearchKind,
+        page: usize,
+        total: usize,
+        results: Vec<SearchResult>,
+    },
+    PageLoaded(DetailPage),
+    PageExtended {
+        key: String,
+        entries: Vec<DetailEntry>,
+        /// Ids of a page that failed to load.
+        failed: Vec<FeedId>,
+    },
+    PlaylistUpdated(Playlist),
+    RadioLoaded {
+        station: String,
+        batch_id: Option<String>,
+        tracks: Vec<Track>,
+    },
+    LyricsLoaded {
+        track_id: i32,
+        lyrics: Option<Lyrics>,
+        /// Whether the lyrics couldn't be fetched, as opposed to the track
+        /// having none.
+        failed: bool,
+    },
 
     // Commands
     Play(i32),
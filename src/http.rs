@@ -0,0 +1,197 @@
+use std::{
+    fs,
+    future::Future,
+    net::{IpAddr, Ipv4Addr, Ipv6Addr},
+    time::Duration,
+};
+
+use anyhow::Context;
+use color_eyre::eyre::WrapErr;
+use reqwest::{
+    header::{HeaderMap, HeaderValue, AUTHORIZATION},
+    Certificate, Client, ClientBuilder, Proxy,
+};
+
+use tracing::warn;
+
+use crate::{
+    config::{IpVersion, CONFIG},
+    utils::random,
+};
+
+const MAX_RETRY_BACKOFF: Duration = Duration::from_secs(30);
+
+/// How often a failed request is retried, and how long to wait in between.
+#[derive(Clone, Copy, Debug)]
+pub struct RetryPolicy {
+    pub max_retries: u32,
+    /// Delay before the first retry. It doubles with each attempt.
+    pub backoff: Duration,
+}
+
+impl RetryPolicy {
+    /// Runs `request` until it succeeds or the retry limit is reached,
+    /// waiting with exponential backoff and jitter between attempts. Errors
+    /// that `is_permanent` accepts are not retried.
+    pub async fn run<T, F>(
+        &self,
+        mut request: impl FnMut() -> F,
+        is_permanent: impl Fn(&anyhow::Error) -> bool,
+    ) -> anyhow::Result<T>
+    where
+        F: Future<Output = anyhow::Result<T>>,
+    {
+        let mut attempt = 0;
+        loop {
+            match request().await {
+                Ok(value) => return Ok(value),
+                Err(err) if is_permanent(&err) => return Err(err),
+                Err(err) if attempt < self.max_retries => {
+                    let delay = self.delay(attempt);
+                    warn!(
+                        "Request failed (attempt {}), retrying in {:?}: {:#}",
+                        attempt + 1,
+                        delay,
+                        err
+                    );
+                    tokio::time::sleep(delay).await;
+                    attempt += 1;
+                }
+                Err(err) => {
+                    return Err(err).with_context(|| {
+                        format!("Request failed after {} attempts", attempt + 1)
+                    })
+                }
+            }
+        }
+    }
+
+    fn delay(&self, attempt: u32) -> Duration {
+        let delay = self
+            .backoff
+            .saturating_mul(2u32.saturating_pow(attempt))
+            .min(MAX_RETRY_BACKOFF);
+        let half = (delay.as_millis() / 2) as i32;
+        let jitter = if half > 0 { random(0, half) } else { 0 };
+
+        Duration::from_millis((half + jitter) as u64)
+    }
+}
+
+/// Builds a client for downloading audio and images.
+pub fn build_client() -> color_eyre::Result<Client> {
+    Ok(builder()?.build()?)
+}
+
+/// Builds a client for API requests that authenticates with `token`.
+///
+/// Kept separate from [`build_client`] so that the token is never sent to
+/// third-party hosts such as the audio CDN.
+pub fn build_api_client(token: &str) -> color_eyre::Result<Client> {
+    let mut authorization = HeaderValue::from_str(&format!("OAuth {}", token))?;
+    authorization.set_sensitive(true);
+
+    let mut headers = HeaderMap::new();
+    headers.insert(AUTHORIZATION, authorization);
+
+    Ok(builder()?.default_headers(headers).build()?)
+}
+
+fn builder() -> color_eyre::Result<ClientBuilder> {
+    let mut builder = Client::builder()
+        .user_agent(&CONFIG.user_agent)
+        .connect_timeout(CONFIG.connect_timeout)
+        .read_timeout(CONFIG.read_timeout);
+
+    if let Some(proxy) = CONFIG.proxy.as_ref() {
+        builder = builder.proxy(
+            Proxy::all(proxy)
+                .wrap_err_with(|| format!("Invalid proxy URL: {}", proxy))?,
+        );
+    }
+
+    for path in &CONFIG.ca_certificates {
+        let pem = fs::read(path).wrap_err_with(|| {
+            format!("Failed to read certificate {}", path.display())
+        })?;
+        for certificate in Certificate::from_pem_bundle(&pem)? {
+            builder = builder.add_root_certificate(certificate);
+        }
+    }
+
+    builder = match CONFIG.ip_version {
+        Some(IpVersion::V4) => {
+            builder.local_address(IpAddr::V4(Ipv4Addr::UNSPECIFIED))
+        }
+        Some(IpVersion::V6) => {
+            builder.local_address(IpAddr::V6(Ipv6Addr::UNSPECIFIED))
+        }
+        None => builder,
+    };
+
+    Ok(builder)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const POLICY: RetryPolicy = RetryPolicy {
+        max_retries: 2,
+        backoff: Duration::ZERO,
+    };
+
+    #[tokio::test]
+    async fn retries_until_the_request_succeeds() {
+        let mut attempts = 0;
+        let result = POLICY
+            .run(
+                || {
+                    attempts += 1;
+                    let attempt = attempts;
+                    async move {
+                        anyhow::ensure!(attempt == 3, "attempt {}", attempt);
+                        Ok(attempt)
+                    }
+                },
+                |_| false,
+            )
+            .await;
+
+        assert_eq!(result.unwrap(), 3);
+    }
+
+    #[tokio::test]
+    async fn gives_up_after_the_retry_limit() {
+        let mut attempts = 0;
+        let result = POLICY
+            .run(
+                || {
+                    attempts += 1;
+                    async { anyhow::Result::<()>::Err(anyhow::anyhow!("down")) }
+                },
+                |_| false,
+            )
+            .await;
+
+        assert!(result.is_err());
+        assert_eq!(attempts, 3);
+    }
+
+    #[tokio::test]
+    async fn does_not_retry_permanent_errors() {
+        let mut attempts = 0;
+        let result = POLICY
+            .run(
+                || {
+                    attempts += 1;
+                    async { anyhow::Result::<()>::Err(anyhow::anyhow!("gone")) }
+                },
+                |_| true,
+            )
+            .await;
+
+        assert!(result.is_err());
+        assert_eq!(attempts, 1);
+    }
+}
@@ -1,5 +1,15 @@
+pub mod api;
 pub mod audio;
+pub mod config;
 pub mod event;
+pub mod http;
+pub mod lyrics;
+pub mod metrics;
+pub mod offline;
+pub mod profile;
 pub mod ui;
 pub mod utils;
-pub mod stream;
\ No newline at end of file
+pub mod stream;
+
+#[cfg(test)]
+mod test_utils;
\ No newline at end of file
@@ -0,0 +1,265 @@
+use std::{
+    collections::HashMap, fs, io, path::PathBuf, sync::Mutex, time::Duration,
+};
+
+use tracing::info;
+use yandex_music::YandexMusicClient;
+
+use crate::api::lyrics::{fetch_lyrics, LyricsFormat};
+
+const OFFSETS_FILE: &str = "offsets.json";
+
+#[derive(Clone, Debug)]
+pub struct LyricsLine {
+    /// When the line starts, if the lyrics are time-synced.
+    pub time: Option<Duration>,
+    pub text: String,
+}
+
+#[derive(Clone, Debug)]
+pub struct Lyrics {
+    pub lines: Vec<LyricsLine>,
+    pub is_synced: bool,
+}
+
+impl Lyrics {
+    /// Parses lyrics in the LRC format. Lines may carry several timestamps,
+    /// and metadata tags such as `[ar:...]` are skipped.
+    pub fn parse_lrc(text: &str) -> Self {
+        let mut lines = Vec::new();
+        for line in text.lines() {
+            let mut rest = line.trim();
+            let mut times = Vec::new();
+            while let Some((tag, after)) =
+                rest.strip_prefix('[').and_then(|r| r.split_once(']'))
+            {
+                match parse_timestamp(tag) {
+                    Some(time) => times.push(time),
+                    None => break,
+                }
+                rest = after;
+            }
+
+            lines.extend(times.into_iter().map(|time| LyricsLine {
+                time: Some(time),
+                text: rest.trim().to_string(),
+            }));
+        }
+        lines.sort_by_key(|line| line.time);
+
+        Self {
+            lines,
+            is_synced: true,
+        }
+    }
+
+    pub fn parse_text(text: &str) -> Self {
+        Self {
+            lines: text
+                .lines()
+                .map(|line| LyricsLine {
+                    time: None,
+                    text: line.trim_end().to_string(),
+                })
+                .collect(),
+            is_synced: false,
+        }
+    }
+
+    /// The line that is sung at `position`, shifted by `offset_ms`.
+    pub fn current_line(
+        &self,
+        position: Duration,
+        offset_ms: i64,
+    ) -> Option<usize> {
+        if !self.is_synced {
+            return None;
+        }
+
+        let position = position.as_millis() as i64 - offset_ms;
+        self.lines.iter().rposition(|line| {
+            line.time
+                .is_some_and(|time| time.as_millis() as i64 <= position)
+        })
+    }
+}
+
+/// Parses an LRC timestamp such as `01:23.45`.
+fn parse_timestamp(tag: &str) -> Option<Duration> {
+    let (minutes, seconds) = tag.split_once(':')?;
+    let minutes = minutes.parse::<u64>().ok()?;
+    // Negative, NaN and infinite seconds are rejected rather than panicking.
+    let seconds = Duration::try_from_secs_f64(seconds.parse().ok()?).ok()?;
+
+    Duration::from_secs(minutes.checked_mul(60)?).checked_add(seconds)
+}
+
+fn is_not_found(err: &anyhow::Error) -> bool {
+    err.downcast_ref::<reqwest::Error>()
+        .and_then(|err| err.status())
+        .is_some_and(|status| status == reqwest::StatusCode::NOT_FOUND)
+}
+
+/// Lyrics stored on disk, along with the timing offsets set per track.
+pub struct LyricsCache {
+    directory: PathBuf,
+    offsets: Mutex<HashMap<i32, i64>>,
+}
+
+impl LyricsCache {
+    pub fn open(directory: PathBuf) -> io::Result<Self> {
+        fs::create_dir_all(&directory)?;
+
+        let offsets = match fs::read(directory.join(OFFSETS_FILE)) {
+            Ok(bytes) => serde_json::from_slice(&bytes)?,
+            Err(err) if err.kind() == io::ErrorKind::NotFound => HashMap::new(),
+            Err(err) => return Err(err),
+        };
+
+        Ok(Self {
+            directory,
+            offsets: Mutex::new(offsets),
+        })
+    }
+
+    /// Returns the lyrics of a track from the cache, or fetches and stores
+    /// them. Synced lyrics are preferred over plain text.
+    pub async fn load(
+        &self,
+        client: &YandexMusicClient,
+        http: &reqwest::Client,
+        track_id: i32,
+    ) -> anyhow::Result<Option<Lyrics>> {
+        if let Some(lyrics) = self.get(track_id) {
+            return Ok(Some(lyrics));
+        }
+
+        // Only a missing format moves on to the next one. Other errors are
+        // returned, so that a failed request isn't taken for a track
+        // without lyrics.
+        for format in [LyricsFormat::Lrc, LyricsFormat::Text] {
+            let text = match fetch_lyrics(client, http, track_id, format).await
+            {
+                Ok(text) => text,
+                Err(err) if is_not_found(&err) => continue,
+                Err(err) => return Err(err),
+            };
+
+            fs::write(self.path(track_id, format), &text)?;
+            return Ok(Some(match format {
+                LyricsFormat::Lrc => Lyrics::parse_lrc(&text),
+                LyricsFormat::Text => Lyrics::parse_text(&text),
+            }));
+        }
+
+        info!("No lyrics found for track {}", track_id);
+        Ok(None)
+    }
+
+    /// Returns the lyrics of a track if they are cached.
+    pub fn get(&self, track_id: i32) -> Option<Lyrics> {
+        if let Ok(text) =
+            fs::read_to_string(self.path(track_id, LyricsFormat::Lrc))
+        {
+            return Some(Lyrics::parse_lrc(&text));
+        }
+
+        fs::read_to_string(self.path(track_id, LyricsFormat::Text))
+            .ok()
+            .map(|text| Lyrics::parse_text(&text))
+    }
+
+    /// Timing offset of a track in milliseconds. Positive values show the
+    /// lyrics later.
+    pub fn offset(&self, track_id: i32) -> i64 {
+        self.offsets
+            .lock()
+            .unwrap()
+            .get(&track_id)
+            .copied()
+            .unwrap_or(0)
+    }
+
+    pub fn set_offset(&self, track_id: i32, offset_ms: i64) -> io::Result<()> {
+        let mut offsets = self.offsets.lock().unwrap();
+        if offset_ms == 0 {
+            offsets.remove(&track_id);
+        } else {
+            offsets.insert(track_id, offset_ms);
+        }
+
+        let temp_path = self.directory.join(OFFSETS_FILE).with_extension("tmp");
+        fs::write(&temp_path, serde_json::to_vec(&*offsets)?)?;
+        fs::rename(temp_path, self.directory.join(OFFSETS_FILE))
+    }
+
+    fn path(&self, track_id: i32, format: LyricsFormat) -> PathBuf {
+        let extension = match format {
+            LyricsFormat::Lrc => "lrc",
+            LyricsFormat::Text => "txt",
+        };
+
+        self.directory.join(format!("{}.{}", track_id, extension))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_timestamps() {
+        assert_eq!(
+            parse_timestamp("01:23.45"),
+            Some(Duration::from_millis(83_450))
+        );
+        assert_eq!(parse_timestamp("00:00"), Some(Duration::ZERO));
+        assert_eq!(parse_timestamp("ar:Artist"), None);
+    }
+
+    #[test]
+    fn rejects_timestamps_that_do_not_fit_a_duration() {
+        assert_eq!(parse_timestamp("00:-1.5"), None);
+        assert_eq!(parse_timestamp("00:NaN"), None);
+        assert_eq!(parse_timestamp("00:inf"), None);
+        assert_eq!(parse_timestamp("00:1e30"), None);
+        assert_eq!(parse_timestamp(&format!("{}:00", u64::MAX)), None);
+    }
+
+    #[test]
+    fn parses_lrc_lines_with_several_timestamps() {
+        let lyrics = Lyrics::parse_lrc(
+            "[ar:Artist]\n[00:10.00][00:30.00]Chorus\n[00:20.00]Verse",
+        );
+
+        let lines = lyrics
+            .lines
+            .iter()
+            .map(|line| (line.time.unwrap().as_secs(), line.text.as_str()))
+            .collect::<Vec<_>>();
+        assert_eq!(lines, [(10, "Chorus"), (20, "Verse"), (30, "Chorus")]);
+    }
+
+    #[test]
+    fn finds_the_current_line_with_an_offset() {
+        let lyrics = Lyrics::parse_lrc("[00:01.00]One\n[00:02.00]Two");
+
+        assert_eq!(lyrics.current_line(Duration::from_millis(500), 0), None);
+        assert_eq!(
+            lyrics.current_line(Duration::from_millis(1500), 0),
+            Some(0)
+        );
+        assert_eq!(
+            lyrics.current_line(Duration::from_millis(2000), 0),
+            Some(1)
+        );
+        assert_eq!(
+            lyrics.current_line(Duration::from_millis(2000), 100),
+            Some(0)
+        );
+        assert_eq!(
+            Lyrics::parse_text("One").current_line(Duration::from_secs(5), 0),
+            None
+        );
+    }
+}
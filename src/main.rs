@@ -1,10 +1,13 @@
-use yatui::ui::{app::App, hook::install_hooks, log::initialize_logging};
+use yatui::{
+    config::Args,
+    ui::{app::App, hook::install_hooks, log::initialize_logging},
+};
 
 #[tokio::main]
 async fn main() -> color_eyre::Result<()> {
     setup()?;
 
-    let mut app = App::new().await?;
+    let mut app = App::new(Args::parse()).await?;
     app.run().await
 }
 
@@ -0,0 +1,118 @@
+use std::{
+    collections::VecDeque,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Mutex,
+    },
+    time::{Duration, Instant},
+};
+
+use lazy_static::lazy_static;
+
+/// Time span over which the download throughput is averaged.
+const THROUGHPUT_WINDOW: Duration = Duration::from_secs(5);
+
+lazy_static! {
+    pub static ref METRICS: Metrics = Metrics::default();
+}
+
+/// Playback and network statistics, updated by the streaming and audio
+/// modules and shown in the diagnostics overlay.
+#[derive(Default)]
+pub struct Metrics {
+    samples: Mutex<VecDeque<(Instant, u64)>>,
+    latency: AtomicU64,
+    requests: AtomicU64,
+    retries: AtomicU64,
+    buffered: AtomicU64,
+    buffer_capacity: AtomicU64,
+    underruns: AtomicU64,
+    output: Mutex<Option<String>>,
+    format: Mutex<Option<(String, i32)>>,
+}
+
+/// A point-in-time copy of [`Metrics`].
+#[derive(Clone, Debug, Default)]
+pub struct MetricsSnapshot {
+    pub throughput: u64,
+    pub latency: Duration,
+    pub requests: u64,
+    pub retries: u64,
+    pub buffered: u64,
+    pub buffer_capacity: u64,
+    pub underruns: u64,
+    pub output: Option<String>,
+    pub format: Option<(String, i32)>,
+}
+
+impl Metrics {
+    pub fn record_download(&self, bytes: u64) {
+        let now = Instant::now();
+        let mut samples = self.samples.lock().unwrap();
+        samples.push_back((now, bytes));
+        Self::expire(&mut samples, now);
+    }
+
+    /// Records the time it took for a request to receive its response
+    /// headers.
+    pub fn record_latency(&self, latency: Duration) {
+        self.requests.fetch_add(1, Ordering::Relaxed);
+        self.latency
+            .store(latency.as_millis() as u64, Ordering::Relaxed);
+    }
+
+    pub fn record_retry(&self) {
+        self.retries.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Records that the decoder had to wait for data.
+    pub fn record_underrun(&self) {
+        self.underruns.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn set_buffer_fill(&self, buffered: u64, capacity: u64) {
+        self.buffered.store(buffered, Ordering::Relaxed);
+        self.buffer_capacity.store(capacity, Ordering::Relaxed);
+    }
+
+    pub fn set_output(&self, output: String) {
+        *self.output.lock().unwrap() = Some(output);
+    }
+
+    pub fn set_format(&self, codec: String, bitrate: i32) {
+        *self.format.lock().unwrap() = Some((codec, bitrate));
+    }
+
+    pub fn snapshot(&self) -> MetricsSnapshot {
+        MetricsSnapshot {
+            throughput: self.throughput(),
+            latency: Duration::from_millis(
+                self.latency.load(Ordering::Relaxed),
+            ),
+            requests: self.requests.load(Ordering::Relaxed),
+            retries: self.retries.load(Ordering::Relaxed),
+            buffered: self.buffered.load(Ordering::Relaxed),
+            buffer_capacity: self.buffer_capacity.load(Ordering::Relaxed),
+            underruns: self.underruns.load(Ordering::Relaxed),
+            output: self.output.lock().unwrap().clone(),
+            format: self.format.lock().unwrap().clone(),
+        }
+    }
+
+    /// Average download rate over the last few seconds in bytes per second.
+    fn throughput(&self) -> u64 {
+        let mut samples = self.samples.lock().unwrap();
+        Self::expire(&mut samples, Instant::now());
+        let bytes = samples.iter().map(|(_, bytes)| bytes).sum::<u64>();
+
+        bytes / THROUGHPUT_WINDOW.as_secs()
+    }
+
+    fn expire(samples: &mut VecDeque<(Instant, u64)>, now: Instant) {
+        while samples.front().is_some_and(|(time, _)| {
+            now.duration_since(*time) > THROUGHPUT_WINDOW
+        }) {
+            samples.pop_front();
+        }
+    }
+}
@@ -0,0 +1,120 @@
+use std::{io::Write, sync::Arc};
+
+use tracing::{info, warn};
+use yandex_music::{model::track_model::track::Track, YandexMusicClient};
+
+use crate::{
+    api::library::{
+        fetch_album, fetch_liked_tracks, fetch_playlist, fetch_uid,
+    },
+    audio::playback::utils::{fetch_track_url, TrackUrl},
+    stream::bandwidth::BANDWIDTH,
+};
+
+use super::store::{CollectionSource, OfflineStore};
+
+/// Refreshes the track lists of all offline collections and downloads every
+/// track that is not stored yet.
+pub async fn download_collections(
+    client: Arc<YandexMusicClient>,
+    http: reqwest::Client,
+    store: Arc<OfflineStore>,
+) -> anyhow::Result<()> {
+    let _guard = store.download_lock.lock().await;
+    let uid = fetch_uid(&client).await?;
+
+    for collection in store.collections() {
+        let tracks =
+            match fetch_collection_tracks(&client, uid, &collection.source)
+                .await
+            {
+                Ok(tracks) => tracks,
+                Err(err) => {
+                    warn!(
+                        "Failed to sync collection \"{}\": {:#}",
+                        collection.title, err
+                    );
+                    continue;
+                }
+            };
+        store.update_collection(
+            &collection.source,
+            tracks.iter().map(|t| t.id).collect(),
+        )?;
+
+        for track in tracks {
+            if store.contains(track.id) {
+                continue;
+            }
+
+            let track_id = track.id;
+            if let Err(err) =
+                download_track(&client, &http, &store, track).await
+            {
+                warn!("Failed to download track {}: {}", track_id, err);
+            }
+        }
+
+        info!("Collection \"{}\" is available offline", collection.title);
+    }
+
+    Ok(())
+}
+
+pub async fn fetch_collection_tracks(
+    client: &YandexMusicClient,
+    uid: i32,
+    source: &CollectionSource,
+) -> anyhow::Result<Vec<Track>> {
+    Ok(match source {
+        CollectionSource::Liked => fetch_liked_tracks(client, uid).await?,
+        CollectionSource::Playlist { uid, kind } => {
+            fetch_playlist(client, *uid, *kind)
+                .await?
+                .tracks
+                .into_iter()
+                .filter_map(|t| t.track)
+                .collect()
+        }
+        CollectionSource::Album { id } => fetch_album(client, *id)
+            .await?
+            .volumes
+            .into_iter()
+            .flatten()
+            .collect(),
+    })
+}
+
+async fn download_track(
+    client: &YandexMusicClient,
+    http: &reqwest::Client,
+    store: &OfflineStore,
+    track: Track,
+) -> anyhow::Result<()> {
+    let TrackUrl {
+        url,
+        codec,
+        bitrate,
+        key,
+    } = fetch_track_url(client, http, track.id, false).await?;
+    let mut response = http.get(url).send().await?.error_for_status()?;
+
+    // The audio is written to disk as it arrives instead of being held in
+    // memory as a whole.
+    let mut file = store.create_download(track.id, &codec, bitrate)?;
+    let mut offset = 0;
+    while let Some(chunk) = response.chunk().await? {
+        BANDWIDTH.consume(chunk.len() as u64).await;
+        let mut chunk = chunk.to_vec();
+        if let Some(key) = key {
+            key.decrypt(offset, &mut chunk);
+        }
+        file.write_all(&chunk)?;
+        offset += chunk.len() as u64;
+    }
+    file.sync_all()?;
+
+    store.store_track(track, codec, bitrate)?;
+
+    Ok(())
+}
@@ -0,0 +1,3 @@
+pub mod download;
+pub mod store;
+pub mod sync;
@@ -0,0 +1,277 @@
+use std::{
+    collections::{HashMap, HashSet},
+    fs::{self, File},
+    io,
+    path::PathBuf,
+    sync::Mutex,
+};
+
+use serde::{Deserialize, Serialize};
+use yandex_music::model::track_model::track::Track;
+
+use crate::stream::cache::AudioCache;
+
+const INDEX_FILE: &str = "index.json";
+const AUDIO_FOLDER: &str = "audio";
+
+#[derive(Clone, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum CollectionSource {
+    Liked,
+    Playlist { uid: i32, kind: i32 },
+    Album { id: i32 },
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct OfflineCollection {
+    pub source: CollectionSource,
+    pub title: String,
+    #[serde(default)]
+    pub track_ids: Vec<i32>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct OfflineTrack {
+    pub track: Track,
+    pub codec: String,
+    pub bitrate: i32,
+}
+
+#[derive(Default, Serialize, Deserialize)]
+struct StoreIndex {
+    collections: Vec<OfflineCollection>,
+    tracks: HashMap<i32, OfflineTrack>,
+}
+
+/// Permanent storage for collections marked as available offline.
+///
+/// Unlike [`AudioCache`], nothing in here is evicted until the collection
+/// is unmarked.
+pub struct OfflineStore {
+    directory: PathBuf,
+    index: Mutex<StoreIndex>,
+    pub(super) download_lock: tokio::sync::Mutex<()>,
+}
+
+impl OfflineStore {
+    pub fn open(directory: PathBuf) -> io::Result<Self> {
+        fs::create_dir_all(directory.join(AUDIO_FOLDER))?;
+
+        let index = match fs::read(directory.join(INDEX_FILE)) {
+            Ok(bytes) => serde_json::from_slice(&bytes)?,
+            Err(err) if err.kind() == io::ErrorKind::NotFound => {
+                StoreIndex::default()
+            }
+            Err(err) => return Err(err),
+        };
+
+        Ok(Self {
+            directory,
+            index: Mutex::new(index),
+            download_lock: tokio::sync::Mutex::new(()),
+        })
+    }
+
+    pub fn collections(&self) -> Vec<OfflineCollection> {
+        self.index.lock().unwrap().collections.clone()
+    }
+
+    pub fn is_marked(&self, source: &CollectionSource) -> bool {
+        self.index
+            .lock()
+            .unwrap()
+            .collections
+            .iter()
+            .any(|c| &c.source == source)
+    }
+
+    pub fn mark(
+        &self,
+        source: CollectionSource,
+        title: String,
+    ) -> io::Result<()> {
+        let mut index = self.index.lock().unwrap();
+        if index.collections.iter().any(|c| c.source == source) {
+            return Ok(());
+        }
+
+        index.collections.push(OfflineCollection {
+            source,
+            title,
+            track_ids: Vec::new(),
+        });
+
+        self.save(&index)
+    }
+
+    /// Removes the collection and deletes every track that no other offline
+    /// collection refers to.
+    pub fn unmark(&self, source: &CollectionSource) -> io::Result<()> {
+        let mut index = self.index.lock().unwrap();
+        index.collections.retain(|c| &c.source != source);
+        self.remove_orphans(&mut index);
+
+        self.save(&index)
+    }
+
+    /// Replaces the track list of a collection and deletes the tracks that
+    /// were removed from it, unless another offline collection refers to
+    /// them.
+    pub fn update_collection(
+        &self,
+        source: &CollectionSource,
+        track_ids: Vec<i32>,
+    ) -> io::Result<()> {
+        let mut index = self.index.lock().unwrap();
+        if let Some(collection) =
+            index.collections.iter_mut().find(|c| &c.source == source)
+        {
+            collection.track_ids = track_ids;
+        }
+        self.remove_orphans(&mut index);
+
+        self.save(&index)
+    }
+
+    pub fn contains(&self, track_id: i32) -> bool {
+        self.index.lock().unwrap().tracks.contains_key(&track_id)
+    }
+
+    /// Returns every downloaded track in collection order without
+    /// duplicates.
+    pub fn tracks(&self) -> Vec<Track> {
+        let index = self.index.lock().unwrap();
+        let mut seen = HashSet::new();
+
+        index
+            .collections
+            .iter()
+            .flat_map(|c| c.track_ids.iter())
+            .filter(|id| seen.insert(**id))
+            .filter_map(|id| index.tracks.get(id))
+            .map(|t| t.track.clone())
+            .collect()
+    }
+
+    /// Returns the downloaded tracks of a collection in its order.
+    pub fn collection_tracks(&self, source: &CollectionSource) -> Vec<Track> {
+        let index = self.index.lock().unwrap();
+        index
+            .collections
+            .iter()
+            .find(|c| &c.source == source)
+            .into_iter()
+            .flat_map(|c| c.track_ids.iter())
+            .filter_map(|id| index.tracks.get(id))
+            .map(|t| t.track.clone())
+            .collect()
+    }
+
+    /// Creates the file the audio of a track is downloaded into. It is
+    /// added to the store by [`OfflineStore::store_track`] once complete.
+    pub fn create_download(
+        &self,
+        track_id: i32,
+        codec: &str,
+        bitrate: i32,
+    ) -> io::Result<File> {
+        File::create(self.download_path(track_id, codec, bitrate))
+    }
+
+    /// Adds a track whose audio was written to the file from
+    /// [`OfflineStore::create_download`]. It is discarded if its collection
+    /// was unmarked in the meantime.
+    pub fn store_track(
+        &self,
+        track: Track,
+        codec: String,
+        bitrate: i32,
+    ) -> io::Result<()> {
+        let path = self.audio_path(track.id, &codec, bitrate);
+        let download_path = self.download_path(track.id, &codec, bitrate);
+        let is_referenced = self
+            .index
+            .lock()
+            .unwrap()
+            .collections
+            .iter()
+            .any(|c| c.track_ids.contains(&track.id));
+        if !is_referenced {
+            return fs::remove_file(download_path);
+        }
+
+        fs::rename(&download_path, &path)?;
+
+        let mut index = self.index.lock().unwrap();
+        index.tracks.insert(
+            track.id,
+            OfflineTrack {
+                track,
+                codec,
+                bitrate,
+            },
+        );
+
+        self.save(&index)
+    }
+
+    /// Opens the downloaded audio of a track along with its codec and
+    /// bitrate.
+    pub fn open_audio(&self, track_id: i32) -> Option<(File, String, i32)> {
+        let index = self.index.lock().unwrap();
+        let track = index.tracks.get(&track_id)?;
+        let file =
+            File::open(self.audio_path(track_id, &track.codec, track.bitrate))
+                .ok()?;
+
+        Some((file, track.codec.clone(), track.bitrate))
+    }
+
+    fn audio_path(&self, track_id: i32, codec: &str, bitrate: i32) -> PathBuf {
+        self.directory
+            .join(AUDIO_FOLDER)
+            .join(AudioCache::key(track_id, codec, bitrate))
+    }
+
+    fn download_path(
+        &self,
+        track_id: i32,
+        codec: &str,
+        bitrate: i32,
+    ) -> PathBuf {
+        self.audio_path(track_id, codec, bitrate)
+            .with_extension("part")
+    }
+
+    /// Deletes every track that no offline collection refers to.
+    fn remove_orphans(&self, index: &mut StoreIndex) {
+        let referenced = index
+            .collections
+            .iter()
+            .flat_map(|c| c.track_ids.iter().copied())
+            .collect::<HashSet<_>>();
+        let orphaned = index
+            .tracks
+            .keys()
+            .filter(|id| !referenced.contains(id))
+            .copied()
+            .collect::<Vec<_>>();
+
+        for track_id in orphaned {
+            if let Some(track) = index.tracks.remove(&track_id) {
+                let _ = fs::remove_file(self.audio_path(
+                    track_id,
+                    &track.codec,
+                    track.bitrate,
+                ));
+            }
+        }
+    }
+
+    fn save(&self, index: &StoreIndex) -> io::Result<()> {
+        let path = self.directory.join(INDEX_FILE);
+        let temp_path = path.with_extension("tmp");
+        fs::write(&temp_path, serde_json::to_vec(index)?)?;
+        fs::rename(temp_path, path)
+    }
+}
@@ -0,0 +1,280 @@
+use std::{
+    fs, io,
+    path::PathBuf,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, Mutex,
+    },
+    time::Duration,
+};
+
+use serde::{Deserialize, Serialize};
+use tracing::{info, warn};
+use yandex_music::YandexMusicClient;
+
+use crate::api::{
+    library::{
+        dislike_tracks, fetch_uid, like_tracks, undislike_tracks, unlike_tracks,
+    },
+    playlist::{edit_playlist, set_playlist_visibility, PlaylistEdit},
+    plays::{send_plays, PlayReport},
+};
+
+/// How long play reports are collected before they are sent together.
+const PLAYS_DELAY: Duration = Duration::from_secs(30);
+
+/// Most play reports sent in one request.
+const PLAYS_BATCH_SIZE: usize = 50;
+
+/// An account change that still has to be sent to the API, e.g. because it
+/// was made while offline.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum PendingAction {
+    Like {
+        track_id: i32,
+    },
+    Unlike {
+        track_id: i32,
+    },
+    Dislike {
+        track_id: i32,
+    },
+    Undislike {
+        track_id: i32,
+    },
+    AddToPlaylist {
+        kind: i32,
+        track_id: i32,
+        album_id: Option<i32>,
+    },
+    RemoveFromPlaylist {
+        kind: i32,
+        track_id: i32,
+    },
+    MoveInPlaylist {
+        kind: i32,
+        track_id: i32,
+        from: usize,
+        to: usize,
+    },
+    SetPlaylistVisibility {
+        kind: i32,
+        is_public: bool,
+    },
+}
+
+/// Persistent queue of [`PendingAction`]s.
+pub struct PendingQueue {
+    path: PathBuf,
+    actions: Mutex<Vec<PendingAction>>,
+    flush_lock: tokio::sync::Mutex<()>,
+}
+
+impl PendingQueue {
+    pub fn open(path: PathBuf) -> io::Result<Self> {
+        let actions = match fs::read(&path) {
+            Ok(bytes) => serde_json::from_slice(&bytes)?,
+            Err(err) if err.kind() == io::ErrorKind::NotFound => Vec::new(),
+            Err(err) => return Err(err),
+        };
+
+        Ok(Self {
+            path,
+            actions: Mutex::new(actions),
+            flush_lock: tokio::sync::Mutex::new(()),
+        })
+    }
+
+    pub fn push(&self, action: PendingAction) -> io::Result<()> {
+        let mut actions = self.actions.lock().unwrap();
+        actions.push(action);
+        self.save(&actions)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.actions.lock().unwrap().is_empty()
+    }
+
+    /// Sends queued actions in order, stopping at the first failure so that
+    /// the remaining ones are retried on the next sync.
+    pub async fn flush(
+        &self,
+        client: &YandexMusicClient,
+    ) -> anyhow::Result<()> {
+        let _lock = self.flush_lock.lock().await;
+        if self.is_empty() {
+            return Ok(());
+        }
+
+        let uid = fetch_uid(client).await?;
+        loop {
+            let Some(action) = self.actions.lock().unwrap().first().cloned()
+            else {
+                break;
+            };
+
+            if let Err(err) = apply(client, uid, &action).await {
+                warn!("Failed to sync {:?}: {}", action, err);
+                return Err(err);
+            }
+
+            info!("Synced {:?}", action);
+            let mut actions = self.actions.lock().unwrap();
+            actions.remove(0);
+            self.save(&actions)?;
+        }
+
+        Ok(())
+    }
+
+    fn save(&self, actions: &[PendingAction]) -> io::Result<()> {
+        let temp_path = self.path.with_extension("tmp");
+        fs::write(&temp_path, serde_json::to_vec(actions)?)?;
+        fs::rename(temp_path, &self.path)
+    }
+}
+
+/// Persistent queue of [`PlayReport`]s, which are sent in batches. Reports
+/// that can't be sent stay queued and go out with the next batch.
+pub struct PendingPlays {
+    path: PathBuf,
+    reports: Mutex<Vec<PlayReport>>,
+    flush_lock: tokio::sync::Mutex<()>,
+    is_scheduled: AtomicBool,
+}
+
+impl PendingPlays {
+    pub fn open(path: PathBuf) -> io::Result<Self> {
+        let reports = match fs::read(&path) {
+            Ok(bytes) => serde_json::from_slice(&bytes)?,
+            Err(err) if err.kind() == io::ErrorKind::NotFound => Vec::new(),
+            Err(err) => return Err(err),
+        };
+
+        Ok(Self {
+            path,
+            reports: Mutex::new(reports),
+            flush_lock: tokio::sync::Mutex::new(()),
+            is_scheduled: AtomicBool::new(false),
+        })
+    }
+
+    pub fn push(&self, report: PlayReport) -> io::Result<()> {
+        let mut reports = self.reports.lock().unwrap();
+        reports.push(report);
+        self.save(&reports)
+    }
+
+    /// Sends the queued reports after [`PLAYS_DELAY`], so that the reports
+    /// made in the meantime are sent along with them.
+    pub fn schedule_flush(self: &Arc<Self>, client: Arc<YandexMusicClient>) {
+        if self.is_scheduled.swap(true, Ordering::AcqRel) {
+            return;
+        }
+
+        let plays = self.clone();
+        tokio::spawn(async move {
+            tokio::time::sleep(PLAYS_DELAY).await;
+            plays.is_scheduled.store(false, Ordering::Release);
+            if let Err(err) = plays.flush(&client).await {
+                warn!("Failed to send play reports: {:#}", err);
+            }
+        });
+    }
+
+    /// Sends all queued reports in batches of [`PLAYS_BATCH_SIZE`].
+    pub async fn flush(
+        &self,
+        client: &YandexMusicClient,
+    ) -> anyhow::Result<()> {
+        let _lock = self.flush_lock.lock().await;
+        loop {
+            let batch = {
+                let reports = self.reports.lock().unwrap();
+                reports[..reports.len().min(PLAYS_BATCH_SIZE)].to_vec()
+            };
+            if batch.is_empty() {
+                break;
+            }
+
+            send_plays(client, &batch).await?;
+
+            info!("Sent {} play reports", batch.len());
+            let mut reports = self.reports.lock().unwrap();
+            reports.drain(..batch.len());
+            self.save(&reports)?;
+        }
+
+        Ok(())
+    }
+
+    fn save(&self, reports: &[PlayReport]) -> io::Result<()> {
+        let temp_path = self.path.with_extension("tmp");
+        fs::write(&temp_path, serde_json::to_vec(reports)?)?;
+        fs::rename(temp_path, &self.path)
+    }
+}
+
+async fn apply(
+    client: &YandexMusicClient,
+    uid: i32,
+    action: &PendingAction,
+) -> anyhow::Result<()> {
+    match action {
+        PendingAction::Like { track_id } => {
+            like_tracks(client, uid, &[*track_id]).await
+        }
+        PendingAction::Unlike { track_id } => {
+            unlike_tracks(client, uid, &[*track_id]).await
+        }
+        PendingAction::Dislike { track_id } => {
+            dislike_tracks(client, uid, &[*track_id]).await
+        }
+        PendingAction::Undislike { track_id } => {
+            undislike_tracks(client, uid, &[*track_id]).await
+        }
+        PendingAction::AddToPlaylist {
+            kind,
+            track_id,
+            album_id,
+        } => {
+            let edit = PlaylistEdit::Insert {
+                at: None,
+                tracks: vec![(*track_id, *album_id)],
+            };
+            edit_playlist(client, uid, *kind, &edit).await?;
+
+            Ok(())
+        }
+        PendingAction::RemoveFromPlaylist { kind, track_id } => {
+            let edit = PlaylistEdit::Remove {
+                track_id: *track_id,
+                index: None,
+            };
+            edit_playlist(client, uid, *kind, &edit).await?;
+
+            Ok(())
+        }
+        PendingAction::MoveInPlaylist {
+            kind,
+            track_id,
+            from,
+            to,
+        } => {
+            let edit = PlaylistEdit::Move {
+                track_id: *track_id,
+                from: *from,
+                to: *to,
+            };
+            edit_playlist(client, uid, *kind, &edit).await?;
+
+            Ok(())
+        }
+        PendingAction::SetPlaylistVisibility { kind, is_public } => {
+            set_playlist_visibility(client, uid, *kind, *is_public).await?;
+
+            Ok(())
+        }
+    }
+}
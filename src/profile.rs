@@ -0,0 +1,265 @@
+use std::{
+    fs,
+    io::{self, Write},
+    path::PathBuf,
+};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{config::CONFIG, ui::log::get_data_dir};
+
+/// The profile used if none is chosen. It keeps its data directly in the
+/// data directory, where it was stored before there were profiles.
+pub const DEFAULT_PROFILE: &str = "default";
+
+/// Settings remembered separately for each profile.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct ProfileSettings {
+    pub volume: u8,
+    pub data_saver: bool,
+}
+
+impl Default for ProfileSettings {
+    fn default() -> Self {
+        Self {
+            volume: 100,
+            data_saver: CONFIG.data_saver,
+        }
+    }
+}
+
+/// An account along with its own settings, cache and history.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Profile {
+    pub name: String,
+    /// OAuth token of the account. The default profile falls back to
+    /// `YANDEX_MUSIC_TOKEN` if it has none.
+    #[serde(default)]
+    pub token: Option<String>,
+    #[serde(default)]
+    pub settings: ProfileSettings,
+}
+
+impl Profile {
+    fn new(name: &str) -> Self {
+        Self {
+            name: name.to_string(),
+            token: None,
+            settings: ProfileSettings::default(),
+        }
+    }
+
+    pub fn token(&self) -> Option<String> {
+        match &self.token {
+            Some(token) => Some(token.clone()),
+            None if self.name == DEFAULT_PROFILE => {
+                std::env::var("YANDEX_MUSIC_TOKEN").ok()
+            }
+            None => None,
+        }
+    }
+
+    /// Where the cache, offline tracks and history of the profile are
+    /// stored.
+    pub fn directory(&self) -> PathBuf {
+        if self.name == DEFAULT_PROFILE {
+            get_data_dir()
+        } else {
+            get_data_dir().join("profiles").join(&self.name)
+        }
+    }
+}
+
+#[derive(Default, Serialize, Deserialize)]
+struct ProfileIndex {
+    #[serde(default)]
+    profiles: Vec<Profile>,
+    /// The profile that was used last, which is chosen on the next start.
+    last: Option<String>,
+}
+
+/// The stored profiles.
+pub struct Profiles {
+    path: PathBuf,
+    index: ProfileIndex,
+}
+
+impl Profiles {
+    pub fn open() -> io::Result<Self> {
+        Self::load(get_data_dir().join("profiles.json"))
+    }
+
+    fn load(path: PathBuf) -> io::Result<Self> {
+        let index = match fs::read(&path) {
+            Ok(bytes) => serde_json::from_slice(&bytes)?,
+            Err(err) if err.kind() == io::ErrorKind::NotFound => {
+                ProfileIndex::default()
+            }
+            Err(err) => return Err(err),
+        };
+
+        Ok(Self { path, index })
+    }
+
+    /// Names of all profiles, starting with the default one.
+    pub fn names(&self) -> Vec<String> {
+        let mut names = vec![DEFAULT_PROFILE.to_string()];
+        names.extend(
+            self.index
+                .profiles
+                .iter()
+                .map(|profile| profile.name.clone())
+                .filter(|name| name != DEFAULT_PROFILE),
+        );
+
+        names
+    }
+
+    pub fn get(&self, name: &str) -> Option<Profile> {
+        match self.position(name) {
+            Some(index) => Some(self.index.profiles[index].clone()),
+            None if name == DEFAULT_PROFILE => Some(Profile::new(name)),
+            None => None,
+        }
+    }
+
+    /// Returns the profile called `name`, or the one used last if no name is
+    /// given, and remembers it as the last one. The profile is created if it
+    /// doesn't exist, and `token` replaces its token if given.
+    ///
+    /// A new profile needs a token, otherwise it couldn't log in and would
+    /// be chosen again on every start.
+    pub fn select(
+        &mut self,
+        name: Option<&str>,
+        token: Option<String>,
+    ) -> io::Result<Profile> {
+        let name = name
+            .or(self.index.last.as_deref())
+            .unwrap_or(DEFAULT_PROFILE)
+            .to_string();
+        if name.is_empty()
+            || !name
+                .chars()
+                .all(|c| c.is_alphanumeric() || c == '-' || c == '_')
+        {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("invalid profile name: {:?}", name),
+            ));
+        }
+
+        let index = match self.position(&name) {
+            Some(index) => index,
+            None if token.is_none() && name != DEFAULT_PROFILE => {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidInput,
+                    format!(
+                        "profile {} doesn't exist, pass --token to create it",
+                        name
+                    ),
+                ));
+            }
+            None => {
+                self.index.profiles.push(Profile::new(&name));
+                self.index.profiles.len() - 1
+            }
+        };
+        if token.is_some() {
+            self.index.profiles[index].token = token;
+        }
+        self.index.last = Some(name);
+        self.save()?;
+
+        Ok(self.index.profiles[index].clone())
+    }
+
+    /// Remembers the settings of a profile for the next time it is used.
+    pub fn save_settings(
+        &mut self,
+        name: &str,
+        settings: ProfileSettings,
+    ) -> io::Result<()> {
+        if let Some(index) = self.position(name) {
+            self.index.profiles[index].settings = settings;
+            self.save()?;
+        }
+
+        Ok(())
+    }
+
+    fn position(&self, name: &str) -> Option<usize> {
+        self.index
+            .profiles
+            .iter()
+            .position(|profile| profile.name == name)
+    }
+
+    /// Saves the profiles, which contain tokens, so that only the current
+    /// user can read them.
+    fn save(&self) -> io::Result<()> {
+        if let Some(parent) = self.path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+
+        // The file is created with its final permissions, so the tokens are
+        // never readable by others, not even briefly. A leftover temporary
+        // file could have other permissions, so it is replaced.
+        let temp_path = self.path.with_extension("tmp");
+        match fs::remove_file(&temp_path) {
+            Err(err) if err.kind() != io::ErrorKind::NotFound => {
+                return Err(err)
+            }
+            _ => {}
+        }
+
+        let mut options = fs::OpenOptions::new();
+        options.write(true).create_new(true);
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::OpenOptionsExt;
+            options.mode(0o600);
+        }
+        options
+            .open(&temp_path)?
+            .write_all(&serde_json::to_vec_pretty(&self.index)?)?;
+        fs::rename(temp_path, &self.path)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::TempDir;
+
+    #[test]
+    fn does_not_create_a_profile_without_a_token() {
+        let directory = TempDir::new("profiles-no-token");
+        let path = directory.join("profiles.json");
+        let mut profiles = Profiles::load(path.clone()).unwrap();
+
+        let err = profiles.select(Some("work"), None).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidInput);
+        assert_eq!(profiles.names(), [DEFAULT_PROFILE]);
+        assert!(!path.exists());
+
+        // The next plain start still uses the default profile.
+        let profile = profiles.select(None, None).unwrap();
+        assert_eq!(profile.name, DEFAULT_PROFILE);
+    }
+
+    #[test]
+    fn remembers_a_new_profile_with_a_token() {
+        let directory = TempDir::new("profiles-token");
+        let path = directory.join("profiles.json");
+        let mut profiles = Profiles::load(path.clone()).unwrap();
+        profiles
+            .select(Some("work"), Some("token".to_string()))
+            .unwrap();
+
+        let mut profiles = Profiles::load(path).unwrap();
+        let profile = profiles.select(None, None).unwrap();
+        assert_eq!(profile.name, "work");
+        assert_eq!(profile.token.as_deref(), Some("token"));
+    }
+}
@@ -0,0 +1,104 @@
+use std::{
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Mutex,
+    },
+    time::{Duration, Instant},
+};
+
+use lazy_static::lazy_static;
+use tokio_util::bytes::{Bytes, BytesMut};
+
+use crate::{config::CONFIG, metrics::METRICS};
+
+lazy_static! {
+    pub static ref BANDWIDTH: BandwidthLimiter =
+        BandwidthLimiter::new(CONFIG.rate_limit);
+}
+
+/// Counts downloaded audio and caps the download rate shared by all
+/// streams.
+pub struct BandwidthLimiter {
+    downloaded: AtomicU64,
+    rate: AtomicU64,
+    bucket: Mutex<Bucket>,
+}
+
+struct Bucket {
+    available: f64,
+    updated: Instant,
+}
+
+impl BandwidthLimiter {
+    pub fn new(rate: u64) -> Self {
+        Self {
+            downloaded: AtomicU64::new(0),
+            rate: AtomicU64::new(rate),
+            bucket: Mutex::new(Bucket {
+                available: rate as f64,
+                updated: Instant::now(),
+            }),
+        }
+    }
+
+    /// Total number of bytes downloaded in this session.
+    pub fn downloaded(&self) -> u64 {
+        self.downloaded.load(Ordering::Relaxed)
+    }
+
+    /// Download rate limit in bytes per second. `0` means unlimited.
+    pub fn rate(&self) -> u64 {
+        self.rate.load(Ordering::Relaxed)
+    }
+
+    pub fn set_rate(&self, rate: u64) {
+        self.rate.store(rate, Ordering::Relaxed);
+    }
+
+    /// Records `bytes` as downloaded and waits for as long as needed to stay
+    /// within the rate limit.
+    pub async fn consume(&self, bytes: u64) {
+        self.downloaded.fetch_add(bytes, Ordering::Relaxed);
+        METRICS.record_download(bytes);
+
+        let rate = self.rate();
+        if rate == 0 {
+            return;
+        }
+
+        let delay = {
+            let mut bucket = self.bucket.lock().unwrap();
+            let now = Instant::now();
+            let refill =
+                now.duration_since(bucket.updated).as_secs_f64() * rate as f64;
+            bucket.available = (bucket.available + refill).min(rate as f64);
+            bucket.updated = now;
+            bucket.available -= bytes as f64;
+
+            if bucket.available < 0.0 {
+                Duration::from_secs_f64(-bucket.available / rate as f64)
+            } else {
+                Duration::ZERO
+            }
+        };
+
+        if !delay.is_zero() {
+            tokio::time::sleep(delay).await;
+        }
+    }
+}
+
+/// Reads the whole body of `response`, accounting for every chunk.
+pub async fn read_body(
+    mut response: reqwest::Response,
+) -> reqwest::Result<Bytes> {
+    let mut body = BytesMut::with_capacity(
+        response.content_length().unwrap_or(0) as usize,
+    );
+    while let Some(chunk) = response.chunk().await? {
+        BANDWIDTH.consume(chunk.len() as u64).await;
+        body.extend_from_slice(&chunk);
+    }
+
+    Ok(body.freeze())
+}
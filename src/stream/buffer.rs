@@ -0,0 +1,323 @@
+use std::{
+    collections::VecDeque,
+    io,
+    sync::{Condvar, Mutex},
+};
+
+use tokio::sync::Notify;
+use tokio_util::bytes::Bytes;
+
+use crate::metrics::METRICS;
+
+/// A bounded buffer of downloaded chunks shared between an async fetcher and
+/// a blocking reader.
+///
+/// The buffer holds one contiguous byte range of the stream. The fetcher may
+/// run at most `capacity` bytes ahead of the reader, and up to `history`
+/// bytes behind the reader are retained so that short backward seeks don't
+/// have to download anything again.
+pub struct StreamBuffer {
+    state: Mutex<BufferState>,
+    changed: Condvar,
+    writer: Notify,
+    capacity: u64,
+    history: u64,
+}
+
+struct BufferState {
+    chunks: VecDeque<Bytes>,
+    start: u64,
+    end: u64,
+    read_position: u64,
+    error: Option<io::Error>,
+    finished: bool,
+    closed: bool,
+}
+
+impl StreamBuffer {
+    pub fn new(capacity: u64, history: u64) -> Self {
+        Self {
+            state: Mutex::new(BufferState {
+                chunks: VecDeque::new(),
+                start: 0,
+                end: 0,
+                read_position: 0,
+                error: None,
+                finished: false,
+                closed: false,
+            }),
+            changed: Condvar::new(),
+            writer: Notify::new(),
+            capacity,
+            history,
+        }
+    }
+
+    /// Waits until there is room for more data and returns the offset the
+    /// fetcher should continue from, or `None` once the reader is gone.
+    pub async fn next_write_position(&self, total_bytes: u64) -> Option<u64> {
+        loop {
+            let notified = self.writer.notified();
+            {
+                let state = self.state.lock().unwrap();
+                if state.closed {
+                    return None;
+                }
+
+                if state.end < total_bytes
+                    && state.end - state.read_position < self.capacity
+                {
+                    return Some(state.end);
+                }
+            }
+
+            notified.await;
+        }
+    }
+
+    /// Appends bytes that were fetched starting at `offset`.
+    ///
+    /// Returns `false` and discards the data if the reader has seeked
+    /// elsewhere in the meantime.
+    pub fn push(&self, offset: u64, bytes: Bytes) -> bool {
+        let mut state = self.state.lock().unwrap();
+        if state.closed || offset != state.end {
+            return false;
+        }
+
+        state.end += bytes.len() as u64;
+        state.chunks.push_back(bytes);
+        self.report(&state);
+        self.changed.notify_all();
+
+        true
+    }
+
+    /// Reports an unrecoverable fetch error to the reader.
+    pub fn fail(&self, err: io::Error) {
+        self.state.lock().unwrap().error = Some(err);
+        self.changed.notify_all();
+    }
+
+    /// Marks the end of a stream whose length wasn't known in advance.
+    pub fn finish(&self) {
+        self.state.lock().unwrap().finished = true;
+        self.changed.notify_all();
+    }
+
+    /// Whether `position` lies within the data that is currently buffered.
+    pub fn is_buffered(&self, position: u64) -> bool {
+        let state = self.state.lock().unwrap();
+        position >= state.start && position <= state.end
+    }
+
+    /// Tells the fetcher that nobody is going to read from the buffer
+    /// anymore.
+    pub fn close(&self) {
+        self.state.lock().unwrap().closed = true;
+        METRICS.set_buffer_fill(0, self.capacity);
+        self.changed.notify_all();
+        self.writer.notify_waiters();
+    }
+
+    /// Moves the read position, dropping all buffered data if `position` lies
+    /// outside of it.
+    pub fn seek(&self, position: u64) {
+        let mut state = self.state.lock().unwrap();
+        if position >= state.start && position <= state.end {
+            state.read_position = position;
+        } else {
+            state.chunks.clear();
+            state.start = position;
+            state.end = position;
+            state.read_position = position;
+        }
+        self.report(&state);
+        self.writer.notify_waiters();
+    }
+
+    /// Copies bytes starting at the read position into `buf`, blocking until
+    /// at least one byte is available.
+    pub fn read(&self, buf: &mut [u8]) -> io::Result<usize> {
+        if buf.is_empty() {
+            return Ok(0);
+        }
+
+        let mut state = self.state.lock().unwrap();
+        if state.read_position == state.end
+            && state.read_position > 0
+            && state.error.is_none()
+            && !state.finished
+        {
+            METRICS.record_underrun();
+        }
+        while state.read_position == state.end {
+            if let Some(err) = state.error.as_ref() {
+                return Err(io::Error::new(err.kind(), err.to_string()));
+            }
+
+            if state.finished {
+                return Ok(0);
+            }
+
+            state = self.changed.wait(state).unwrap();
+        }
+
+        let position = state.read_position;
+        let mut offset = state.start;
+        let mut copied = 0;
+        for chunk in &state.chunks {
+            let chunk_end = offset + chunk.len() as u64;
+            let current = position + copied as u64;
+            if chunk_end > current {
+                let from = (current - offset) as usize;
+                let amount = (chunk.len() - from).min(buf.len() - copied);
+                buf[copied..copied + amount]
+                    .copy_from_slice(&chunk[from..from + amount]);
+                copied += amount;
+
+                if copied == buf.len() {
+                    break;
+                }
+            }
+            offset = chunk_end;
+        }
+
+        state.read_position += copied as u64;
+        self.trim(&mut state);
+        self.report(&state);
+        self.writer.notify_waiters();
+
+        Ok(copied)
+    }
+
+    fn report(&self, state: &BufferState) {
+        METRICS.set_buffer_fill(state.end - state.read_position, self.capacity);
+    }
+
+    fn trim(&self, state: &mut BufferState) {
+        while let Some(chunk) = state.chunks.front() {
+            let chunk_end = state.start + chunk.len() as u64;
+            if state.read_position < chunk_end + self.history {
+                break;
+            }
+
+            state.start = chunk_end;
+            state.chunks.pop_front();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{sync::Arc, thread, time::Duration};
+
+    use super::*;
+
+    fn read(buffer: &StreamBuffer, len: usize) -> Vec<u8> {
+        let mut buf = vec![0; len];
+        let read = buffer.read(&mut buf).unwrap();
+        buf.truncate(read);
+        buf
+    }
+
+    #[test]
+    fn reads_across_chunks() {
+        let buffer = StreamBuffer::new(100, 0);
+        assert!(buffer.push(0, Bytes::from_static(b"abc")));
+        assert!(buffer.push(3, Bytes::from_static(b"def")));
+
+        assert_eq!(read(&buffer, 4), b"abcd");
+        assert_eq!(read(&buffer, 4), b"ef");
+    }
+
+    #[test]
+    fn discards_data_that_does_not_continue_the_buffer() {
+        let buffer = StreamBuffer::new(100, 0);
+        assert!(buffer.push(0, Bytes::from_static(b"abc")));
+
+        assert!(!buffer.push(10, Bytes::from_static(b"xyz")));
+        assert_eq!(read(&buffer, 10), b"abc");
+    }
+
+    #[test]
+    fn keeps_history_for_backward_seeks() {
+        let buffer = StreamBuffer::new(100, 4);
+        buffer.push(0, Bytes::from_static(b"abcd"));
+        buffer.push(4, Bytes::from_static(b"efgh"));
+        buffer.push(8, Bytes::from_static(b"ijkl"));
+        assert_eq!(read(&buffer, 10), b"abcdefghij");
+
+        // The first chunk lies more than `history` bytes behind the reader.
+        assert!(!buffer.is_buffered(2));
+        assert!(buffer.is_buffered(6));
+
+        buffer.seek(6);
+        assert_eq!(read(&buffer, 10), b"ghijkl");
+    }
+
+    #[test]
+    fn drops_the_data_on_a_seek_outside_of_it() {
+        let buffer = StreamBuffer::new(100, 0);
+        buffer.push(0, Bytes::from_static(b"abc"));
+
+        buffer.seek(50);
+        assert!(!buffer.is_buffered(0));
+        assert!(!buffer.push(3, Bytes::from_static(b"def")));
+        assert!(buffer.push(50, Bytes::from_static(b"xyz")));
+        assert_eq!(read(&buffer, 10), b"xyz");
+    }
+
+    #[test]
+    fn ends_once_finished() {
+        let buffer = StreamBuffer::new(100, 0);
+        buffer.push(0, Bytes::from_static(b"abc"));
+        buffer.finish();
+
+        assert_eq!(read(&buffer, 10), b"abc");
+        assert_eq!(read(&buffer, 10), b"");
+    }
+
+    #[test]
+    fn reports_errors_after_the_buffered_data() {
+        let buffer = StreamBuffer::new(100, 0);
+        buffer.push(0, Bytes::from_static(b"abc"));
+        buffer.fail(io::Error::new(io::ErrorKind::TimedOut, "timed out"));
+
+        assert_eq!(read(&buffer, 10), b"abc");
+        let err = buffer.read(&mut [0; 10]).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::TimedOut);
+    }
+
+    #[test]
+    fn blocks_the_reader_until_data_arrives() {
+        let buffer = Arc::new(StreamBuffer::new(100, 0));
+        let writer = buffer.clone();
+        let handle = thread::spawn(move || {
+            thread::sleep(Duration::from_millis(50));
+            writer.push(0, Bytes::from_static(b"abc"));
+        });
+
+        assert_eq!(read(&buffer, 10), b"abc");
+        handle.join().unwrap();
+    }
+
+    #[tokio::test]
+    async fn limits_how_far_the_fetcher_runs_ahead() {
+        let buffer = StreamBuffer::new(4, 0);
+        assert_eq!(buffer.next_write_position(100).await, Some(0));
+        buffer.push(0, Bytes::from_static(b"abcd"));
+
+        let waiting = tokio::time::timeout(
+            Duration::from_millis(50),
+            buffer.next_write_position(100),
+        );
+        assert!(waiting.await.is_err());
+
+        read(&buffer, 2);
+        assert_eq!(buffer.next_write_position(100).await, Some(4));
+
+        buffer.close();
+        assert_eq!(buffer.next_write_position(100).await, None);
+    }
+}
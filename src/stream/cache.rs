@@ -0,0 +1,323 @@
+use std::{
+    collections::HashSet,
+    fs::{self, File, OpenOptions},
+    io::{self, Read, Seek, SeekFrom, Write},
+    path::PathBuf,
+    sync::{Arc, Mutex},
+    time::SystemTime,
+};
+
+use tokio_util::bytes::Bytes;
+use tracing::warn;
+
+const PARTIAL_EXTENSION: &str = "part";
+
+/// Content-addressed audio cache with least-recently-used eviction.
+///
+/// Complete files are stored as `<key>` and downloads in progress as
+/// `<key>.part`, so an interrupted download can be resumed later on.
+pub struct AudioCache {
+    directory: PathBuf,
+    max_size: u64,
+    in_progress: Mutex<HashSet<String>>,
+}
+
+impl AudioCache {
+    pub fn new(directory: PathBuf, max_size: u64) -> io::Result<Self> {
+        fs::create_dir_all(&directory)?;
+
+        Ok(Self {
+            directory,
+            max_size,
+            in_progress: Mutex::new(HashSet::new()),
+        })
+    }
+
+    pub fn key(track_id: i32, codec: &str, bitrate: i32) -> String {
+        format!("{}-{}-{}", track_id, codec, bitrate)
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.max_size > 0
+    }
+
+    /// Opens a fully downloaded entry and marks it as recently used.
+    pub fn get(&self, key: &str) -> Option<File> {
+        if !self.is_enabled() {
+            return None;
+        }
+
+        let file = File::options().read(true).write(true).open(self.path(key));
+        match file {
+            Ok(file) => {
+                let _ = file.set_modified(SystemTime::now());
+                Some(file)
+            }
+            Err(_) => None,
+        }
+    }
+
+    /// Opens the partial entry for `key`, creating it if necessary.
+    ///
+    /// Returns `None` if caching is disabled or another stream is already
+    /// writing the same entry.
+    pub fn open_partial(
+        self: &Arc<Self>,
+        key: &str,
+        total_bytes: u64,
+    ) -> Option<PartialEntry> {
+        if !self.is_enabled() || total_bytes > self.max_size {
+            return None;
+        }
+
+        if !self.in_progress.lock().unwrap().insert(key.to_string()) {
+            return None;
+        }
+
+        match self.create_partial(key, total_bytes) {
+            Ok(file) => Some(PartialEntry {
+                cache: self.clone(),
+                key: key.to_string(),
+                len: file.metadata().map(|m| m.len()).unwrap_or(0),
+                file,
+                committed: false,
+            }),
+            Err(err) => {
+                warn!("Failed to open cache entry {}: {}", key, err);
+                self.in_progress.lock().unwrap().remove(key);
+                None
+            }
+        }
+    }
+
+    fn create_partial(&self, key: &str, total_bytes: u64) -> io::Result<File> {
+        let file = OpenOptions::new()
+            .read(true)
+            .append(true)
+            .create(true)
+            .open(self.partial_path(key))?;
+
+        if file.metadata()?.len() > total_bytes {
+            file.set_len(0)?;
+        }
+
+        Ok(file)
+    }
+
+    fn commit(&self, key: &str) -> io::Result<()> {
+        fs::rename(self.partial_path(key), self.path(key))?;
+        self.evict()
+    }
+
+    /// Removes the least recently used entries until the cache fits into
+    /// its size limit.
+    fn evict(&self) -> io::Result<()> {
+        let in_progress = self.in_progress.lock().unwrap();
+        let mut entries = Vec::new();
+        let mut total_size = 0;
+
+        for entry in fs::read_dir(&self.directory)? {
+            let entry = entry?;
+            let metadata = entry.metadata()?;
+            if !metadata.is_file() {
+                continue;
+            }
+
+            total_size += metadata.len();
+
+            let name = entry.file_name().to_string_lossy().to_string();
+            let key = name
+                .strip_suffix(&format!(".{}", PARTIAL_EXTENSION))
+                .unwrap_or(&name);
+            if in_progress.contains(key) {
+                continue;
+            }
+
+            entries.push((
+                metadata.modified().unwrap_or(SystemTime::UNIX_EPOCH),
+                metadata.len(),
+                entry.path(),
+            ));
+        }
+
+        entries.sort_by_key(|(modified, _, _)| *modified);
+
+        for (_, size, path) in entries {
+            if total_size <= self.max_size {
+                break;
+            }
+
+            if fs::remove_file(&path).is_ok() {
+                total_size -= size;
+            }
+        }
+
+        Ok(())
+    }
+
+    fn path(&self, key: &str) -> PathBuf {
+        self.directory.join(key)
+    }
+
+    fn partial_path(&self, key: &str) -> PathBuf {
+        self.directory
+            .join(format!("{}.{}", key, PARTIAL_EXTENSION))
+    }
+}
+
+/// A cache entry that is still being downloaded.
+///
+/// It stays readable after it is committed, and isn't evicted until it is
+/// dropped.
+pub struct PartialEntry {
+    cache: Arc<AudioCache>,
+    key: String,
+    file: File,
+    len: u64,
+    committed: bool,
+}
+
+impl PartialEntry {
+    /// Number of bytes already stored on disk.
+    pub fn len(&self) -> u64 {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Reads `len` bytes starting at `start` if they have been stored
+    /// already.
+    pub fn read_range(&mut self, start: u64, len: u64) -> Option<Bytes> {
+        if start + len > self.len {
+            return None;
+        }
+
+        let mut bytes = vec![0; len as usize];
+        self.file.seek(SeekFrom::Start(start)).ok()?;
+        self.file.read_exact(&mut bytes).ok()?;
+
+        Some(Bytes::from(bytes))
+    }
+
+    /// Appends bytes that were fetched starting at `offset`. Only data that
+    /// directly continues the stored prefix is written.
+    pub fn append(&mut self, offset: u64, bytes: &[u8]) -> io::Result<()> {
+        if offset != self.len {
+            return Ok(());
+        }
+
+        self.file.write_all(bytes)?;
+        self.len += bytes.len() as u64;
+
+        Ok(())
+    }
+
+    pub fn is_committed(&self) -> bool {
+        self.committed
+    }
+
+    /// Moves the finished download into the cache.
+    pub fn commit(&mut self) -> io::Result<()> {
+        if self.committed {
+            return Ok(());
+        }
+
+        self.file.sync_all()?;
+        self.cache.commit(&self.key)?;
+        self.committed = true;
+
+        Ok(())
+    }
+}
+
+impl Drop for PartialEntry {
+    fn drop(&mut self) {
+        self.cache.in_progress.lock().unwrap().remove(&self.key);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::*;
+    use crate::test_utils::TempDir;
+
+    fn open_cache(name: &str, max_size: u64) -> (Arc<AudioCache>, TempDir) {
+        let directory = TempDir::new(name);
+        let cache = AudioCache::new(directory.to_path_buf(), max_size).unwrap();
+        (Arc::new(cache), directory)
+    }
+
+    fn store(cache: &Arc<AudioCache>, key: &str, len: usize) {
+        let mut entry = cache.open_partial(key, len as u64).unwrap();
+        entry.append(0, &vec![0; len]).unwrap();
+        entry.commit().unwrap();
+    }
+
+    #[test]
+    fn allows_one_writer_per_entry() {
+        let (cache, _directory) = open_cache("one-writer", 1000);
+
+        let entry = cache.open_partial("a", 100).unwrap();
+        assert!(cache.open_partial("a", 100).is_none());
+        drop(entry);
+        assert!(cache.open_partial("a", 100).is_some());
+    }
+
+    #[test]
+    fn skips_entries_larger_than_the_cache() {
+        let (cache, _directory) = open_cache("too-large", 1000);
+        assert!(cache.open_partial("a", 1001).is_none());
+
+        let (disabled, _directory) = open_cache("disabled", 0);
+        assert!(disabled.open_partial("a", 10).is_none());
+    }
+
+    #[test]
+    fn resumes_a_partial_entry() {
+        let (cache, _directory) = open_cache("resume", 1000);
+
+        let mut entry = cache.open_partial("a", 100).unwrap();
+        entry.append(0, b"abc").unwrap();
+        entry.append(10, b"xyz").unwrap();
+        drop(entry);
+
+        let mut entry = cache.open_partial("a", 100).unwrap();
+        assert_eq!(entry.len(), 3);
+        assert_eq!(entry.read_range(1, 2).unwrap(), &b"bc"[..]);
+        assert!(entry.read_range(2, 2).is_none());
+    }
+
+    #[test]
+    fn keeps_a_committed_entry_readable() {
+        let (cache, _directory) = open_cache("committed", 1000);
+
+        let mut entry = cache.open_partial("a", 3).unwrap();
+        entry.append(0, b"abc").unwrap();
+        entry.commit().unwrap();
+
+        assert!(entry.is_committed());
+        assert!(cache.get("a").is_some());
+        assert_eq!(entry.read_range(0, 3).unwrap(), &b"abc"[..]);
+    }
+
+    #[test]
+    fn evicts_the_least_recently_used_entries() {
+        let (cache, _directory) = open_cache("evict", 250);
+
+        store(&cache, "a", 100);
+        std::thread::sleep(Duration::from_millis(20));
+        store(&cache, "b", 100);
+        std::thread::sleep(Duration::from_millis(20));
+        assert!(cache.get("a").is_some());
+        std::thread::sleep(Duration::from_millis(20));
+        store(&cache, "c", 100);
+
+        assert!(cache.get("a").is_some());
+        assert!(cache.get("b").is_none());
+        assert!(cache.get("c").is_some());
+    }
+}
@@ -0,0 +1,212 @@
+use aes::Aes128;
+use anyhow::Context;
+use ctr::{
+    cipher::{KeyIvInit, StreamCipher, StreamCipherSeek},
+    Ctr128BE,
+};
+use tokio_util::bytes::{Bytes, BytesMut};
+
+use super::source::ByteSource;
+
+/// AES-128 key of a track that is sent encrypted over the `encraw`
+/// transport.
+#[derive(Clone, Copy)]
+pub struct TrackKey([u8; 16]);
+
+impl TrackKey {
+    pub fn from_hex(hex: &str) -> anyhow::Result<Self> {
+        anyhow::ensure!(
+            hex.len() == 32 && hex.is_ascii(),
+            "Invalid track key length"
+        );
+
+        let mut key = [0; 16];
+        for (i, byte) in key.iter_mut().enumerate() {
+            *byte = u8::from_str_radix(&hex[i * 2..i * 2 + 2], 16)
+                .context("Invalid track key")?;
+        }
+
+        Ok(Self(key))
+    }
+
+    /// Decrypts `data` in place, where `offset` is the position of `data`
+    /// within the file.
+    pub fn decrypt(&self, offset: u64, data: &mut [u8]) {
+        let mut cipher =
+            Ctr128BE::<Aes128>::new(&self.0.into(), &[0; 16].into());
+        cipher.seek(offset);
+        cipher.apply_keystream(data);
+    }
+}
+
+/// Decrypts data read from another source if a key is given, and passes it
+/// through unchanged otherwise.
+pub struct DecryptingSource<S> {
+    inner: S,
+    key: Option<TrackKey>,
+}
+
+impl<S: ByteSource> DecryptingSource<S> {
+    pub fn new(inner: S, key: Option<TrackKey>) -> Self {
+        Self { inner, key }
+    }
+}
+
+impl<S: ByteSource> ByteSource for DecryptingSource<S> {
+    fn total_bytes(&self) -> Option<u64> {
+        self.inner.total_bytes()
+    }
+
+    async fn read_range(
+        &mut self,
+        offset: u64,
+        len: u64,
+    ) -> anyhow::Result<Bytes> {
+        let bytes = self.inner.read_range(offset, len).await?;
+        if let Some(key) = self.inner.take_key() {
+            self.key = Some(key);
+        }
+        let Some(key) = self.key else {
+            return Ok(bytes);
+        };
+
+        let mut bytes = BytesMut::from(&bytes[..]);
+        key.decrypt(offset, &mut bytes);
+
+        Ok(bytes.freeze())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::stream::source::MemorySource;
+
+    use super::*;
+
+    const KEY: &str = "000102030405060708090a0b0c0d0e0f";
+
+    fn encrypt(key: TrackKey, plain: &[u8]) -> Bytes {
+        let mut bytes = plain.to_vec();
+        key.decrypt(0, &mut bytes);
+        Bytes::from(bytes)
+    }
+
+    fn plain() -> Vec<u8> {
+        (0..=255).cycle().take(1000).collect()
+    }
+
+    /// Serves `first` until `switch_at`, and from there on data encrypted
+    /// with `second`, like a file whose link was replaced.
+    struct RekeyedSource {
+        first: MemorySource,
+        second: MemorySource,
+        second_key: TrackKey,
+        switch_at: u64,
+        has_switched: bool,
+    }
+
+    impl ByteSource for RekeyedSource {
+        fn total_bytes(&self) -> Option<u64> {
+            self.first.total_bytes()
+        }
+
+        async fn read_range(
+            &mut self,
+            offset: u64,
+            len: u64,
+        ) -> anyhow::Result<Bytes> {
+            if offset < self.switch_at {
+                return self.first.read_range(offset, len).await;
+            }
+            self.has_switched = true;
+            self.second.read_range(offset, len).await
+        }
+
+        fn take_key(&mut self) -> Option<TrackKey> {
+            self.has_switched.then_some(self.second_key)
+        }
+    }
+
+    #[test]
+    fn parses_hex_keys() {
+        assert!(TrackKey::from_hex(KEY).is_ok());
+        assert!(TrackKey::from_hex("0001").is_err());
+        assert!(TrackKey::from_hex(&"zz".repeat(16)).is_err());
+        assert!(TrackKey::from_hex(&"é".repeat(16)).is_err());
+    }
+
+    #[test]
+    fn decrypts_with_a_zero_nonce() {
+        // The AES-128 encryption of a zero block under a zero key, which is
+        // the first block of the keystream.
+        let key = TrackKey::from_hex(&"00".repeat(16)).unwrap();
+        let mut data = [0; 16];
+        key.decrypt(0, &mut data);
+
+        assert_eq!(
+            data,
+            [
+                0x66, 0xe9, 0x4b, 0xd4, 0xef, 0x8a, 0x2c, 0x3b, 0x88, 0x4c,
+                0xfa, 0x59, 0xca, 0x34, 0x2b, 0x2e
+            ]
+        );
+    }
+
+    #[test]
+    fn decrypts_from_any_offset() {
+        let key = TrackKey::from_hex(KEY).unwrap();
+        let plain = plain();
+        let encrypted = encrypt(key, &plain);
+
+        for offset in [0, 1, 15, 16, 17, 500, 999] {
+            let mut part = encrypted[offset..].to_vec();
+            key.decrypt(offset as u64, &mut part);
+            assert_eq!(part, plain[offset..], "offset {}", offset);
+        }
+    }
+
+    #[tokio::test]
+    async fn decrypts_ranges_of_the_inner_source() {
+        let key = TrackKey::from_hex(KEY).unwrap();
+        let plain = plain();
+        let mut source = DecryptingSource::new(
+            MemorySource::new(encrypt(key, &plain)),
+            Some(key),
+        );
+
+        assert_eq!(source.total_bytes(), Some(1000));
+        assert_eq!(source.read_range(123, 200).await.unwrap(), plain[123..323]);
+        assert_eq!(source.read_range(900, 200).await.unwrap(), plain[900..]);
+    }
+
+    #[tokio::test]
+    async fn passes_data_through_without_a_key() {
+        let plain = plain();
+        let mut source = DecryptingSource::new(
+            MemorySource::new(plain.clone().into()),
+            None,
+        );
+
+        assert_eq!(source.read_range(10, 20).await.unwrap(), plain[10..30]);
+    }
+
+    #[tokio::test]
+    async fn switches_to_a_new_key() {
+        let first_key = TrackKey::from_hex(KEY).unwrap();
+        let second_key = TrackKey::from_hex(&"ab".repeat(16)).unwrap();
+        let plain = plain();
+        let mut source = DecryptingSource::new(
+            RekeyedSource {
+                first: MemorySource::new(encrypt(first_key, &plain)),
+                second: MemorySource::new(encrypt(second_key, &plain)),
+                second_key,
+                switch_at: 500,
+                has_switched: false,
+            },
+            Some(first_key),
+        );
+
+        assert_eq!(source.read_range(0, 500).await.unwrap(), plain[..500]);
+        assert_eq!(source.read_range(500, 500).await.unwrap(), plain[500..]);
+    }
+}
@@ -1 +1,7 @@
+pub mod bandwidth;
+pub mod buffer;
+pub mod cache;
+pub mod decrypt;
+pub mod prefetch;
+pub mod source;
 pub mod streamer;
@@ -0,0 +1,239 @@
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+use tokio_util::{
+    bytes::{Bytes, BytesMut},
+    sync::CancellationToken,
+};
+use tracing::{info, warn};
+use yandex_music::YandexMusicClient;
+
+use crate::{
+    audio::playback::utils::{fetch_track_url, TrackUrl},
+    config::CONFIG,
+};
+
+use super::{cache::AudioCache, decrypt::TrackKey, streamer::AudioStreamer};
+
+const PREFETCH_CHUNK: u64 = 64 * 1024;
+/// Bitrate assumed for lossless files, for which the API reports none.
+const LOSSLESS_BITRATE: u64 = 1411;
+
+/// The resolved URL and the first few seconds of an upcoming track.
+pub struct PrefetchedTrack {
+    pub url: String,
+    pub codec: String,
+    pub bitrate: i32,
+    pub key: Option<TrackKey>,
+    pub total_bytes: u64,
+    /// Already decrypted if the file is encrypted.
+    pub head: Bytes,
+}
+
+/// Resolves and pre-buffers upcoming queue items in the background.
+pub struct Prefetcher {
+    client: Arc<YandexMusicClient>,
+    http: reqwest::Client,
+    cache: Arc<AudioCache>,
+    tracks: Arc<Mutex<HashMap<i32, PrefetchedTrack>>>,
+    cancel: Option<CancellationToken>,
+}
+
+impl Prefetcher {
+    pub fn new(
+        client: Arc<YandexMusicClient>,
+        http: reqwest::Client,
+        cache: Arc<AudioCache>,
+    ) -> Self {
+        Self {
+            client,
+            http,
+            cache,
+            tracks: Arc::new(Mutex::new(HashMap::new())),
+            cancel: None,
+        }
+    }
+
+    /// Replaces the set of prefetched tracks with `track_ids`, keeping data
+    /// that was already fetched for tracks that are still upcoming.
+    pub fn prefetch(&mut self, track_ids: Vec<i32>) {
+        self.stop();
+        self.tracks
+            .lock()
+            .unwrap()
+            .retain(|track_id, _| track_ids.contains(track_id));
+
+        let track_ids = track_ids
+            .into_iter()
+            .take(CONFIG.prefetch_tracks)
+            .collect::<Vec<_>>();
+        if track_ids.is_empty() || CONFIG.prefetch_memory == 0 {
+            return;
+        }
+
+        let cancel = CancellationToken::new();
+        self.cancel = Some(cancel.clone());
+
+        let client = self.client.clone();
+        let http = self.http.clone();
+        let cache = self.cache.clone();
+        let tracks = self.tracks.clone();
+        tokio::spawn(async move {
+            tokio::select! {
+                _ = Self::run(client, http, cache, tracks, track_ids) => {}
+                _ = cancel.cancelled() => {}
+            }
+        });
+    }
+
+    /// Takes the prefetched data of a track if there is any.
+    pub fn take(&self, track_id: i32) -> Option<PrefetchedTrack> {
+        self.tracks.lock().unwrap().remove(&track_id)
+    }
+
+    /// Discards everything, e.g. after the queue has changed.
+    pub fn clear(&mut self) {
+        self.stop();
+        self.tracks.lock().unwrap().clear();
+    }
+
+    fn stop(&mut self) {
+        if let Some(cancel) = self.cancel.take() {
+            cancel.cancel();
+        }
+    }
+
+    async fn run(
+        client: Arc<YandexMusicClient>,
+        http: reqwest::Client,
+        cache: Arc<AudioCache>,
+        tracks: Arc<Mutex<HashMap<i32, PrefetchedTrack>>>,
+        track_ids: Vec<i32>,
+    ) {
+        for track_id in track_ids {
+            if tracks.lock().unwrap().contains_key(&track_id) {
+                continue;
+            }
+
+            let TrackUrl {
+                url,
+                codec,
+                bitrate,
+                key,
+            } = match fetch_track_url(&client, &http, track_id, false).await {
+                Ok(info) => info,
+                Err(err) => {
+                    warn!("Failed to prefetch track {}: {:#}", track_id, err);
+                    continue;
+                }
+            };
+            if cache
+                .get(&AudioCache::key(track_id, &codec, bitrate))
+                .is_some()
+            {
+                continue;
+            }
+
+            let used = tracks
+                .lock()
+                .unwrap()
+                .values()
+                .map(|t| t.head.len() as u64)
+                .sum::<u64>();
+            let kbps = if bitrate > 0 {
+                bitrate as u64
+            } else {
+                LOSSLESS_BITRATE
+            };
+            let budget = CONFIG
+                .prefetch_memory
+                .saturating_sub(used)
+                .min(CONFIG.prefetch_duration.as_secs() * kbps * 1000 / 8);
+            if budget == 0 {
+                break;
+            }
+
+            match Self::fetch_head(&http, &url, key, budget).await {
+                Ok((total_bytes, head)) => {
+                    info!(
+                        "prefetched {} bytes of track {}",
+                        head.len(),
+                        track_id
+                    );
+                    tracks.lock().unwrap().insert(
+                        track_id,
+                        PrefetchedTrack {
+                            url,
+                            codec,
+                            bitrate,
+                            key,
+                            total_bytes,
+                            head,
+                        },
+                    );
+                }
+                Err(err) => {
+                    warn!("Failed to prefetch track {}: {:#}", track_id, err)
+                }
+            }
+        }
+    }
+
+    /// Downloads up to `budget` bytes from the start of `url` while staying
+    /// within the configured bandwidth limit, decrypting them with `key`.
+    async fn fetch_head(
+        http: &reqwest::Client,
+        url: &str,
+        key: Option<TrackKey>,
+        budget: u64,
+    ) -> anyhow::Result<(u64, Bytes)> {
+        let Some(total_bytes) = AudioStreamer::with_retries(|| {
+            AudioStreamer::fetch_total_bytes(http, url)
+        })
+        .await?
+        else {
+            anyhow::bail!("Length of the stream is unknown");
+        };
+        let length = budget.min(total_bytes);
+
+        let started = Instant::now();
+        let mut head = BytesMut::with_capacity(length as usize);
+        while (head.len() as u64) < length {
+            let start = head.len() as u64;
+            let end = (start + PREFETCH_CHUNK).min(length) - 1;
+            let bytes = AudioStreamer::with_retries(|| {
+                AudioStreamer::fetch_range_bytes(http, url, start, end)
+            })
+            .await?;
+            if bytes.is_empty() {
+                anyhow::bail!("Stream ended after {} bytes", start);
+            }
+            head.extend_from_slice(&bytes);
+            head.truncate(length as usize);
+
+            if CONFIG.prefetch_bandwidth > 0 {
+                let expected = Duration::from_secs_f64(
+                    head.len() as f64 / CONFIG.prefetch_bandwidth as f64,
+                );
+                if let Some(delay) = expected.checked_sub(started.elapsed()) {
+                    tokio::time::sleep(delay).await;
+                }
+            }
+        }
+
+        if let Some(key) = key {
+            key.decrypt(0, &mut head);
+        }
+
+        Ok((total_bytes, head.freeze()))
+    }
+}
+
+impl Drop for Prefetcher {
+    fn drop(&mut self) {
+        self.stop();
+    }
+}
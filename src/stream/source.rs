@@ -0,0 +1,490 @@
+use std::{
+    fs::File,
+    future::Future,
+    io::{self, Read, Seek, SeekFrom},
+    sync::Arc,
+};
+
+use tokio_util::bytes::Bytes;
+use tracing::{info, warn};
+use yandex_music::YandexMusicClient;
+
+use crate::audio::playback::utils::{refresh_track_url, TrackUrl};
+
+use super::{
+    bandwidth::BANDWIDTH,
+    cache::{AudioCache, PartialEntry},
+    decrypt::TrackKey,
+    streamer::{is_link_expired, AudioStreamer},
+};
+
+/// Random access to the bytes of a track, wherever they are stored.
+pub trait ByteSource: Send + 'static {
+    /// Length of the data if it is known in advance.
+    fn total_bytes(&self) -> Option<u64>;
+
+    /// Reads up to `len` bytes starting at `offset`. An empty result means
+    /// that the end of the data has been reached.
+    fn read_range(
+        &mut self,
+        offset: u64,
+        len: u64,
+    ) -> impl Future<Output = anyhow::Result<Bytes>> + Send;
+
+    /// The key that data read from now on is encrypted with, if it changed
+    /// since it was last asked for, e.g. because an expired link was
+    /// replaced.
+    fn take_key(&mut self) -> Option<TrackKey> {
+        None
+    }
+}
+
+/// The track a direct link points to, used to resolve a new link once the
+/// current one expires.
+#[derive(Clone)]
+pub struct TrackLink {
+    pub client: Arc<YandexMusicClient>,
+    pub track_id: i32,
+    pub codec: String,
+    pub bitrate: i32,
+    pub encrypted: bool,
+}
+
+impl TrackLink {
+    async fn resolve(
+        &self,
+        http: &reqwest::Client,
+    ) -> anyhow::Result<TrackUrl> {
+        refresh_track_url(
+            &self.client,
+            http,
+            self.track_id,
+            &self.codec,
+            self.bitrate,
+            self.encrypted,
+        )
+        .await
+    }
+}
+
+/// Reads a remote file with HTTP range requests.
+///
+/// If the server doesn't report the length of the file, it is downloaded
+/// sequentially with a single request instead.
+pub struct HttpSource {
+    client: reqwest::Client,
+    url: String,
+    total_bytes: Option<u64>,
+    link: Option<TrackLink>,
+    /// Key of the file behind a replaced link, until it is taken.
+    key: Option<TrackKey>,
+    response: Option<reqwest::Response>,
+    pending: Bytes,
+    position: u64,
+}
+
+impl HttpSource {
+    /// Determines the length of the file at `url` before anything is read.
+    ///
+    /// Expired links are resolved again through `link` and reading continues
+    /// from the same offset.
+    pub async fn new(
+        client: reqwest::Client,
+        url: String,
+        link: Option<TrackLink>,
+    ) -> anyhow::Result<Self> {
+        let total_bytes = AudioStreamer::with_retries(|| {
+            AudioStreamer::fetch_total_bytes(&client, &url)
+        })
+        .await?;
+
+        Ok(Self::with_length(client, url, total_bytes, link))
+    }
+
+    pub fn with_length(
+        client: reqwest::Client,
+        url: String,
+        total_bytes: Option<u64>,
+        link: Option<TrackLink>,
+    ) -> Self {
+        Self {
+            client,
+            url,
+            total_bytes,
+            link,
+            key: None,
+            response: None,
+            pending: Bytes::new(),
+            position: 0,
+        }
+    }
+
+    async fn read_ranged(
+        &mut self,
+        start: u64,
+        end: u64,
+    ) -> anyhow::Result<Bytes> {
+        let client = &self.client;
+        let result = AudioStreamer::with_retries(|| {
+            AudioStreamer::fetch_range_bytes(client, &self.url, start, end)
+        })
+        .await;
+
+        match (result, self.link.as_ref()) {
+            (Err(err), Some(link)) if is_link_expired(&err) => {
+                info!("Link of track {} has expired", link.track_id);
+                let url = AudioStreamer::with_retries(|| link.resolve(client))
+                    .await?;
+                self.url = url.url;
+                self.key = url.key;
+                AudioStreamer::with_retries(|| {
+                    AudioStreamer::fetch_range_bytes(
+                        client, &self.url, start, end,
+                    )
+                })
+                .await
+            }
+            (result, _) => result,
+        }
+    }
+
+    async fn read_sequential(
+        &mut self,
+        offset: u64,
+        len: u64,
+    ) -> anyhow::Result<Bytes> {
+        if offset != self.position {
+            anyhow::bail!("Stream of unknown length can only be read in order");
+        }
+
+        if self.pending.is_empty() {
+            if self.response.is_none() {
+                let (client, url) = (&self.client, &self.url);
+                let response = AudioStreamer::with_retries(|| async {
+                    Ok(client.get(url).send().await?.error_for_status()?)
+                })
+                .await?;
+                self.response = Some(response);
+            }
+
+            let Some(response) = self.response.as_mut() else {
+                return Ok(Bytes::new());
+            };
+            let Some(chunk) = response.chunk().await? else {
+                return Ok(Bytes::new());
+            };
+            BANDWIDTH.consume(chunk.len() as u64).await;
+            self.pending = chunk;
+        }
+
+        let bytes = self
+            .pending
+            .split_to((len as usize).min(self.pending.len()));
+        self.position += bytes.len() as u64;
+
+        Ok(bytes)
+    }
+}
+
+impl ByteSource for HttpSource {
+    fn total_bytes(&self) -> Option<u64> {
+        self.total_bytes
+    }
+
+    async fn read_range(
+        &mut self,
+        offset: u64,
+        len: u64,
+    ) -> anyhow::Result<Bytes> {
+        match self.total_bytes {
+            Some(total_bytes) => {
+                let end = (offset + len).min(total_bytes);
+                if offset >= end {
+                    return Ok(Bytes::new());
+                }
+                self.read_ranged(offset, end - 1).await
+            }
+            None => self.read_sequential(offset, len).await,
+        }
+    }
+
+    fn take_key(&mut self) -> Option<TrackKey> {
+        self.key.take()
+    }
+}
+
+/// Reads a local file, e.g. a track from the offline store or the cache.
+pub struct FileSource {
+    file: File,
+    total_bytes: u64,
+}
+
+impl FileSource {
+    pub fn new(file: File) -> io::Result<Self> {
+        let total_bytes = file.metadata()?.len();
+
+        Ok(Self { file, total_bytes })
+    }
+}
+
+impl ByteSource for FileSource {
+    fn total_bytes(&self) -> Option<u64> {
+        Some(self.total_bytes)
+    }
+
+    async fn read_range(
+        &mut self,
+        offset: u64,
+        len: u64,
+    ) -> anyhow::Result<Bytes> {
+        let len = len.min(self.total_bytes.saturating_sub(offset));
+        let mut bytes = vec![0; len as usize];
+        self.file.seek(SeekFrom::Start(offset))?;
+        self.file.read_exact(&mut bytes)?;
+
+        Ok(Bytes::from(bytes))
+    }
+}
+
+/// Serves data that is already in memory.
+pub struct MemorySource {
+    bytes: Bytes,
+}
+
+impl MemorySource {
+    pub fn new(bytes: Bytes) -> Self {
+        Self { bytes }
+    }
+}
+
+impl ByteSource for MemorySource {
+    fn total_bytes(&self) -> Option<u64> {
+        Some(self.bytes.len() as u64)
+    }
+
+    async fn read_range(
+        &mut self,
+        offset: u64,
+        len: u64,
+    ) -> anyhow::Result<Bytes> {
+        let start = (offset as usize).min(self.bytes.len());
+        let end = (start + len as usize).min(self.bytes.len());
+
+        Ok(self.bytes.slice(start..end))
+    }
+}
+
+/// Writes everything read from another source into the disk cache, and
+/// serves ranges that were stored in an earlier session from there.
+pub struct CachedSource<S> {
+    inner: S,
+    partial: Option<PartialEntry>,
+}
+
+impl<S: ByteSource> CachedSource<S> {
+    /// Opens the cache entry `key`, which already contains `head` if given.
+    /// Sources of unknown length aren't cached.
+    pub fn new(
+        inner: S,
+        cache: Option<(Arc<AudioCache>, String)>,
+        head: &[u8],
+    ) -> Self {
+        let mut partial = match (cache, inner.total_bytes()) {
+            (Some((cache, key)), Some(total_bytes)) => {
+                cache.open_partial(&key, total_bytes)
+            }
+            _ => None,
+        };
+        if let Some(entry) = partial.as_mut() {
+            if let Err(err) = entry.append(0, head) {
+                warn!("Failed to write to audio cache: {}", err);
+                partial = None;
+            }
+        }
+
+        // A previous session may have stored the whole file already.
+        let mut source = Self { inner, partial };
+        source.commit_if_complete();
+        source
+    }
+
+    /// Moves the entry into the cache once all of the data is stored.
+    fn commit_if_complete(&mut self) {
+        let total_bytes = self.inner.total_bytes();
+        let Some(entry) = self.partial.as_mut() else {
+            return;
+        };
+        if entry.is_committed() || Some(entry.len()) != total_bytes {
+            return;
+        }
+
+        if let Err(err) = entry.commit() {
+            warn!("Failed to commit audio cache entry: {}", err);
+            self.partial = None;
+        }
+    }
+}
+
+impl<S: ByteSource> ByteSource for CachedSource<S> {
+    fn total_bytes(&self) -> Option<u64> {
+        self.inner.total_bytes()
+    }
+
+    async fn read_range(
+        &mut self,
+        offset: u64,
+        len: u64,
+    ) -> anyhow::Result<Bytes> {
+        let total_bytes = self.inner.total_bytes();
+        let len = total_bytes
+            .map_or(len, |total| len.min(total.saturating_sub(offset)));
+        let stored = self
+            .partial
+            .as_mut()
+            .and_then(|entry| entry.read_range(offset, len));
+        if let Some(bytes) = stored {
+            return Ok(bytes);
+        }
+
+        let bytes = self.inner.read_range(offset, len).await?;
+
+        if let Some(entry) = self.partial.as_mut() {
+            if let Err(err) = entry.append(offset, &bytes) {
+                warn!("Failed to write to audio cache: {}", err);
+                self.partial = None;
+            }
+        }
+
+        self.commit_if_complete();
+
+        Ok(bytes)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use super::*;
+    use crate::test_utils::TempDir;
+
+    const KEY: &str = "1-flac-0";
+
+    /// A source of the given length that fails every read, standing in for
+    /// a connection that is down.
+    struct UnreachableSource {
+        total_bytes: u64,
+    }
+
+    impl ByteSource for UnreachableSource {
+        fn total_bytes(&self) -> Option<u64> {
+            Some(self.total_bytes)
+        }
+
+        async fn read_range(
+            &mut self,
+            _offset: u64,
+            _len: u64,
+        ) -> anyhow::Result<Bytes> {
+            anyhow::bail!("Source is unreachable")
+        }
+    }
+
+    fn data() -> Vec<u8> {
+        (0..=255).cycle().take(1000).collect()
+    }
+
+    #[tokio::test]
+    async fn reads_ranges_of_a_file() {
+        let directory = TempDir::new("file-source");
+        let path = directory.join("track");
+        let data = data();
+        fs::write(&path, &data).unwrap();
+
+        let mut source = FileSource::new(File::open(&path).unwrap()).unwrap();
+        assert_eq!(source.total_bytes(), Some(1000));
+        assert_eq!(source.read_range(10, 20).await.unwrap(), data[10..30]);
+        assert_eq!(source.read_range(950, 100).await.unwrap(), data[950..]);
+        assert!(source.read_range(1000, 100).await.unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn reads_ranges_from_memory() {
+        let data = data();
+        let mut source = MemorySource::new(Bytes::from(data.clone()));
+
+        assert_eq!(source.total_bytes(), Some(1000));
+        assert_eq!(source.read_range(10, 20).await.unwrap(), data[10..30]);
+        assert_eq!(source.read_range(950, 100).await.unwrap(), data[950..]);
+        assert!(source.read_range(2000, 100).await.unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn caches_a_source_that_was_read_completely() {
+        let directory = TempDir::new("write-through");
+        let cache = Arc::new(
+            AudioCache::new(directory.to_path_buf(), 1 << 20).unwrap(),
+        );
+        let data = data();
+        let mut source = CachedSource::new(
+            MemorySource::new(Bytes::from(data.clone())),
+            Some((cache.clone(), KEY.to_string())),
+            &data[..100],
+        );
+
+        for offset in (100..1000).step_by(300) {
+            assert!(cache.get(KEY).is_none());
+            source.read_range(offset, 300).await.unwrap();
+        }
+
+        let mut cached = Vec::new();
+        cache.get(KEY).unwrap().read_to_end(&mut cached).unwrap();
+        assert_eq!(cached, data);
+    }
+
+    #[tokio::test]
+    async fn serves_stored_ranges_from_the_cache() {
+        let directory = TempDir::new("stored-ranges");
+        let cache = Arc::new(
+            AudioCache::new(directory.to_path_buf(), 1 << 20).unwrap(),
+        );
+        let data = data();
+        fs::write(directory.join(format!("{}.part", KEY)), &data[..500])
+            .unwrap();
+
+        let mut source = CachedSource::new(
+            UnreachableSource {
+                total_bytes: data.len() as u64,
+            },
+            Some((cache.clone(), KEY.to_string())),
+            &[],
+        );
+
+        assert_eq!(source.read_range(0, 300).await.unwrap(), data[..300]);
+        assert_eq!(source.read_range(300, 200).await.unwrap(), data[300..500]);
+        assert!(source.read_range(400, 200).await.is_err());
+        assert!(cache.get(KEY).is_none());
+    }
+
+    #[tokio::test]
+    async fn commits_a_complete_partial_entry_when_it_is_opened() {
+        let directory = TempDir::new("complete-partial");
+        let cache = Arc::new(
+            AudioCache::new(directory.to_path_buf(), 1 << 20).unwrap(),
+        );
+        let data = data();
+        fs::write(directory.join(format!("{}.part", KEY)), &data).unwrap();
+
+        let mut source = CachedSource::new(
+            UnreachableSource {
+                total_bytes: data.len() as u64,
+            },
+            Some((cache.clone(), KEY.to_string())),
+            &[],
+        );
+
+        assert!(cache.get(KEY).is_some());
+        assert!(!directory.join(format!("{}.part", KEY)).exists());
+        assert_eq!(source.read_range(100, 200).await.unwrap(), data[100..300]);
+    }
+}
@@ -1,147 +1,323 @@
 use std::{
-    io::{Read, Seek, SeekFrom},
-    sync::{Arc, RwLock},
-    thread,
+    future::Future,
+    io::{self, Read, Seek, SeekFrom},
+    sync::Arc,
+    time::Instant,
 };
 
-use flume::{Receiver, Sender};
-use tokio_util::bytes::Bytes;
+use reqwest::{
+    header::{CONTENT_LENGTH, CONTENT_RANGE, RANGE},
+    StatusCode,
+};
+use tokio_util::{bytes::Bytes, sync::CancellationToken};
+use tracing::warn;
+
+use crate::{config::CONFIG, http::RetryPolicy, metrics::METRICS};
+
+use super::{
+    bandwidth::read_body,
+    buffer::StreamBuffer,
+    cache::AudioCache,
+    decrypt::{DecryptingSource, TrackKey},
+    prefetch::PrefetchedTrack,
+    source::{ByteSource, CachedSource, HttpSource, TrackLink},
+};
 
 pub struct AudioStreamer {
-    url: String,
-    client: Arc<reqwest::blocking::Client>,
-    ring_buffer: Receiver<u8>,
-    position: Arc<RwLock<u64>>,
-    eof: bool,
-    pub total_bytes: u64,
+    buffer: Arc<StreamBuffer>,
+    cancel: CancellationToken,
+    position: u64,
+    pub total_bytes: Option<u64>,
 }
 
 impl AudioStreamer {
-    pub fn new(
+    /// Starts streaming `url` in a background task, caching it on disk if
+    /// `cache` is given. Encrypted files are decrypted with `key`.
+    ///
+    /// If the length of the stream can't be determined, it is downloaded
+    /// sequentially instead and can only be seeked within the buffered data.
+    /// Expired links are resolved again through `link`.
+    pub async fn new(
+        client: reqwest::Client,
         url: String,
-        // prefetch_bytes: u64,
         fetch_amount: u64,
+        cache: Option<(Arc<AudioCache>, String)>,
+        key: Option<TrackKey>,
+        link: Option<TrackLink>,
+        cancel: CancellationToken,
     ) -> anyhow::Result<Self> {
-        let client = reqwest::blocking::Client::new();
-        let total_bytes = Self::fetch_total_bytes(&client, &url)?;
-        let (tx, rx) = flume::bounded((fetch_amount * 2) as usize);
-
-        let streamer = Self {
-            url,
-            client: Arc::new(client),
-            ring_buffer: rx,
-            position: Arc::new(RwLock::new(0)),
-            eof: false,
-            total_bytes,
+        let source = tokio::select! {
+            result = HttpSource::new(client, url, link) => result?,
+            _ = cancel.cancelled() => anyhow::bail!("Stream was cancelled"),
         };
+        let source = DecryptingSource::new(source, key);
 
-        Self::fetch(
-            streamer.url.clone(),
-            streamer.client.clone(),
-            tx,
-            total_bytes,
+        Ok(Self::start(
+            CachedSource::new(source, cache, &[]),
+            Bytes::new(),
             fetch_amount,
-            streamer.position.clone(),
+            cancel,
+        ))
+    }
+
+    /// Continues streaming a track whose beginning was already downloaded by
+    /// the [`Prefetcher`](super::prefetch::Prefetcher).
+    pub fn with_prefetched(
+        client: reqwest::Client,
+        prefetched: PrefetchedTrack,
+        fetch_amount: u64,
+        cache: Option<(Arc<AudioCache>, String)>,
+        link: Option<TrackLink>,
+        cancel: CancellationToken,
+    ) -> Self {
+        let source = DecryptingSource::new(
+            HttpSource::with_length(
+                client,
+                prefetched.url,
+                Some(prefetched.total_bytes),
+                link,
+            ),
+            prefetched.key,
         );
 
-        Ok(streamer)
+        Self::start(
+            CachedSource::new(source, cache, &prefetched.head),
+            prefetched.head,
+            fetch_amount,
+            cancel,
+        )
     }
 
-    fn fetch(
-        url: String,
-        client: Arc<reqwest::blocking::Client>,
-        ring_buffer: Sender<u8>,
-        total_bytes: u64,
+    /// Starts streaming from any [`ByteSource`] in a background task.
+    ///
+    /// Reading stops as soon as `cancel` is cancelled or the streamer is
+    /// dropped.
+    pub fn from_source(
+        source: impl ByteSource,
         fetch_amount: u64,
-        position: Arc<RwLock<u64>>,
-    ) {
-        thread::spawn(move || {
-            let mut current_position = *position.read().unwrap();
-
-            while current_position < total_bytes {
-                let end =
-                    (current_position + fetch_amount).min(total_bytes - 1);
-
-                if let Ok(bytes) = Self::fetch_range_bytes(
-                    &client.clone(),
-                    &url,
-                    current_position,
-                    end,
-                ) {
-                    for b in bytes {
-                        if ring_buffer.send(b).is_err() {
-                            break;
-                        }
-                    }
+        cancel: CancellationToken,
+    ) -> Self {
+        Self::start(source, Bytes::new(), fetch_amount, cancel)
+    }
 
-                    current_position = end + 1;
+    fn start(
+        source: impl ByteSource,
+        head: Bytes,
+        fetch_amount: u64,
+        cancel: CancellationToken,
+    ) -> Self {
+        let streamer = Self {
+            buffer: Arc::new(StreamBuffer::new(
+                fetch_amount * 2,
+                fetch_amount * 2,
+            )),
+            cancel: cancel.child_token(),
+            position: 0,
+            total_bytes: source.total_bytes(),
+        };
+        if !head.is_empty() {
+            streamer.buffer.push(0, head);
+        }
+
+        let buffer = streamer.buffer.clone();
+        let cancel = streamer.cancel.clone();
+        tokio::spawn(async move {
+            tokio::select! {
+                _ = Self::fetch(source, buffer.clone(), fetch_amount) => {}
+                _ = cancel.cancelled() => {
+                    buffer.fail(io::Error::other("Stream was cancelled"));
                 }
             }
         });
+
+        streamer
+    }
+
+    async fn fetch(
+        mut source: impl ByteSource,
+        buffer: Arc<StreamBuffer>,
+        fetch_amount: u64,
+    ) {
+        let limit = source.total_bytes().unwrap_or(u64::MAX);
+        while let Some(start) = buffer.next_write_position(limit).await {
+            match source.read_range(start, fetch_amount).await {
+                Ok(bytes) if bytes.is_empty() => {
+                    buffer.finish();
+                    return;
+                }
+                Ok(bytes) => {
+                    buffer.push(start, bytes);
+                }
+                Err(err) => {
+                    warn!("Giving up on audio stream: {:#}", err);
+                    buffer.fail(io::Error::other(format!("{:#}", err)));
+                    return;
+                }
+            }
+        }
     }
 
-    fn fetch_range_bytes(
-        client: &reqwest::blocking::Client,
+    pub(super) async fn fetch_range_bytes(
+        client: &reqwest::Client,
         url: &str,
         start: u64,
         end: u64,
     ) -> anyhow::Result<Bytes> {
-        Ok(client
+        let started = Instant::now();
+        let response = client
             .get(url)
-            .header("Range", format!("bytes={}-{}", start, end))
-            .send()?
-            .bytes()?)
+            .header(RANGE, format!("bytes={}-{}", start, end))
+            .send()
+            .await?;
+        METRICS.record_latency(started.elapsed());
+
+        // A server that ignores the range would send the whole file instead.
+        let response = response.error_for_status()?;
+        if response.status() != StatusCode::PARTIAL_CONTENT {
+            anyhow::bail!(
+                "Server answered a range request with {}",
+                response.status()
+            );
+        }
+
+        Ok(read_body(response).await?)
+    }
+
+    /// Runs `request` with the retry settings of streams, counting each
+    /// retry in the metrics. Expired links are not retried.
+    pub(super) async fn with_retries<T, F>(
+        mut request: impl FnMut() -> F,
+    ) -> anyhow::Result<T>
+    where
+        F: Future<Output = anyhow::Result<T>>,
+    {
+        let policy = RetryPolicy {
+            max_retries: CONFIG.max_retries,
+            backoff: CONFIG.retry_backoff,
+        };
+        let mut is_retry = false;
+        policy
+            .run(
+                || {
+                    if std::mem::replace(&mut is_retry, true) {
+                        METRICS.record_retry();
+                    }
+                    request()
+                },
+                is_link_expired,
+            )
+            .await
     }
 
-    fn fetch_total_bytes(
-        client: &reqwest::blocking::Client,
+    /// Determines the length of the stream from the `Content-Length` of a
+    /// HEAD request, falling back to the `Content-Range` of a single byte
+    /// request. Returns `None` if the server reports neither or doesn't
+    /// support range requests.
+    pub(super) async fn fetch_total_bytes(
+        client: &reqwest::Client,
         url: &str,
-    ) -> anyhow::Result<u64> {
-        Ok(client
+    ) -> anyhow::Result<Option<u64>> {
+        match client
             .head(url)
-            .send()?
+            .send()
+            .await
+            .and_then(|r| r.error_for_status())
+        {
+            Ok(response) => {
+                let total_bytes = response
+                    .headers()
+                    .get(CONTENT_LENGTH)
+                    .and_then(|value| value.to_str().ok())
+                    .and_then(|value| value.parse().ok());
+                if total_bytes.is_some() {
+                    return Ok(total_bytes);
+                }
+            }
+            Err(err) => warn!("HEAD request failed: {}", err),
+        }
+
+        let response = client
+            .get(url)
+            .header(RANGE, "bytes=0-0")
+            .send()
+            .await?
+            .error_for_status()?;
+        if response.status() != StatusCode::PARTIAL_CONTENT {
+            return Ok(None);
+        }
+
+        Ok(response
             .headers()
-            .get("Content-Length")
-            .unwrap()
-            .to_str()?
-            .parse()?)
+            .get(CONTENT_RANGE)
+            .and_then(|value| value.to_str().ok())
+            .and_then(|value| value.rsplit_once('/'))
+            .and_then(|(_, total)| total.parse().ok()))
     }
 
     pub fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
-        if self.eof {
+        if self.total_bytes.is_some_and(|total| self.position >= total) {
             return Ok(0);
         }
 
-        let mut read_bytes = 0;
-
-        while read_bytes < buf.len() {
-            if let Ok(b) = self.ring_buffer.recv() {
-                buf[read_bytes] = b;
-                read_bytes += 1;
-            } else {
-                self.eof = true;
-                break;
-            }
-        }
+        let read_bytes = self.buffer.read(buf)?;
+        self.position += read_bytes as u64;
 
         Ok(read_bytes)
     }
 
     pub fn seek(&mut self, pos: SeekFrom) -> std::io::Result<u64> {
-        match pos {
-            SeekFrom::Start(pos) => {
-                *self.position.write().unwrap() = pos;
-                Ok(pos)
-            }
-            SeekFrom::Current(pos) => {
-                *self.position.write().unwrap() += pos as u64;
-                Ok(*self.position.read().unwrap())
-            }
-            SeekFrom::End(pos) => {
-                *self.position.write().unwrap() = self.total_bytes - pos as u64;
-                Ok(*self.position.read().unwrap())
+        let position = match pos {
+            SeekFrom::Start(pos) => Some(pos),
+            SeekFrom::Current(pos) => self.position.checked_add_signed(pos),
+            SeekFrom::End(pos) => match self.total_bytes {
+                Some(total) => total.checked_add_signed(pos),
+                None => return Err(Self::unsupported_seek()),
+            },
+        }
+        .ok_or_else(|| {
+            io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "invalid seek to a negative position",
+            )
+        })?;
+
+        let position = self
+            .total_bytes
+            .map_or(position, |total| position.min(total));
+        match self.total_bytes {
+            Some(_) => self.buffer.seek(position),
+            None if self.buffer.is_buffered(position) => {
+                self.buffer.seek(position)
             }
+            None => return Err(Self::unsupported_seek()),
         }
+        self.position = position;
+
+        Ok(position)
+    }
+
+    fn unsupported_seek() -> io::Error {
+        io::Error::new(
+            io::ErrorKind::Unsupported,
+            "stream of unknown length can't be seeked outside of the buffer",
+        )
+    }
+}
+
+/// Whether the server rejected a request because the direct link is no
+/// longer valid.
+pub(super) fn is_link_expired(err: &anyhow::Error) -> bool {
+    err.downcast_ref::<reqwest::Error>()
+        .and_then(|err| err.status())
+        .is_some_and(|status| {
+            status == StatusCode::FORBIDDEN || status == StatusCode::GONE
+        })
+}
+
+impl Drop for AudioStreamer {
+    fn drop(&mut self) {
+        self.cancel.cancel();
+        self.buffer.close();
     }
 }
 
@@ -156,3 +332,26 @@ impl Seek for AudioStreamer {
         self.seek(pos)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use tokio_util::bytes::Bytes;
+
+    use crate::stream::source::MemorySource;
+
+    use super::*;
+
+    #[tokio::test]
+    async fn clamps_seeks_past_the_end() {
+        let mut streamer = AudioStreamer::from_source(
+            MemorySource::new(Bytes::from(vec![0; 1000])),
+            256,
+            CancellationToken::new(),
+        );
+
+        assert_eq!(streamer.seek(SeekFrom::Start(5000)).unwrap(), 1000);
+        assert_eq!(streamer.seek(SeekFrom::Current(10)).unwrap(), 1000);
+        assert_eq!(streamer.seek(SeekFrom::End(-100)).unwrap(), 900);
+        assert!(streamer.seek(SeekFrom::Current(-1000)).is_err());
+    }
+}
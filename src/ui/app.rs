@@ -1,10 +1,16 @@
-use std::sync::atomic::Ordering;
+use std::{
+    sync::{atomic::Ordering, Arc},
+    time::{Duration, Instant},
+};
 
 use flume::{Receiver, Sender};
+use tokio::sync::watch;
+use tracing::warn;
+use yandex_music::model::track_model::track::Track;
 
 use ratatui::{
     buffer::Buffer,
-    crossterm::event::{self, KeyCode, KeyEvent, KeyEventKind},
+    crossterm::event::{self, KeyCode, KeyEvent, KeyEventKind, KeyModifiers},
     layout::{Alignment, Constraint, Direction, Layout, Rect},
     style::{Color, Style},
     symbols::{self, border},
@@ -12,30 +18,100 @@ use ratatui::{
     Frame,
 };
 
-use crate::{audio::backend::AudioPlayer, event::events::Event, keymap};
+use crate::{
+    api::{playlist::PlaylistEdit, plays::PlayContext, rotor::Station},
+    audio::{backend::AudioPlayer, progress::ProgressSnapshot},
+    config::Args,
+    event::events::Event,
+    keymap,
+    lyrics::{Lyrics, LyricsCache},
+    metrics::METRICS,
+    offline::{store::CollectionSource, sync::PendingAction},
+    profile::{Profile, Profiles, DEFAULT_PROFILE},
+    stream::bandwidth::BANDWIDTH,
+};
 
 use super::{
-    components::player::PlayerWidget,
+    components::{
+        details::DetailPageWidget, diagnostics::DiagnosticsWidget,
+        library::LibraryWidget, lyrics::LyricsWidget, player::PlayerWidget,
+        prompt::PromptWidget, search::SearchWidget,
+    },
+    details::{DetailEntry, DetailPage},
+    library::{Library, LibraryItem, LibrarySection, PlaylistAction},
+    prompt::{Prompt, PromptKind},
+    search::{Search, SearchResult},
     tui::{self, TerminalEvent},
+    utils::format_bytes,
 };
 
+/// How often the diagnostics overlay is refreshed while nothing else
+/// changes.
+const DIAGNOSTICS_INTERVAL: Duration = Duration::from_secs(1);
+
+/// How often synced lyrics are redrawn, so that the highlighted line
+/// follows the playback closely.
+const LYRICS_INTERVAL: Duration = Duration::from_millis(50);
+
+/// How much the lyrics timing is shifted with each key press.
+const LYRICS_OFFSET_STEP_MS: i64 = 100;
+
 pub struct App {
     pub event_rx: Receiver<Event>,
     pub event_tx: Sender<Event>,
     pub player: AudioPlayer,
+    pub profiles: Profiles,
+    /// The profile whose account is being used.
+    pub profile: Profile,
+    pub progress_rx: watch::Receiver<ProgressSnapshot>,
+    pub library: Library,
+    pub search: Option<Search>,
+    pub prompt: Option<Prompt>,
+    pub pages: Vec<DetailPage>,
+    pub lyrics_cache: Arc<LyricsCache>,
+    /// The track whose lyrics are shown or being loaded.
+    pub lyrics_track: Option<i32>,
+    pub lyrics: Option<Lyrics>,
+    pub is_loading_lyrics: bool,
+    /// Whether the lyrics of `lyrics_track` failed to load.
+    pub lyrics_failed: bool,
+    pub show_lyrics: bool,
+    pub downloaded: u64,
+    pub show_diagnostics: bool,
     pub has_focus: bool,
     pub should_quit: bool,
 }
 
 impl App {
-    pub async fn new() -> color_eyre::Result<Self> {
+    pub async fn new(args: Args) -> color_eyre::Result<Self> {
         let (event_tx, event_rx) = flume::unbounded();
-        let player = AudioPlayer::new(event_tx.clone()).await?;
+        let mut profiles = Profiles::open()?;
+        let profile = profiles.select(args.profile.as_deref(), args.token)?;
+        let player =
+            AudioPlayer::new(event_tx.clone(), &profile, args.offline).await?;
+        let progress_rx = player.track_progress.subscribe();
+        let lyrics_cache =
+            Arc::new(LyricsCache::open(profile.directory().join("lyrics"))?);
 
         Ok(Self {
             event_rx,
             event_tx,
             player,
+            profiles,
+            profile,
+            progress_rx,
+            library: Library::default(),
+            search: None,
+            prompt: None,
+            pages: Vec::new(),
+            lyrics_cache,
+            lyrics_track: None,
+            lyrics: None,
+            is_loading_lyrics: false,
+            lyrics_failed: false,
+            show_lyrics: false,
+            downloaded: 0,
+            show_diagnostics: false,
             has_focus: true,
             should_quit: false,
         })
@@ -47,22 +123,42 @@ impl App {
         tui.enter()?;
 
         self.handle_event(TerminalEvent::Init).await?;
+        let mut needs_redraw = true;
+        let mut last_draw = Instant::now();
         loop {
-            tui.draw(|f| {
-                self.ui(f);
-            })?;
+            let downloaded = BANDWIDTH.downloaded();
+            if needs_redraw
+                || downloaded != self.downloaded
+                || self.progress_rx.has_changed().unwrap_or(false)
+                || (self.show_diagnostics
+                    && last_draw.elapsed() >= DIAGNOSTICS_INTERVAL)
+                || (self.show_synced_lyrics()
+                    && last_draw.elapsed() >= LYRICS_INTERVAL)
+            {
+                self.progress_rx.borrow_and_update();
+                self.downloaded = downloaded;
+                last_draw = Instant::now();
+                tui.draw(|f| {
+                    self.ui(f);
+                })?;
+            }
 
+            needs_redraw = false;
             if let Some(evt) = tui.next().await {
+                needs_redraw = !matches!(evt, TerminalEvent::Tick);
                 self.handle_event(evt).await?;
             }
 
-            self.handle_actions().await;
+            needs_redraw |= self.handle_actions().await;
+            self.update_lyrics();
 
             if self.should_quit {
                 break;
             }
         }
 
+        self.player.save_episode_position();
+        self.save_settings();
         tui.exit()?;
 
         Ok(())
@@ -73,7 +169,7 @@ impl App {
         evt: TerminalEvent,
     ) -> color_eyre::Result<()> {
         match evt {
-            TerminalEvent::Init => self.player.init().await?,
+            TerminalEvent::Init => self.load_account().await?,
             TerminalEvent::Quit => self.should_quit = true,
             TerminalEvent::FocusGained => self.has_focus = true,
             TerminalEvent::FocusLost => self.has_focus = false,
@@ -84,7 +180,89 @@ impl App {
         Ok(())
     }
 
+    /// Loads the liked tracks and the library of the current account.
+    async fn load_account(&mut self) -> color_eyre::Result<()> {
+        self.player.init().await?;
+        self.library.liked = self.player.liked.clone();
+        if self.player.is_offline {
+            let collections = self.player.offline_store().collections();
+            self.library.load_offline(collections);
+        } else {
+            Library::load(self.player.client(), self.event_tx.clone());
+        }
+
+        Ok(())
+    }
+
+    /// Switches to the account of another profile. Playback stops and
+    /// everything loaded for the previous account is dropped, while the
+    /// audio output stays open.
+    async fn switch_profile(&mut self, name: &str) {
+        if name == self.profile.name {
+            return;
+        }
+        let Some(profile) = self.profiles.get(name) else {
+            return;
+        };
+        let lyrics_cache =
+            match LyricsCache::open(profile.directory().join("lyrics")) {
+                Ok(cache) => Arc::new(cache),
+                Err(err) => {
+                    warn!("Failed to switch to profile {}: {}", name, err);
+                    return;
+                }
+            };
+
+        self.player.save_episode_position();
+        self.save_settings();
+        // Tasks still running for the previous account keep sending to the
+        // old channel, so their results are dropped along with it.
+        let (event_tx, event_rx) = flume::unbounded();
+        if let Err(err) = self.player.switch_profile(&profile, event_tx.clone())
+        {
+            warn!("Failed to switch to profile {}: {:#}", name, err);
+            return;
+        }
+        if let Err(err) = self.profiles.select(Some(name), None) {
+            warn!("Failed to remember the last profile: {}", err);
+        }
+        self.profile = profile;
+
+        self.event_tx = event_tx;
+        self.event_rx = event_rx;
+        self.library = Library::default();
+        self.pages.clear();
+        self.search = None;
+        self.lyrics_cache = lyrics_cache;
+        self.lyrics_track = None;
+        self.lyrics = None;
+        self.is_loading_lyrics = false;
+        self.lyrics_failed = false;
+        if let Err(err) = self.load_account().await {
+            warn!("Failed to load profile {}: {:#}", name, err);
+        }
+    }
+
+    /// Remembers the volume and data saver mode of the current profile.
+    fn save_settings(&mut self) {
+        let settings = self.player.settings();
+        if let Err(err) =
+            self.profiles.save_settings(&self.profile.name, settings)
+        {
+            warn!("Failed to save profile settings: {}", err);
+        }
+    }
+
     async fn handle_key_event(&mut self, evt: KeyEvent) {
+        if evt.kind == KeyEventKind::Press && self.prompt.is_some() {
+            self.handle_prompt_key_event(evt).await;
+            return;
+        }
+        if evt.kind == KeyEventKind::Press && self.search.is_some() {
+            self.handle_search_key_event(evt).await;
+            return;
+        }
+
         #[allow(clippy::single_match)]
         if evt.kind == KeyEventKind::Press {
             keymap! { evt,
@@ -101,20 +279,854 @@ impl App {
                 KeyCode::Char('r') => self.player.toggle_repeat_mode(),
                 KeyCode::Char('s') => self.player.toggle_shuffling(),
                 KeyCode::Char('m') => self.player.toggle_mute(),
+                KeyCode::Char('d') => self.player.toggle_data_saver(),
+                KeyCode::Char('i') => {
+                    self.show_diagnostics = !self.show_diagnostics
+                },
+                KeyCode::Char('o') => self.toggle_offline(),
+                KeyCode::F(5) => {
+                    if !self.player.is_offline {
+                        Library::load_home(
+                            self.player.client(),
+                            self.event_tx.clone(),
+                        );
+                    }
+                },
+                KeyCode::Char('y') => self.toggle_lyrics(),
+                KeyCode::Char('[') => {
+                    self.shift_lyrics(-LYRICS_OFFSET_STEP_MS)
+                },
+                KeyCode::Char(']') => self.shift_lyrics(LYRICS_OFFSET_STEP_MS),
+                KeyCode::Up => self.select_previous(),
+                KeyCode::Char('k') => self.select_previous(),
+                KeyCode::Down => self.select_next(),
+                KeyCode::Char('j') => self.select_next(),
+                KeyCode::Left => self.switch_section(false),
+                KeyCode::BackTab => self.switch_section(false),
+                KeyCode::Right => self.switch_section(true),
+                KeyCode::Tab => self.switch_section(true),
+                KeyCode::Esc => self.go_back(),
+                KeyCode::Backspace => self.go_back(),
+                KeyCode::Enter => self.open_selected().await,
+                KeyCode::Char('b') => self.open_album_page(),
+                KeyCode::Char('e') => self.open_artist_page(),
+                KeyCode::Char('a') => self.enqueue_selected(),
+                KeyCode::Char('A') => self.enqueue_all(),
+                KeyCode::Char('l') => {
+                    self.player.toggle_like();
+                    self.library.liked = self.player.liked.clone();
+                },
+                KeyCode::Char('x') => {
+                    self.player.toggle_dislike().await;
+                    self.library.liked = self.player.liked.clone();
+                },
+                KeyCode::Char('N') => {
+                    self.open_prompt(PromptKind::CreatePlaylist, String::new())
+                },
+                KeyCode::Char('R') => self.rename_playlist(),
+                KeyCode::Char('D') => self.delete_playlist(),
+                KeyCode::Char('V') => self.toggle_playlist_visibility(),
+                KeyCode::Char('P') => self.add_to_playlist(),
+                KeyCode::Delete => self.remove_from_playlist(),
+                KeyCode::Char('J') => self.move_in_playlist(1),
+                KeyCode::Char('K') => self.move_in_playlist(-1),
+                KeyCode::Char('M') => self.toggle_played(),
+                KeyCode::Char('U') => {
+                    let selected = self
+                        .profiles
+                        .names()
+                        .iter()
+                        .position(|name| *name == self.profile.name)
+                        .unwrap_or_default();
+                    let mut prompt =
+                        Prompt::new(PromptKind::SwitchProfile, String::new());
+                    prompt.selected = selected;
+                    self.prompt = Some(prompt);
+                },
+                KeyCode::Char('w') => {
+                    if !self.player.is_offline {
+                        self.start_radio();
+                    }
+                },
+                KeyCode::Char('/') => {
+                    if !self.player.is_offline {
+                        self.search = Some(Search::default());
+                    }
+                },
+            }
+        }
+    }
+
+    async fn handle_prompt_key_event(&mut self, evt: KeyEvent) {
+        let options = self.prompt_options().len();
+        let Some(prompt) = self.prompt.as_mut() else {
+            return;
+        };
+
+        match evt.code {
+            KeyCode::Esc => self.prompt = None,
+            KeyCode::Enter => self.submit_prompt().await,
+            KeyCode::Up => prompt.selected = prompt.selected.saturating_sub(1),
+            KeyCode::Down if prompt.selected + 1 < options => {
+                prompt.selected += 1
+            }
+            KeyCode::Backspace if prompt.has_input() => {
+                prompt.input.pop();
+            }
+            KeyCode::Char(c) if prompt.has_input() => prompt.input.push(c),
+            _ => {}
+        }
+    }
+
+    /// What can be picked in the open prompt.
+    fn prompt_options(&self) -> Vec<String> {
+        match self.prompt.as_ref().map(|prompt| &prompt.kind) {
+            Some(PromptKind::AddToPlaylist { .. }) => self
+                .library
+                .playlists()
+                .into_iter()
+                .map(|playlist| playlist.title.clone())
+                .collect(),
+            Some(PromptKind::SwitchProfile) => self.profiles.names(),
+            _ => Vec::new(),
+        }
+    }
+
+    /// Opens a prompt for a playlist change. Offline, only tracks can be
+    /// added to playlists, which is synced on the next start with a
+    /// connection.
+    fn open_prompt(&mut self, kind: PromptKind, input: String) {
+        if !self.player.is_offline
+            || matches!(kind, PromptKind::AddToPlaylist { .. })
+        {
+            self.prompt = Some(Prompt::new(kind, input));
+        }
+    }
+
+    async fn submit_prompt(&mut self) {
+        let options = self.prompt_options();
+        let Some(prompt) = self.prompt.take() else {
+            return;
+        };
+
+        let title = prompt.input.trim().to_string();
+        let action = match prompt.kind {
+            PromptKind::CreatePlaylist if !title.is_empty() => {
+                PlaylistAction::Create { title }
+            }
+            PromptKind::RenamePlaylist { uid, kind } if !title.is_empty() => {
+                PlaylistAction::Rename { uid, kind, title }
+            }
+            PromptKind::DeletePlaylist { uid, kind, .. } => {
+                if self.library.opened.is_some()
+                    && self.library.selected_playlist().is_some_and(
+                        |playlist| playlist.uid == uid && playlist.kind == kind,
+                    )
+                {
+                    self.library.close();
+                }
+                PlaylistAction::Delete { uid, kind }
+            }
+            PromptKind::AddToPlaylist { tracks } => {
+                let playlists = self.library.playlists();
+                let Some(playlist) = playlists.get(prompt.selected) else {
+                    return;
+                };
+                let tracks = tracks
+                    .iter()
+                    .map(|t| (t.id, t.albums.first().and_then(|a| a.id)))
+                    .collect();
+                PlaylistAction::Edit {
+                    uid: playlist.uid,
+                    kind: playlist.kind,
+                    edit: PlaylistEdit::Insert { at: None, tracks },
+                }
+            }
+            PromptKind::SwitchProfile => {
+                if let Some(name) = options.get(prompt.selected) {
+                    self.switch_profile(name).await;
+                }
+                return;
+            }
+            _ => return,
+        };
+
+        self.update_playlist(action);
+    }
+
+    /// Applies a playlist change. Offline, edits and visibility changes are
+    /// queued and synced on the next start with a connection, while other
+    /// changes aren't possible.
+    fn update_playlist(&self, action: PlaylistAction) {
+        if !self.player.is_offline {
+            Library::update_playlist(
+                self.player.client(),
+                self.event_tx.clone(),
+                action,
+            );
+            return;
+        }
+
+        match action {
+            PlaylistAction::Edit { kind, edit, .. } => match edit {
+                PlaylistEdit::Insert { tracks, .. } => {
+                    for (track_id, album_id) in tracks {
+                        self.player.sync(PendingAction::AddToPlaylist {
+                            kind,
+                            track_id,
+                            album_id,
+                        });
+                    }
+                }
+                PlaylistEdit::Remove { track_id, .. } => {
+                    self.player.sync(PendingAction::RemoveFromPlaylist {
+                        kind,
+                        track_id,
+                    });
+                }
+                PlaylistEdit::Move { track_id, from, to } => {
+                    self.player.sync(PendingAction::MoveInPlaylist {
+                        kind,
+                        track_id,
+                        from,
+                        to,
+                    });
+                }
+            },
+            PlaylistAction::SetVisibility {
+                kind, is_public, ..
+            } => {
+                self.player.sync(PendingAction::SetPlaylistVisibility {
+                    kind,
+                    is_public,
+                });
+            }
+            _ => {
+                warn!("Playlists can't be created, renamed or deleted offline")
+            }
+        }
+    }
+
+    fn rename_playlist(&mut self) {
+        if let Some(playlist) = self.library.selected_playlist() {
+            let kind = PromptKind::RenamePlaylist {
+                uid: playlist.uid,
+                kind: playlist.kind,
+            };
+            self.open_prompt(kind, playlist.title.clone());
+        }
+    }
+
+    fn delete_playlist(&mut self) {
+        if let Some(playlist) = self.library.selected_playlist() {
+            let kind = PromptKind::DeletePlaylist {
+                uid: playlist.uid,
+                kind: playlist.kind,
+                title: playlist.title.clone(),
+            };
+            self.open_prompt(kind, String::new());
+        }
+    }
+
+    fn toggle_playlist_visibility(&mut self) {
+        if let Some(playlist) = self.library.selected_playlist() {
+            self.update_playlist(PlaylistAction::SetVisibility {
+                uid: playlist.uid,
+                kind: playlist.kind,
+                is_public: !playlist.is_public(),
+            });
+        }
+    }
+
+    /// Asks for a playlist to add the selected track to, or the playing
+    /// track if no track is selected.
+    fn add_to_playlist(&mut self) {
+        let selected = self.library.selected();
+        let track = match self.library.tracks() {
+            Some(tracks) => tracks.get(selected),
+            None => self.player.track.as_ref(),
+        };
+
+        if let Some(track) = track {
+            let kind = PromptKind::AddToPlaylist {
+                tracks: vec![track.clone()],
+            };
+            self.open_prompt(kind, String::new());
+        }
+    }
+
+    /// Removes the selected track from the opened playlist. The change is
+    /// shown right away and reverted if it can't be applied.
+    fn remove_from_playlist(&mut self) {
+        if self.library.opened.is_none() {
+            return;
+        }
+        let Some((uid, kind)) = self
+            .library
+            .selected_playlist()
+            .map(|playlist| (playlist.uid, playlist.kind))
+        else {
+            return;
+        };
+
+        if let Some((index, track)) = self.library.remove_selected() {
+            self.update_playlist(PlaylistAction::Edit {
+                uid,
+                kind,
+                edit: PlaylistEdit::Remove {
+                    track_id: track.id,
+                    index: Some(index),
+                },
+            });
+        }
+    }
+
+    /// Moves the selected track of the opened playlist by `offset`.
+    fn move_in_playlist(&mut self, offset: isize) {
+        if self.player.is_offline || self.library.opened.is_none() {
+            return;
+        }
+        let Some((uid, kind)) = self
+            .library
+            .selected_playlist()
+            .map(|playlist| (playlist.uid, playlist.kind))
+        else {
+            return;
+        };
+
+        let Some((from, to)) = self.library.move_selected(offset) else {
+            return;
+        };
+        if let Some(track) = self.library.tracks().and_then(|t| t.get(to)) {
+            let track_id = track.id;
+            self.update_playlist(PlaylistAction::Edit {
+                uid,
+                kind,
+                edit: PlaylistEdit::Move { track_id, from, to },
+            });
+        }
+    }
+
+    /// Keys typed while the search view is open go into the query, so only
+    /// keys that can't be typed control the view.
+    async fn handle_search_key_event(&mut self, evt: KeyEvent) {
+        let client = self.player.client();
+        let event_tx = self.event_tx.clone();
+        let Some(search) = self.search.as_mut() else {
+            return;
+        };
+
+        match (evt.code, evt.modifiers) {
+            (KeyCode::Char('c'), KeyModifiers::CONTROL) => {
+                self.should_quit = true
+            }
+            (KeyCode::Char('a'), KeyModifiers::CONTROL) => {
+                self.enqueue_search_result()
+            }
+            (KeyCode::Esc, _) => self.close_search(),
+            (KeyCode::Enter, _) => self.open_search_result().await,
+            (KeyCode::Up, _) => search.select_previous(),
+            (KeyCode::Down, _) => search.select_next(client, event_tx),
+            (KeyCode::Tab, _) => search.next_kind(client, event_tx),
+            (KeyCode::BackTab, _) => search.previous_kind(client, event_tx),
+            (KeyCode::Backspace, _) => search.pop(client, event_tx),
+            (KeyCode::Char(c), _) => search.push(c, client, event_tx),
+            _ => {}
+        }
+    }
+
+    fn close_search(&mut self) {
+        if let Some(mut search) = self.search.take() {
+            search.cancel();
+        }
+    }
+
+    /// Plays the selected track, replacing the queue with the tracks that
+    /// were found, or opens the page of the selected item in the library.
+    async fn open_search_result(&mut self) {
+        let Some(search) = self.search.as_ref() else {
+            return;
+        };
+
+        match search.selected_result() {
+            Some(SearchResult::Track(_)) => {
+                let (tracks, index) = search.tracks();
+                self.player
+                    .play_tracks(tracks, index, PlayContext::Search)
+                    .await;
+            }
+            Some(SearchResult::Item(item)) => {
+                self.load_item(item.clone(), false);
+                self.close_search();
+            }
+            None => {}
+        }
+    }
+
+    /// Adds the selected track, or all tracks of the selected item, to the
+    /// end of the queue.
+    fn enqueue_search_result(&mut self) {
+        match self.search.as_ref().and_then(|s| s.selected_result()) {
+            Some(SearchResult::Track(track)) => {
+                self.player.enqueue(vec![track.clone()])
+            }
+            Some(SearchResult::Item(item)) => {
+                self.load_item(item.clone(), true)
+            }
+            None => {}
+        }
+    }
+
+    /// Plays the selected track, replacing the queue with the list it is in,
+    /// or opens the selected playlist, album or artist.
+    async fn open_selected(&mut self) {
+        if let Some(page) = self.pages.last() {
+            match page.selected_entry() {
+                Some(DetailEntry::Track { .. }) if page.is_long_form => {
+                    let (tracks, index) = page.tracks();
+                    let context = page.context.clone();
+                    self.player.play_episodes(tracks, index, context).await;
+                }
+                Some(DetailEntry::Track { .. }) => {
+                    let (tracks, index) = page.tracks();
+                    let context = page.context.clone();
+                    self.player.play_tracks(tracks, index, context).await;
+                }
+                Some(DetailEntry::Item { item, .. }) => {
+                    let item = item.clone();
+                    self.open_page(&item);
+                }
+                _ => {}
+            }
+        } else if let Some(tracks) = self.library.tracks() {
+            let tracks = tracks.to_vec();
+            let index = self.library.selected();
+            let context = self.library.context();
+            self.player.play_tracks(tracks, index, context).await;
+        } else if let Some(LibraryItem::Station(station)) =
+            self.library.selected_item()
+        {
+            self.player
+                .start_radio(station.id.key(), station.name.clone());
+        } else if let Some(item) = self
+            .library
+            .selected_item()
+            .filter(|item| item.opens_as_page())
+        {
+            self.open_page(item);
+        } else if let Some(item) = self.library.selected_item() {
+            self.load_item(item.clone(), false);
+        }
+    }
+
+    /// Loads the tracks of a collection to open or queue them. Offline,
+    /// they come from the offline store.
+    fn load_item(&self, item: LibraryItem, append: bool) {
+        if !self.player.is_offline {
+            Library::load_item(
+                self.player.client(),
+                self.event_tx.clone(),
+                item,
+                append,
+            );
+            return;
+        }
+
+        let source = item.source();
+        let tracks = source
+            .as_ref()
+            .map(|source| self.player.offline_store().collection_tracks(source))
+            .unwrap_or_default();
+        let _ = self.event_tx.send(Event::CollectionLoaded {
+            title: item.title(),
+            source,
+            tracks,
+            append,
+        });
+    }
+
+    fn select_previous(&mut self) {
+        match self.pages.last_mut() {
+            Some(page) => page.select_previous(),
+            None => self.library.select_previous(),
+        }
+    }
+
+    fn select_next(&mut self) {
+        match self.pages.last_mut() {
+            Some(page) => {
+                page.select_next();
+                page.load_more(self.player.client(), self.event_tx.clone());
             }
+            None => self.library.select_next(),
         }
     }
 
-    async fn handle_actions(&mut self) {
+    fn switch_section(&mut self, forward: bool) {
+        self.pages.clear();
+        if forward {
+            self.library.next_section();
+        } else {
+            self.library.previous_section();
+        }
+    }
+
+    /// Closes the topmost album or artist page, or the opened collection.
+    fn go_back(&mut self) {
+        if self.pages.pop().is_none() {
+            self.library.close();
+        }
+    }
+
+    /// The selected track, or the playing track if no track is selected.
+    fn selected_track(&self) -> Option<&Track> {
+        let selected = match self.pages.last() {
+            Some(page) => match page.selected_entry() {
+                Some(DetailEntry::Track { track, .. }) => Some(track),
+                _ => None,
+            },
+            None => self
+                .library
+                .tracks()
+                .and_then(|tracks| tracks.get(self.library.selected())),
+        };
+
+        selected.or(self.player.track.as_ref())
+    }
+
+    /// The selected playlist, album, artist or station, if no track list is
+    /// shown.
+    fn selected_item(&self) -> Option<&LibraryItem> {
+        match self.pages.last() {
+            Some(page) => match page.selected_entry() {
+                Some(DetailEntry::Item { item, .. }) => Some(item),
+                _ => None,
+            },
+            None if self.library.tracks().is_none() => {
+                self.library.selected_item()
+            }
+            None => None,
+        }
+    }
+
+    /// Opens the page of an album, artist, playlist or feed. Stations can't
+    /// be shown as a page.
+    fn open_page(&self, item: &LibraryItem) {
+        let (client, event_tx) = (self.player.client(), self.event_tx.clone());
+        match item {
+            LibraryItem::Album(album) if album.is_long_form() => {
+                DetailPage::load_podcast(client, event_tx, album.id)
+            }
+            LibraryItem::Album(album) => {
+                DetailPage::load_album(client, event_tx, album.id)
+            }
+            LibraryItem::Artist(artist) => {
+                DetailPage::load_artist(client, event_tx, artist.id)
+            }
+            LibraryItem::Playlist(playlist) => DetailPage::load_playlist(
+                client,
+                event_tx,
+                playlist.uid,
+                playlist.kind,
+            ),
+            LibraryItem::Feed {
+                title,
+                source,
+                entities,
+            } => DetailPage::load_feed(
+                client,
+                event_tx,
+                title.clone(),
+                source.clone(),
+                entities.clone(),
+            ),
+            LibraryItem::Station(_) => {}
+        }
+    }
+
+    /// Opens the page of the selected album, or of the album of the selected
+    /// track.
+    fn open_album_page(&self) {
+        if self.player.is_offline {
+            return;
+        }
+
+        let album_id = match self.selected_item() {
+            Some(LibraryItem::Album(album)) => Some(album.id),
+            _ => self
+                .selected_track()
+                .and_then(|track| track.albums.first())
+                .and_then(|album| album.id),
+        };
+        if let Some(album_id) = album_id {
+            DetailPage::load_album(
+                self.player.client(),
+                self.event_tx.clone(),
+                album_id,
+            );
+        }
+    }
+
+    /// Opens the page of the selected artist, or of the first artist of the
+    /// selected track. Album pages list all of their artists.
+    fn open_artist_page(&self) {
+        if self.player.is_offline {
+            return;
+        }
+
+        let artist_id = match self.selected_item() {
+            Some(LibraryItem::Artist(artist)) => Some(artist.id),
+            _ => self
+                .selected_track()
+                .and_then(|track| track.artists.first())
+                .and_then(|artist| artist.id),
+        };
+        if let Some(artist_id) = artist_id {
+            DetailPage::load_artist(
+                self.player.client(),
+                self.event_tx.clone(),
+                artist_id,
+            );
+        }
+    }
+
+    /// Marks the selected episode (or the playing one) as played, or as not
+    /// played if it was already.
+    fn toggle_played(&mut self) {
+        if let Some(track_id) = self.selected_track().map(|track| track.id) {
+            self.player.toggle_played(track_id);
+        }
+    }
+
+    /// Starts a station based on the selected track or artist, or My Wave if
+    /// nothing else is selected.
+    fn start_radio(&mut self) {
+        let selected = self.library.selected();
+        let track = self
+            .library
+            .tracks()
+            .and_then(|tracks| tracks.get(selected));
+        let (station, name) = match (track, self.library.selected_item()) {
+            (Some(track), _) => (
+                format!("track:{}", track.id),
+                track.title.clone().unwrap_or_else(|| "Unknown".to_string()),
+            ),
+            (None, Some(LibraryItem::Artist(artist))) => {
+                let name = artist.name.clone();
+                (
+                    format!("artist:{}", artist.id),
+                    name.unwrap_or_else(|| "Unknown".to_string()),
+                )
+            }
+            (None, Some(LibraryItem::Station(station))) => {
+                (station.id.key(), station.name.clone())
+            }
+            _ => {
+                let station = Station::my_wave();
+                (station.id.key(), station.name)
+            }
+        };
+
+        self.player.start_radio(station, name);
+    }
+
+    /// Adds the selected track, or all tracks of the selected playlist,
+    /// album or artist, to the end of the queue.
+    fn enqueue_selected(&mut self) {
+        if let Some(page) = self.pages.last() {
+            match page.selected_entry() {
+                Some(DetailEntry::Track { track, .. }) => {
+                    self.player.enqueue(vec![track.clone()])
+                }
+                Some(DetailEntry::Item { item, .. }) => {
+                    self.load_item(item.clone(), true)
+                }
+                _ => {}
+            }
+        } else if let Some(tracks) = self.library.tracks() {
+            if let Some(track) = tracks.get(self.library.selected()) {
+                self.player.enqueue(vec![track.clone()]);
+            }
+        } else if let Some(item) = self.library.selected_item() {
+            self.load_item(item.clone(), true);
+        }
+    }
+
+    /// Adds every track of the shown list to the end of the queue.
+    fn enqueue_all(&mut self) {
+        match self.library.tracks() {
+            Some(tracks) => self.player.enqueue(tracks.to_vec()),
+            None => self.enqueue_selected(),
+        }
+    }
+
+    /// Toggles offline availability of the opened or selected collection.
+    fn toggle_offline(&mut self) {
+        let collection = match &self.library.opened {
+            Some(opened) => {
+                opened.source.clone().map(|s| (s, opened.title.clone()))
+            }
+            None if self.library.current_section()
+                == LibrarySection::LikedTracks =>
+            {
+                Some((CollectionSource::Liked, "Liked tracks".to_string()))
+            }
+            None => self
+                .library
+                .selected_item()
+                .and_then(|item| item.source().map(|s| (s, item.title()))),
+        };
+
+        if let Some((source, title)) = collection {
+            self.player.toggle_offline(source, title);
+        }
+    }
+
+    /// Loads the lyrics of the playing track while the lyrics panel is
+    /// shown.
+    fn update_lyrics(&mut self) {
+        let track_id = self.player.track.as_ref().map(|track| track.id);
+        if !self.show_lyrics || track_id == self.lyrics_track {
+            return;
+        }
+
+        self.lyrics_track = track_id;
+        self.lyrics = None;
+        self.is_loading_lyrics = track_id.is_some();
+        self.lyrics_failed = false;
+        let Some(track_id) = track_id else {
+            return;
+        };
+        if self.player.is_offline {
+            self.lyrics = self.lyrics_cache.get(track_id);
+            self.is_loading_lyrics = false;
+            return;
+        }
+
+        let cache = self.lyrics_cache.clone();
+        let client = self.player.client();
+        let http = self.player.http();
+        let event_tx = self.event_tx.clone();
+        tokio::spawn(async move {
+            let (lyrics, failed) =
+                match cache.load(&client, &http, track_id).await {
+                    Ok(lyrics) => (lyrics, false),
+                    Err(err) => {
+                        warn!("Failed to load lyrics: {:#}", err);
+                        (None, true)
+                    }
+                };
+            let _ = event_tx.send(Event::LyricsLoaded {
+                track_id,
+                lyrics,
+                failed,
+            });
+        });
+    }
+
+    /// Shows or hides the lyrics panel. Lyrics that failed to load are
+    /// requested again when it is shown.
+    fn toggle_lyrics(&mut self) {
+        self.show_lyrics = !self.show_lyrics;
+        if self.show_lyrics && self.lyrics_failed {
+            self.lyrics_track = None;
+        }
+    }
+
+    fn show_synced_lyrics(&self) -> bool {
+        self.show_lyrics
+            && self.lyrics.as_ref().is_some_and(|lyrics| lyrics.is_synced)
+    }
+
+    /// Shifts the lyrics of the playing track by `offset_ms`, for tracks
+    /// whose lyrics are out of sync with the audio.
+    fn shift_lyrics(&mut self, offset_ms: i64) {
+        let Some(track_id) = self.lyrics_track.filter(|_| self.show_lyrics)
+        else {
+            return;
+        };
+
+        let offset_ms = self.lyrics_cache.offset(track_id) + offset_ms;
+        if let Err(err) = self.lyrics_cache.set_offset(track_id, offset_ms) {
+            warn!("Failed to save the lyrics offset: {}", err);
+        }
+    }
+
+    async fn handle_actions(&mut self) -> bool {
+        let mut handled = false;
         while let Ok(evt) = self.event_rx.try_recv() {
             self.handle_action(evt).await;
+            handled = true;
         }
+        handled
     }
 
     async fn handle_action(&mut self, evt: Event) {
         match evt {
             Event::Play(track_id) => self.player.play_track(track_id).await,
             Event::TrackEnded => self.player.on_track_end().await,
+            Event::PlayStarted {
+                track_id,
+                from_cache,
+                start_at,
+            } => self.player.start_play(track_id, from_cache, start_at),
+            Event::LibraryLoaded(section, items) => {
+                self.library.items.insert(section, items);
+            }
+            Event::CollectionLoaded {
+                title,
+                source,
+                tracks,
+                append,
+            } => {
+                if append {
+                    self.player.enqueue(tracks);
+                } else {
+                    self.library.open(title, source, tracks);
+                }
+            }
+            Event::PageLoaded(page) => self.pages.push(page),
+            Event::PageExtended {
+                key,
+                entries,
+                failed,
+            } => {
+                if let Some(page) = self
+                    .pages
+                    .iter_mut()
+                    .find(|page| page.feed.as_ref() == Some(&key))
+                {
+                    page.extend(entries, failed);
+                }
+            }
+            Event::PlaylistUpdated(playlist) => {
+                self.library.on_playlist_updated(playlist)
+            }
+            Event::RadioLoaded {
+                station,
+                batch_id,
+                tracks,
+            } => self.player.on_radio_loaded(station, batch_id, tracks).await,
+            Event::LyricsLoaded {
+                track_id,
+                lyrics,
+                failed,
+            } if self.lyrics_track == Some(track_id) => {
+                self.lyrics = lyrics;
+                self.is_loading_lyrics = false;
+                self.lyrics_failed = failed;
+            }
+            Event::SearchLoaded {
+                query,
+                kind,
+                page,
+                total,
+                results,
+            } => {
+                if let Some(search) = self.search.as_mut() {
+                    search.on_loaded(query, kind, page, total, results);
+                }
+            }
             _ => {}
         }
     }
@@ -138,11 +1150,25 @@ impl Widget for &App {
             .constraints([Constraint::Min(1), Constraint::Length(3)])
             .split(area);
 
-        let title = Title::default()
-            .alignment(Alignment::Center)
-            .content("Yandex Music");
+        let mut title = if self.player.is_offline {
+            "Yandex Music (offline)".to_string()
+        } else if let Some(radio) = self.player.radio.as_ref() {
+            format!("Yandex Music ( {})", radio.name)
+        } else {
+            "Yandex Music".to_string()
+        };
+        if self.profile.name != DEFAULT_PROFILE {
+            title = format!("{} · {}", title, self.profile.name);
+        }
+        let title =
+            Title::default().alignment(Alignment::Center).content(title);
+        let mut usage = format!(" {} ", format_bytes(self.downloaded));
+        if self.player.is_data_saver {
+            usage = format!(" data saver ·{}", usage);
+        }
+        let usage = Title::default().alignment(Alignment::Right).content(usage);
 
-        Block::new()
+        let block = Block::new()
             .borders(Borders::LEFT | Borders::TOP | Borders::RIGHT)
             .border_set(border::Set {
                 bottom_left: symbols::line::ROUNDED.vertical_right,
@@ -150,7 +1176,46 @@ impl Widget for &App {
                 ..symbols::border::ROUNDED
             })
             .title(title)
-            .render(chunks[0], buf);
+            .title(usage);
+        let inner = block.inner(chunks[0]);
+        block.render(chunks[0], buf);
+
+        let mut inner = inner;
+        if self.show_lyrics {
+            let layout = Layout::default()
+                .direction(Direction::Horizontal)
+                .constraints([
+                    Constraint::Percentage(60),
+                    Constraint::Percentage(40),
+                ])
+                .split(inner);
+            inner = layout[0];
+
+            let position = self.player.position();
+            let offset_ms = self
+                .lyrics_track
+                .map_or(0, |track_id| self.lyrics_cache.offset(track_id));
+            LyricsWidget::new(
+                self.lyrics.as_ref(),
+                self.is_loading_lyrics,
+                self.lyrics_failed,
+                position,
+                offset_ms,
+            )
+            .render(layout[1], buf);
+        }
+
+        let current_track = self.player.track.as_ref().map(|t| t.id);
+        if let Some(search) = self.search.as_ref() {
+            SearchWidget::new(search, current_track).render(inner, buf);
+        } else if let Some(page) = self.pages.last() {
+            DetailPageWidget::new(page, current_track, self.player.episodes())
+                .render(inner, buf);
+        } else {
+            let offline = self.player.offline_collections();
+            LibraryWidget::new(&self.library, &offline, current_track)
+                .render(inner, buf);
+        }
 
         let track_title: &str;
         let track_artist: Option<String>;
@@ -181,7 +1246,20 @@ impl Widget for &App {
                 self.player.volume
             },
             self.player.is_playing.load(Ordering::Relaxed),
+            self.player
+                .track
+                .as_ref()
+                .is_some_and(|track| self.player.is_liked(track.id)),
         );
         player_widget.render(chunks[1], buf);
+
+        if let Some(prompt) = self.prompt.as_ref() {
+            PromptWidget::new(prompt, self.prompt_options())
+                .render(chunks[0], buf);
+        }
+
+        if self.show_diagnostics {
+            DiagnosticsWidget::new(METRICS.snapshot()).render(chunks[0], buf);
+        }
     }
 }
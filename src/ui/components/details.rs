@@ -0,0 +1,117 @@
+use ratatui::{
+    buffer::Buffer,
+    layout::{Constraint, Direction, Layout, Rect},
+    style::{Color, Style, Stylize},
+    text::Line,
+    widgets::{List, ListItem, ListState, Paragraph, StatefulWidget, Widget},
+};
+
+use crate::{
+    audio::episodes::EpisodeStore,
+    ui::{
+        details::{DetailEntry, DetailPage},
+        utils::format_duration,
+    },
+};
+
+pub struct DetailPageWidget<'a> {
+    page: &'a DetailPage,
+    current_track: Option<i32>,
+    episodes: &'a EpisodeStore,
+}
+
+impl<'a> DetailPageWidget<'a> {
+    /// `episodes` provides the progress shown next to episodes on podcast
+    /// and audiobook pages.
+    pub fn new(
+        page: &'a DetailPage,
+        current_track: Option<i32>,
+        episodes: &'a EpisodeStore,
+    ) -> Self {
+        Self {
+            page,
+            current_track,
+            episodes,
+        }
+    }
+
+    /// Whether an episode was played, or where it was left off.
+    fn episode_state(&self, track_id: i32) -> String {
+        if !self.page.is_long_form {
+            return String::new();
+        }
+
+        if self.episodes.is_played(track_id) {
+            return "  󰄬".to_string();
+        }
+        match self.episodes.resume_position(track_id) {
+            Some(position) => format!("  󰐊 {}", format_duration(position)),
+            None => String::new(),
+        }
+    }
+}
+
+impl<'a> Widget for DetailPageWidget<'a> {
+    fn render(self, area: Rect, buf: &mut Buffer) {
+        let layout = Layout::default()
+            .direction(Direction::Vertical)
+            .constraints([Constraint::Length(3), Constraint::Min(1)])
+            .split(area);
+
+        Paragraph::new(vec![
+            Line::from(format!(" 󰁍 {}", self.page.title)).bold(),
+            Line::from(format!("   {}", self.page.subtitle))
+                .fg(Color::from_u32(0x00464646)),
+        ])
+        .render(layout[0], buf);
+
+        let mut items =
+            self.page
+                .entries
+                .iter()
+                .map(|entry| match entry {
+                    DetailEntry::Heading(heading) => ListItem::new(
+                        Line::from(heading.clone())
+                            .bold()
+                            .fg(Color::from_u32(0x00f7d44b)),
+                    ),
+                    DetailEntry::Track { label, track }
+                        if self.current_track == Some(track.id) =>
+                    {
+                        ListItem::new(
+                            Line::from(format!(
+                                "  {}{}",
+                                label,
+                                self.episode_state(track.id)
+                            ))
+                            .fg(Color::from_u32(0x00f7d44b)),
+                        )
+                    }
+                    DetailEntry::Track { label, track } => ListItem::new(
+                        format!("  {}{}", label, self.episode_state(track.id)),
+                    ),
+                    DetailEntry::Item { label, .. } => {
+                        ListItem::new(format!("  {}", label))
+                    }
+                })
+                .collect::<Vec<_>>();
+        if self.page.is_loading {
+            items.push(ListItem::new("  Loading more...".dim()));
+        }
+
+        if items.is_empty() {
+            Paragraph::new(" Nothing here").render(layout[1], buf);
+            return;
+        }
+
+        let mut state =
+            ListState::default().with_selected(Some(self.page.selected));
+        StatefulWidget::render(
+            List::new(items)
+                .highlight_style(Style::new().bg(Color::from_u32(0x00464646))),
+            layout[1],
+            buf,
+            &mut state,
+        );
+    }
+}
@@ -0,0 +1,91 @@
+use ratatui::{
+    buffer::Buffer,
+    layout::{Alignment, Rect},
+    style::{Color, Style},
+    symbols::border,
+    text::Line,
+    widgets::{block::Title, Block, Borders, Clear, Paragraph, Widget},
+};
+
+use crate::{metrics::MetricsSnapshot, ui::utils::format_bytes};
+
+pub struct DiagnosticsWidget {
+    metrics: MetricsSnapshot,
+}
+
+impl DiagnosticsWidget {
+    pub fn new(metrics: MetricsSnapshot) -> Self {
+        Self { metrics }
+    }
+}
+
+impl Widget for DiagnosticsWidget {
+    fn render(self, area: Rect, buf: &mut Buffer) {
+        let metrics = self.metrics;
+        let fill = (metrics.buffered * 100)
+            .checked_div(metrics.buffer_capacity)
+            .unwrap_or(0);
+
+        let lines = vec![
+            Line::from(format!(
+                "Throughput: {}/s",
+                format_bytes(metrics.throughput)
+            )),
+            Line::from(format!(
+                "Latency:    {} ms ({} requests)",
+                metrics.latency.as_millis(),
+                metrics.requests
+            )),
+            Line::from(format!("Retries:    {}", metrics.retries)),
+            Line::from(format!(
+                "Buffer:     {} / {} ({}%)",
+                format_bytes(metrics.buffered),
+                format_bytes(metrics.buffer_capacity),
+                fill
+            )),
+            Line::from(format!("Underruns:  {}", metrics.underruns)),
+            Line::from(format!(
+                "Output:     {}",
+                metrics.output.as_deref().unwrap_or("-")
+            )),
+            Line::from(format!(
+                "Format:     {}",
+                metrics
+                    .format
+                    .map(|(codec, bitrate)| format!(
+                        "{} {} kbps",
+                        codec, bitrate
+                    ))
+                    .unwrap_or_else(|| "-".to_string())
+            )),
+        ];
+
+        let width = lines
+            .iter()
+            .map(|line| line.width() as u16 + 4)
+            .max()
+            .unwrap_or(0)
+            .min(area.width);
+        let height = (lines.len() as u16 + 2).min(area.height);
+        let area = Rect::new(
+            area.x + (area.width - width) / 2,
+            area.y + (area.height - height) / 2,
+            width,
+            height,
+        );
+
+        Clear.render(area, buf);
+        Paragraph::new(lines)
+            .block(
+                Block::default()
+                    .title(
+                        Title::from(" Diagnostics ")
+                            .alignment(Alignment::Center),
+                    )
+                    .borders(Borders::ALL)
+                    .border_set(border::ROUNDED),
+            )
+            .style(Style::new().bg(Color::from_u32(0x00181818)))
+            .render(area, buf);
+    }
+}
@@ -0,0 +1,136 @@
+use ratatui::{
+    buffer::Buffer,
+    layout::{Constraint, Direction, Layout, Rect},
+    style::{Color, Style, Stylize},
+    text::Line,
+    widgets::{
+        Block, Borders, List, ListItem, ListState, Paragraph, StatefulWidget,
+        Widget,
+    },
+};
+use yandex_music::model::track_model::track::Track;
+
+use crate::{
+    offline::store::CollectionSource,
+    ui::{
+        library::{Library, LibrarySection},
+        utils::format_track,
+    },
+};
+
+pub struct LibraryWidget<'a> {
+    library: &'a Library,
+    offline: &'a [CollectionSource],
+    current_track: Option<i32>,
+}
+
+impl<'a> LibraryWidget<'a> {
+    pub fn new(
+        library: &'a Library,
+        offline: &'a [CollectionSource],
+        current_track: Option<i32>,
+    ) -> Self {
+        Self {
+            library,
+            offline,
+            current_track,
+        }
+    }
+
+    fn track_item(&self, track: &Track) -> ListItem<'a> {
+        let line = Line::from(format_track(track));
+        if self.current_track == Some(track.id) {
+            ListItem::new(line.fg(Color::from_u32(0x00f7d44b)))
+        } else {
+            ListItem::new(line)
+        }
+    }
+
+    fn offline_marker(&self, source: Option<CollectionSource>) -> &'static str {
+        match source {
+            Some(source) if self.offline.contains(&source) => "  󰇚",
+            _ => "",
+        }
+    }
+}
+
+impl<'a> Widget for LibraryWidget<'a> {
+    fn render(self, area: Rect, buf: &mut Buffer) {
+        let layout = Layout::default()
+            .direction(Direction::Horizontal)
+            .constraints([Constraint::Length(18), Constraint::Min(10)])
+            .split(area);
+
+        let sections = LibrarySection::ALL
+            .iter()
+            .enumerate()
+            .map(|(i, section)| {
+                let line = Line::from(format!(" {}", section.title()));
+                if i == self.library.section {
+                    ListItem::new(line.fg(Color::from_u32(0x00f7d44b)))
+                } else {
+                    ListItem::new(line)
+                }
+            })
+            .collect::<Vec<_>>();
+        let sections = List::new(sections).block(
+            Block::new()
+                .borders(Borders::RIGHT)
+                .border_style(Style::new().fg(Color::from_u32(0x00464646))),
+        );
+        Widget::render(sections, layout[0], buf);
+
+        let mut area = layout[1];
+        if let Some(opened) = &self.library.opened {
+            let header = Rect { height: 1, ..area };
+            Paragraph::new(format!(
+                " 󰁍 {}{}",
+                opened.title,
+                self.offline_marker(opened.source.clone())
+            ))
+            .bold()
+            .render(header, buf);
+            area.y += 1;
+            area.height = area.height.saturating_sub(1);
+        }
+
+        let section = self.library.current_section();
+        let items =
+            match (self.library.tracks(), self.library.items.get(&section)) {
+                (Some(tracks), _) => tracks
+                    .iter()
+                    .map(|t| self.track_item(t))
+                    .collect::<Vec<_>>(),
+                (None, Some(items)) => items
+                    .iter()
+                    .map(|item| {
+                        ListItem::new(format!(
+                            "{}{}",
+                            item.title(),
+                            self.offline_marker(item.source())
+                        ))
+                    })
+                    .collect(),
+                (None, None) => {
+                    Paragraph::new(" Loading...").render(area, buf);
+                    return;
+                }
+            };
+
+        if items.is_empty() {
+            Paragraph::new(" Nothing here").render(area, buf);
+            return;
+        }
+
+        let mut state =
+            ListState::default().with_selected(Some(self.library.selected()));
+        StatefulWidget::render(
+            List::new(items)
+                .highlight_style(Style::new().bg(Color::from_u32(0x00464646)))
+                .highlight_symbol(" "),
+            area,
+            buf,
+            &mut state,
+        );
+    }
+}
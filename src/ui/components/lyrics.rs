@@ -0,0 +1,94 @@
+use std::time::Duration;
+
+use ratatui::{
+    buffer::Buffer,
+    layout::{Alignment, Rect},
+    style::{Color, Stylize},
+    text::Line,
+    widgets::{block::Title, Block, Borders, Paragraph, Widget},
+};
+
+use crate::lyrics::Lyrics;
+
+pub struct LyricsWidget<'a> {
+    lyrics: Option<&'a Lyrics>,
+    is_loading: bool,
+    has_failed: bool,
+    position: Duration,
+    offset_ms: i64,
+}
+
+impl<'a> LyricsWidget<'a> {
+    pub fn new(
+        lyrics: Option<&'a Lyrics>,
+        is_loading: bool,
+        has_failed: bool,
+        position: Duration,
+        offset_ms: i64,
+    ) -> Self {
+        Self {
+            lyrics,
+            is_loading,
+            has_failed,
+            position,
+            offset_ms,
+        }
+    }
+}
+
+impl<'a> Widget for LyricsWidget<'a> {
+    fn render(self, area: Rect, buf: &mut Buffer) {
+        let mut title = " Lyrics ".to_string();
+        if self.offset_ms != 0 {
+            title =
+                format!(" Lyrics ({:+.1}s) ", self.offset_ms as f64 / 1000.0);
+        }
+
+        let block = Block::new()
+            .borders(Borders::LEFT)
+            .border_style(Color::from_u32(0x00464646))
+            .title(Title::from(title).alignment(Alignment::Center));
+        let inner = block.inner(area);
+        block.render(area, buf);
+
+        let Some(lyrics) = self.lyrics else {
+            let message = if self.is_loading {
+                "Loading..."
+            } else if self.has_failed {
+                "Failed to load lyrics, press y twice to retry"
+            } else {
+                "No lyrics"
+            };
+            Paragraph::new(message)
+                .alignment(Alignment::Center)
+                .fg(Color::from_u32(0x00464646))
+                .render(inner, buf);
+            return;
+        };
+
+        let current = lyrics.current_line(self.position, self.offset_ms);
+        let lines = lyrics
+            .lines
+            .iter()
+            .enumerate()
+            .map(|(index, line)| {
+                if Some(index) == current {
+                    Line::from(line.text.clone())
+                        .bold()
+                        .fg(Color::from_u32(0x00f7d44b))
+                } else {
+                    Line::from(line.text.clone())
+                }
+            })
+            .collect::<Vec<_>>();
+
+        // Keep the current line in the middle of the panel.
+        let scroll = current
+            .unwrap_or(0)
+            .saturating_sub(inner.height as usize / 2);
+        Paragraph::new(lines)
+            .alignment(Alignment::Center)
+            .scroll((scroll as u16, 0))
+            .render(inner, buf);
+    }
+}
@@ -1,3 +1,9 @@
 pub mod controls;
+pub mod details;
+pub mod diagnostics;
+pub mod library;
+pub mod lyrics;
 pub mod player;
 pub mod progress;
+pub mod prompt;
+pub mod search;
@@ -16,9 +16,11 @@ pub struct PlayerWidget<'a> {
     shuffle_mode: bool,
     volume: u8,
     is_playing: bool,
+    is_liked: bool,
 }
 
 impl<'a> PlayerWidget<'a> {
+    #[allow(clippy::too_many_arguments)]
     pub fn new(
         progress: &'a TrackProgress,
         track_title: &'a str,
@@ -27,6 +29,7 @@ impl<'a> PlayerWidget<'a> {
         shuffle_mode: bool,
         volume: u8,
         is_playing: bool,
+        is_liked: bool,
     ) -> Self {
         Self {
             progress,
@@ -36,6 +39,7 @@ impl<'a> PlayerWidget<'a> {
             shuffle_mode,
             volume,
             is_playing,
+            is_liked,
         }
     }
 }
@@ -52,6 +56,7 @@ impl<'a> Widget for PlayerWidget<'a> {
             self.track_title,
             self.track_artist,
             self.is_playing,
+            self.is_liked,
         );
         let controls_widget = PlayerControlsWidget::new(
             self.repeat_mode,
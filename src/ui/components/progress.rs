@@ -1,5 +1,3 @@
-use std::time::Duration;
-
 use ratatui::{
     buffer::Buffer,
     layout::{Alignment, Rect},
@@ -8,13 +6,14 @@ use ratatui::{
     widgets::{block::Title, Block, Borders, Gauge, Widget},
 };
 
-use crate::audio::progress::TrackProgress;
+use crate::{audio::progress::TrackProgress, ui::utils::format_duration};
 
 pub struct ProgressWidget<'a> {
     progress: &'a TrackProgress,
     track_title: &'a str,
     track_artist: Option<String>,
     is_playing: bool,
+    is_liked: bool,
 }
 
 impl<'a> ProgressWidget<'a> {
@@ -23,12 +22,14 @@ impl<'a> ProgressWidget<'a> {
         track_title: &'a str,
         track_artist: Option<String>,
         is_playing: bool,
+        is_liked: bool,
     ) -> Self {
         Self {
             progress,
             track_title,
             track_artist,
             is_playing,
+            is_liked,
         }
     }
 }
@@ -50,6 +51,9 @@ impl<'a> Widget for ProgressWidget<'a> {
         if let Some(artist) = self.track_artist {
             track_info = format!("{} by {}", track_info, artist);
         }
+        if self.is_liked {
+            track_info = format!("{}  󰋑", track_info);
+        }
 
         let duration_info = format!(
             "{} / {}",
@@ -80,10 +84,3 @@ impl<'a> Widget for ProgressWidget<'a> {
         gauge.render(area, buf);
     }
 }
-
-fn format_duration(duration: Duration) -> String {
-    let total_seconds = duration.as_secs();
-    let minutes = total_seconds / 60;
-    let seconds = total_seconds % 60;
-    format!("{:02}:{:02}", minutes, seconds)
-}
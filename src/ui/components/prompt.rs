@@ -0,0 +1,78 @@
+use ratatui::{
+    buffer::Buffer,
+    layout::{Alignment, Rect},
+    style::{Color, Style},
+    symbols::border,
+    widgets::{
+        block::Title, Block, Borders, Clear, List, ListState, Paragraph,
+        StatefulWidget, Widget,
+    },
+};
+
+use crate::ui::prompt::{Prompt, PromptKind};
+
+pub struct PromptWidget<'a> {
+    prompt: &'a Prompt,
+    options: Vec<String>,
+}
+
+impl<'a> PromptWidget<'a> {
+    /// `options` are what can be picked, such as the titles of playlists
+    /// when adding tracks to one.
+    pub fn new(prompt: &'a Prompt, options: Vec<String>) -> Self {
+        Self { prompt, options }
+    }
+}
+
+impl<'a> Widget for PromptWidget<'a> {
+    fn render(self, area: Rect, buf: &mut Buffer) {
+        let title = format!(" {} ", self.prompt.title());
+        let rows = if self.prompt.has_options() {
+            self.options.len().max(1)
+        } else {
+            1
+        };
+
+        let width = (title.chars().count() as u16 + 4).max(40).min(area.width);
+        let height = (rows as u16 + 2).min(area.height);
+        let area = Rect::new(
+            area.x + (area.width - width) / 2,
+            area.y + (area.height - height) / 2,
+            width,
+            height,
+        );
+
+        let block = Block::default()
+            .title(Title::from(title).alignment(Alignment::Center))
+            .borders(Borders::ALL)
+            .border_set(border::ROUNDED)
+            .style(Style::new().bg(Color::from_u32(0x00181818)));
+        let inner = block.inner(area);
+
+        Clear.render(area, buf);
+        block.render(area, buf);
+
+        match self.prompt.kind {
+            PromptKind::AddToPlaylist { .. } if self.options.is_empty() => {
+                Paragraph::new("No playlists").render(inner, buf);
+            }
+            _ if self.prompt.has_options() => {
+                let mut state = ListState::default()
+                    .with_selected(Some(self.prompt.selected));
+                StatefulWidget::render(
+                    List::new(self.options).highlight_style(
+                        Style::new().bg(Color::from_u32(0x00464646)),
+                    ),
+                    inner,
+                    buf,
+                    &mut state,
+                );
+            }
+            PromptKind::DeletePlaylist { .. } => {}
+            _ => {
+                Paragraph::new(format!("{}▏", self.prompt.input))
+                    .render(inner, buf);
+            }
+        }
+    }
+}
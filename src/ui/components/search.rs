@@ -0,0 +1,101 @@
+use ratatui::{
+    buffer::Buffer,
+    layout::{Constraint, Direction, Layout, Rect},
+    style::{Color, Style, Stylize},
+    text::Line,
+    widgets::{
+        List, ListItem, ListState, Paragraph, StatefulWidget, Tabs, Widget,
+    },
+};
+
+use crate::{
+    api::search::SearchKind,
+    ui::{
+        search::{Search, SearchResult},
+        utils::format_track,
+    },
+};
+
+pub struct SearchWidget<'a> {
+    search: &'a Search,
+    current_track: Option<i32>,
+}
+
+impl<'a> SearchWidget<'a> {
+    pub fn new(search: &'a Search, current_track: Option<i32>) -> Self {
+        Self {
+            search,
+            current_track,
+        }
+    }
+
+    fn result_item(&self, result: &SearchResult) -> ListItem<'a> {
+        match result {
+            SearchResult::Track(track) => {
+                let line = Line::from(format_track(track));
+                if self.current_track == Some(track.id) {
+                    ListItem::new(line.fg(Color::from_u32(0x00f7d44b)))
+                } else {
+                    ListItem::new(line)
+                }
+            }
+            SearchResult::Item(item) => ListItem::new(item.title()),
+        }
+    }
+}
+
+impl<'a> Widget for SearchWidget<'a> {
+    fn render(self, area: Rect, buf: &mut Buffer) {
+        let layout = Layout::default()
+            .direction(Direction::Vertical)
+            .constraints([
+                Constraint::Length(1),
+                Constraint::Length(2),
+                Constraint::Min(1),
+            ])
+            .split(area);
+
+        Paragraph::new(format!(" 󰍉 {}▏", self.search.query))
+            .render(layout[0], buf);
+
+        Tabs::new(SearchKind::ALL.iter().map(|kind| kind.title()))
+            .select(self.search.kind)
+            .style(Style::new().fg(Color::from_u32(0x00464646)))
+            .highlight_style(Style::new().fg(Color::from_u32(0x00f7d44b)))
+            .render(layout[1], buf);
+
+        let area = layout[2];
+        if self.search.results.is_empty() {
+            let message = if self.search.is_loading {
+                " Searching..."
+            } else if self.search.query.trim().is_empty() {
+                " Type to search"
+            } else {
+                " Nothing found"
+            };
+            Paragraph::new(message).render(area, buf);
+            return;
+        }
+
+        let mut items = self
+            .search
+            .results
+            .iter()
+            .map(|result| self.result_item(result))
+            .collect::<Vec<_>>();
+        if self.search.is_loading {
+            items.push(ListItem::new("Loading more...".dim()));
+        }
+
+        let mut state =
+            ListState::default().with_selected(Some(self.search.selected));
+        StatefulWidget::render(
+            List::new(items)
+                .highlight_style(Style::new().bg(Color::from_u32(0x00464646)))
+                .highlight_symbol(" "),
+            area,
+            buf,
+            &mut state,
+        );
+    }
+}
@@ -0,0 +1,618 @@
+use std::{
+    sync::Arc,
+    time::{Duration, Instant},
+};
+
+use flume::Sender;
+use tracing::warn;
+use yandex_music::{model::track_model::track::Track, YandexMusicClient};
+
+use crate::{
+    api::{
+        landing::{
+            fetch_feed, fetch_feed_page, Feed, FeedId, FeedSource,
+            LandingEntity,
+        },
+        library::{
+            fetch_album, fetch_artist_brief_info, fetch_playlist,
+            fetch_podcast, AlbumWithTracks, ArtistBriefInfo, LibraryAlbum,
+            Podcast,
+        },
+        plays::PlayContext,
+    },
+    event::events::Event,
+};
+
+use super::{
+    library::LibraryItem,
+    utils::{format_duration, format_track},
+};
+
+/// How many albums or playlists of a feed are fetched at once.
+const FEED_PAGE_SIZE: usize = 20;
+
+/// How close to the end of a feed page the selection gets before the next
+/// one is fetched.
+const FEED_PAGE_THRESHOLD: usize = 5;
+
+/// Delay before a feed page that failed to load is requested again. It
+/// doubles with each failure in a row.
+const FEED_RETRY_DELAY: Duration = Duration::from_secs(2);
+
+/// How many times in a row a feed page may fail to load before no more
+/// pages are requested.
+const FEED_MAX_FAILURES: u32 = 4;
+
+pub enum DetailEntry {
+    Heading(String),
+    Track { label: String, track: Track },
+    Item { label: String, item: LibraryItem },
+}
+
+/// An album or artist page. Pages opened from another page are stacked on
+/// top of it.
+pub struct DetailPage {
+    pub title: String,
+    pub subtitle: String,
+    pub entries: Vec<DetailEntry>,
+    pub selected: usize,
+    /// What tracks played from the page are reported as played from.
+    pub context: PlayContext,
+    /// Whether the page lists podcast episodes or audiobook chapters.
+    pub is_long_form: bool,
+    /// Identifies the feed shown on the page, if any.
+    pub feed: Option<String>,
+    /// Entities of the feed that haven't been fetched yet.
+    pending: Vec<FeedId>,
+    /// How many times in a row the next page failed to load.
+    failures: u32,
+    /// When the page that failed to load may be requested again.
+    retry_at: Option<Instant>,
+    pub is_loading: bool,
+}
+
+impl DetailPage {
+    /// Loads the page of an album in the background and sends it as
+    /// [`Event::PageLoaded`].
+    pub fn load_album(
+        client: Arc<YandexMusicClient>,
+        event_tx: Sender<Event>,
+        album_id: i32,
+    ) {
+        tokio::spawn(async move {
+            match fetch_album(&client, album_id).await {
+                Ok(album) if album.is_long_form() => {
+                    Self::fetch_podcast(&client, &event_tx, album_id).await
+                }
+                Ok(album) => {
+                    let _ =
+                        event_tx.send(Event::PageLoaded(Self::album(album)));
+                }
+                Err(err) => {
+                    warn!("Failed to load album {}: {:#}", album_id, err)
+                }
+            }
+        });
+    }
+
+    /// Loads the episode list of a podcast or audiobook in the background
+    /// and sends it as [`Event::PageLoaded`].
+    pub fn load_podcast(
+        client: Arc<YandexMusicClient>,
+        event_tx: Sender<Event>,
+        album_id: i32,
+    ) {
+        tokio::spawn(async move {
+            Self::fetch_podcast(&client, &event_tx, album_id).await
+        });
+    }
+
+    async fn fetch_podcast(
+        client: &YandexMusicClient,
+        event_tx: &Sender<Event>,
+        album_id: i32,
+    ) {
+        match fetch_podcast(client, album_id).await {
+            Ok(podcast) => {
+                let _ =
+                    event_tx.send(Event::PageLoaded(Self::podcast(podcast)));
+            }
+            Err(err) => {
+                warn!("Failed to load podcast {}: {:#}", album_id, err)
+            }
+        }
+    }
+
+    /// Loads the page of an artist in the background and sends it as
+    /// [`Event::PageLoaded`].
+    pub fn load_artist(
+        client: Arc<YandexMusicClient>,
+        event_tx: Sender<Event>,
+        artist_id: i32,
+    ) {
+        tokio::spawn(async move {
+            match fetch_artist_brief_info(&client, artist_id).await {
+                Ok(info) => {
+                    let _ =
+                        event_tx.send(Event::PageLoaded(Self::artist(info)));
+                }
+                Err(err) => {
+                    warn!("Failed to load artist {}: {:#}", artist_id, err)
+                }
+            }
+        });
+    }
+
+    /// Loads the page of a playlist in the background and sends it as
+    /// [`Event::PageLoaded`].
+    pub fn load_playlist(
+        client: Arc<YandexMusicClient>,
+        event_tx: Sender<Event>,
+        uid: i32,
+        kind: i32,
+    ) {
+        tokio::spawn(async move {
+            match fetch_playlist(&client, uid, kind).await {
+                Ok(playlist) => {
+                    let entries = playlist
+                        .tracks
+                        .into_iter()
+                        .filter_map(|track| track.track)
+                        .map(|track| DetailEntry::Track {
+                            label: format_track(&track),
+                            track,
+                        })
+                        .collect::<Vec<_>>();
+                    let subtitle =
+                        format!("Playlist · {} tracks", entries.len());
+                    let page = Self::new(
+                        playlist.title,
+                        subtitle,
+                        entries,
+                        PlayContext::Playlist { uid, kind },
+                    );
+                    let _ = event_tx.send(Event::PageLoaded(page));
+                }
+                Err(err) => {
+                    warn!("Failed to load playlist {}: {:#}", kind, err)
+                }
+            }
+        });
+    }
+
+    /// Loads the page of a landing block or mix in the background and sends
+    /// it as [`Event::PageLoaded`]. If the feed has a full list, it replaces
+    /// `entities` and its first page is fetched.
+    pub fn load_feed(
+        client: Arc<YandexMusicClient>,
+        event_tx: Sender<Event>,
+        title: String,
+        source: Option<FeedSource>,
+        entities: Vec<LandingEntity>,
+    ) {
+        tokio::spawn(async move {
+            let feed = match &source {
+                Some(source) => fetch_feed(&client, source).await,
+                None => Ok(Feed {
+                    entities,
+                    ids: Vec::new(),
+                }),
+            };
+            let Feed { entities, mut ids } = match feed {
+                Ok(feed) => feed,
+                Err(err) => {
+                    warn!("Failed to load \"{}\": {:#}", title, err);
+                    return;
+                }
+            };
+
+            let mut pending = ids.split_off(ids.len().min(FEED_PAGE_SIZE));
+            let mut entities = entities;
+            match fetch_feed_page(&client, &ids).await {
+                Ok(page) => entities.extend(page),
+                Err(err) => {
+                    warn!("Failed to load \"{}\": {:#}", title, err);
+                    ids.append(&mut pending);
+                    pending = ids;
+                }
+            }
+
+            let mut page = Self::new(
+                title,
+                "Feed".to_string(),
+                entities.into_iter().map(feed_entry).collect(),
+                PlayContext::Other,
+            );
+            page.feed = source.as_ref().map(FeedSource::key);
+            page.pending = pending;
+            let _ = event_tx.send(Event::PageLoaded(page));
+        });
+    }
+
+    /// Fetches the next page of the feed in the background once the
+    /// selection gets close to the end. The entries are sent as
+    /// [`Event::PageExtended`], along with the ids of a page that failed to
+    /// load so that they can be requested again.
+    pub fn load_more(
+        &mut self,
+        client: Arc<YandexMusicClient>,
+        event_tx: Sender<Event>,
+    ) {
+        let Some(key) = self.feed.clone() else {
+            return;
+        };
+        if self.is_loading
+            || self.pending.is_empty()
+            || self.failures >= FEED_MAX_FAILURES
+            || self.retry_at.is_some_and(|at| Instant::now() < at)
+            || self.selected + FEED_PAGE_THRESHOLD < self.entries.len()
+        {
+            return;
+        }
+
+        self.is_loading = true;
+        let ids = self
+            .pending
+            .drain(..self.pending.len().min(FEED_PAGE_SIZE))
+            .collect::<Vec<_>>();
+        tokio::spawn(async move {
+            let (entries, failed) = match fetch_feed_page(&client, &ids).await {
+                Ok(entities) => {
+                    (entities.into_iter().map(feed_entry).collect(), Vec::new())
+                }
+                Err(err) => {
+                    warn!("Failed to load more of {}: {:#}", key, err);
+                    (Vec::new(), ids)
+                }
+            };
+            let _ = event_tx.send(Event::PageExtended {
+                key,
+                entries,
+                failed,
+            });
+        });
+    }
+
+    /// Appends a loaded page. The `failed` ids are put back in front of the
+    /// pending ones and requested again after a delay.
+    pub fn extend(&mut self, entries: Vec<DetailEntry>, failed: Vec<FeedId>) {
+        self.is_loading = false;
+        if failed.is_empty() {
+            self.failures = 0;
+            self.retry_at = None;
+        } else {
+            let delay = FEED_RETRY_DELAY * 2u32.pow(self.failures);
+            self.failures += 1;
+            self.retry_at = Some(Instant::now() + delay);
+        }
+        self.pending.splice(..0, failed);
+        self.entries.extend(entries);
+        if self.selected_entry().is_none() {
+            self.select_next();
+        }
+    }
+
+    fn album(album: AlbumWithTracks) -> Self {
+        let track_count = album.volumes.iter().map(Vec::len).sum::<usize>();
+        let length = album
+            .volumes
+            .iter()
+            .flatten()
+            .filter_map(|track| track.duration_ms)
+            .sum::<u64>();
+
+        let mut subtitle = album
+            .artists
+            .iter()
+            .filter_map(|artist| artist.name.as_deref())
+            .collect::<Vec<_>>()
+            .join(", ");
+        if let Some(year) = album.year {
+            subtitle.push_str(&format!(" · {}", year));
+        }
+        if let Some(label) = album.labels.first() {
+            subtitle.push_str(&format!(" · {}", label.name));
+        }
+        subtitle.push_str(&format!(
+            " · {} tracks · {}",
+            track_count,
+            format_duration(Duration::from_millis(length))
+        ));
+
+        let mut entries = Vec::new();
+        let has_discs = album.volumes.len() > 1;
+        for (disc, volume) in album.volumes.into_iter().enumerate() {
+            if has_discs {
+                entries
+                    .push(DetailEntry::Heading(format!("Disc {}", disc + 1)));
+            }
+            for (position, track) in volume.into_iter().enumerate() {
+                let duration = track
+                    .duration_ms
+                    .map(|ms| format_duration(Duration::from_millis(ms)))
+                    .unwrap_or_default();
+                let label = format!(
+                    "{:>2}. {}  {}",
+                    position + 1,
+                    format_track(&track),
+                    duration
+                );
+                entries.push(DetailEntry::Track { label, track });
+            }
+        }
+
+        if !album.artists.is_empty() {
+            entries.push(DetailEntry::Heading("Artists".to_string()));
+            entries.extend(album.artists.into_iter().map(|artist| {
+                let item = LibraryItem::Artist(artist);
+                DetailEntry::Item {
+                    label: item.title(),
+                    item,
+                }
+            }));
+        }
+
+        Self::new(
+            album.title.unwrap_or_else(|| "Unknown".to_string()),
+            subtitle,
+            entries,
+            PlayContext::Album { id: album.id },
+        )
+    }
+
+    fn podcast(podcast: Podcast) -> Self {
+        let entries = podcast
+            .volumes
+            .into_iter()
+            .flatten()
+            .map(|episode| {
+                let mut label = episode
+                    .track
+                    .title
+                    .clone()
+                    .unwrap_or_else(|| "Unknown".to_string());
+                // Only the date of the timestamp is shown.
+                if let Some(date) =
+                    episode.pub_date.as_deref().and_then(|d| d.get(..10))
+                {
+                    label.push_str(&format!("  {}", date));
+                }
+                if let Some(ms) = episode.track.duration_ms {
+                    label.push_str(&format!(
+                        "  {}",
+                        format_duration(Duration::from_millis(ms))
+                    ));
+                }
+                DetailEntry::Track {
+                    label,
+                    track: episode.track,
+                }
+            })
+            .collect::<Vec<_>>();
+
+        let mut subtitle = podcast
+            .artists
+            .iter()
+            .filter_map(|artist| artist.name.as_deref())
+            .collect::<Vec<_>>()
+            .join(", ");
+        if !subtitle.is_empty() {
+            subtitle.push_str(" · ");
+        }
+        subtitle.push_str(&format!("{} episodes", entries.len()));
+
+        let mut page = Self::new(
+            podcast.title.unwrap_or_else(|| "Unknown".to_string()),
+            subtitle,
+            entries,
+            PlayContext::Album { id: podcast.id },
+        );
+        page.is_long_form = true;
+        page
+    }
+
+    fn artist(info: ArtistBriefInfo) -> Self {
+        let mut entries = Vec::new();
+
+        if !info.popular_tracks.is_empty() {
+            entries.push(DetailEntry::Heading("Popular tracks".to_string()));
+            entries.extend(info.popular_tracks.into_iter().map(|track| {
+                DetailEntry::Track {
+                    label: format_track(&track),
+                    track,
+                }
+            }));
+        }
+
+        let (singles, albums): (Vec<_>, Vec<_>) =
+            info.albums.into_iter().partition(LibraryAlbum::is_single);
+        let (compilations, albums): (Vec<_>, Vec<_>) =
+            albums.into_iter().partition(LibraryAlbum::is_compilation);
+        let compilations = compilations.into_iter().chain(info.also_albums);
+        push_albums(&mut entries, "Albums", albums);
+        push_albums(&mut entries, "Singles", singles);
+        push_albums(&mut entries, "Compilations", compilations);
+
+        if !info.similar_artists.is_empty() {
+            entries.push(DetailEntry::Heading("Similar artists".to_string()));
+            entries.extend(info.similar_artists.into_iter().map(|artist| {
+                let item = LibraryItem::Artist(artist);
+                DetailEntry::Item {
+                    label: item.title(),
+                    item,
+                }
+            }));
+        }
+
+        Self::new(
+            info.artist.name.unwrap_or_else(|| "Unknown".to_string()),
+            "Artist".to_string(),
+            entries,
+            PlayContext::Artist { id: info.artist.id },
+        )
+    }
+
+    fn new(
+        title: String,
+        subtitle: String,
+        entries: Vec<DetailEntry>,
+        context: PlayContext,
+    ) -> Self {
+        let mut page = Self {
+            title,
+            subtitle,
+            entries,
+            selected: 0,
+            context,
+            is_long_form: false,
+            feed: None,
+            pending: Vec::new(),
+            failures: 0,
+            retry_at: None,
+            is_loading: false,
+        };
+        if page.selected_entry().is_none() {
+            page.select_next();
+        }
+
+        page
+    }
+
+    /// The selected entry, unless it is a heading.
+    pub fn selected_entry(&self) -> Option<&DetailEntry> {
+        self.entries
+            .get(self.selected)
+            .filter(|entry| !matches!(entry, DetailEntry::Heading(_)))
+    }
+
+    pub fn select_next(&mut self) {
+        if let Some(index) = (self.selected + 1..self.entries.len())
+            .find(|&i| !matches!(self.entries[i], DetailEntry::Heading(_)))
+        {
+            self.selected = index;
+        }
+    }
+
+    pub fn select_previous(&mut self) {
+        if let Some(index) = (0..self.selected)
+            .rev()
+            .find(|&i| !matches!(self.entries[i], DetailEntry::Heading(_)))
+        {
+            self.selected = index;
+        }
+    }
+
+    /// The tracks on the page, along with the position of the selected one
+    /// among them.
+    pub fn tracks(&self) -> (Vec<Track>, usize) {
+        let index = self.entries[..self.selected.min(self.entries.len())]
+            .iter()
+            .filter(|entry| matches!(entry, DetailEntry::Track { .. }))
+            .count();
+        let tracks = self
+            .entries
+            .iter()
+            .filter_map(|entry| match entry {
+                DetailEntry::Track { track, .. } => Some(track.clone()),
+                _ => None,
+            })
+            .collect();
+
+        (tracks, index)
+    }
+}
+
+fn push_albums(
+    entries: &mut Vec<DetailEntry>,
+    heading: &str,
+    albums: impl IntoIterator<Item = LibraryAlbum>,
+) {
+    let mut albums = albums.into_iter().peekable();
+    if albums.peek().is_none() {
+        return;
+    }
+
+    entries.push(DetailEntry::Heading(heading.to_string()));
+    entries.extend(albums.map(|album| {
+        let title =
+            album.title.clone().unwrap_or_else(|| "Unknown".to_string());
+        let label = match album.year {
+            Some(year) => format!("{} ({})", title, year),
+            None => title,
+        };
+        DetailEntry::Item {
+            label,
+            item: LibraryItem::Album(album),
+        }
+    }));
+}
+
+fn feed_entry(entity: LandingEntity) -> DetailEntry {
+    let item = match entity {
+        LandingEntity::Track { position, track } => {
+            let label = match position {
+                Some(position) => {
+                    format!("{:>3}. {}", position, format_track(&track))
+                }
+                None => format_track(&track),
+            };
+            return DetailEntry::Track { label, track };
+        }
+        LandingEntity::Album(album) => LibraryItem::Album(album),
+        LandingEntity::Playlist(playlist) => LibraryItem::Playlist(playlist),
+        LandingEntity::Mix { title, tag } => LibraryItem::Feed {
+            title,
+            source: Some(FeedSource::Tag(tag)),
+            entities: Vec::new(),
+        },
+    };
+
+    DetailEntry::Item {
+        label: item.title(),
+        item,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn feed_page() -> DetailPage {
+        let mut page = DetailPage::new(
+            "Feed".to_string(),
+            String::new(),
+            Vec::new(),
+            PlayContext::Other,
+        );
+        page.feed = Some("feed".to_string());
+        page.pending = vec![FeedId::Album(3)];
+        page.is_loading = true;
+        page
+    }
+
+    #[test]
+    fn requeues_the_ids_of_a_failed_page_with_a_delay() {
+        let mut page = feed_page();
+
+        page.extend(Vec::new(), vec![FeedId::Album(1), FeedId::Album(2)]);
+
+        assert!(!page.is_loading);
+        assert!(matches!(
+            page.pending[..],
+            [FeedId::Album(1), FeedId::Album(2), FeedId::Album(3)]
+        ));
+        assert!(page.retry_at.is_some_and(|at| at > Instant::now()));
+    }
+
+    #[test]
+    fn resets_the_delay_after_a_page_loads() {
+        let mut page = feed_page();
+        page.extend(Vec::new(), vec![FeedId::Album(1)]);
+        page.extend(Vec::new(), vec![FeedId::Album(1)]);
+        assert_eq!(page.failures, 2);
+
+        page.extend(Vec::new(), Vec::new());
+        assert_eq!(page.failures, 0);
+        assert!(page.retry_at.is_none());
+    }
+}
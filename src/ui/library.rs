@@ -0,0 +1,674 @@
+use std::{collections::HashMap, sync::Arc};
+
+use flume::Sender;
+use tracing::warn;
+use yandex_music::{model::track_model::track::Track, YandexMusicClient};
+
+use crate::{
+    api::{
+        landing::{
+            fetch_feed, fetch_feed_page, fetch_landing, Feed, FeedSource,
+            LandingEntity,
+        },
+        library::{
+            fetch_album, fetch_artist_tracks, fetch_liked_albums,
+            fetch_liked_artists, fetch_playlist, fetch_playlists, fetch_uid,
+            LibraryAlbum, LibraryArtist, Playlist,
+        },
+        playlist::{
+            create_playlist, delete_playlist, edit_playlist, rename_playlist,
+            set_playlist_visibility, PlaylistEdit,
+        },
+        plays::PlayContext,
+        rotor::{fetch_stations, Station},
+    },
+    event::events::Event,
+    offline::{
+        download::fetch_collection_tracks,
+        store::{CollectionSource, OfflineCollection},
+    },
+};
+
+/// How many albums or playlists of a feed are added when the whole feed is
+/// queued.
+const FEED_COLLECTIONS: usize = 10;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum LibrarySection {
+    Home,
+    LikedTracks,
+    Playlists,
+    Albums,
+    Artists,
+    Podcasts,
+    Stations,
+}
+
+impl LibrarySection {
+    pub const ALL: [Self; 7] = [
+        Self::Home,
+        Self::LikedTracks,
+        Self::Playlists,
+        Self::Albums,
+        Self::Artists,
+        Self::Podcasts,
+        Self::Stations,
+    ];
+
+    pub fn title(&self) -> &'static str {
+        match self {
+            Self::Home => "Home",
+            Self::LikedTracks => "Liked tracks",
+            Self::Playlists => "Playlists",
+            Self::Albums => "Albums",
+            Self::Artists => "Artists",
+            Self::Podcasts => "Podcasts & books",
+            Self::Stations => "Stations",
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub enum LibraryItem {
+    Playlist(Playlist),
+    Album(LibraryAlbum),
+    Artist(LibraryArtist),
+    Station(Station),
+    /// A landing block, or a mix of editorial playlists.
+    Feed {
+        title: String,
+        source: Option<FeedSource>,
+        entities: Vec<LandingEntity>,
+    },
+}
+
+impl LibraryItem {
+    pub fn title(&self) -> String {
+        match self {
+            Self::Playlist(playlist) if playlist.is_public() => {
+                playlist.title.clone()
+            }
+            Self::Playlist(playlist) => format!("{} 󰌾", playlist.title),
+            Self::Album(album) => {
+                let title = album.title.as_deref().unwrap_or("Unknown");
+                match album.artists.first().and_then(|a| a.name.as_deref()) {
+                    Some(artist) => format!("{} by {}", title, artist),
+                    None => title.to_string(),
+                }
+            }
+            Self::Artist(artist) => {
+                artist.name.clone().unwrap_or_else(|| "Unknown".to_string())
+            }
+            Self::Station(station) => station.name.clone(),
+            Self::Feed { title, .. } => title.clone(),
+        }
+    }
+
+    /// Whether the item opens as a page rather than as a track list, like
+    /// the episodes of a podcast or the albums of a landing block.
+    pub fn opens_as_page(&self) -> bool {
+        match self {
+            Self::Album(album) => album.is_long_form(),
+            Self::Feed { .. } => true,
+            _ => false,
+        }
+    }
+
+    /// The collection this item can be made available offline as.
+    pub fn source(&self) -> Option<CollectionSource> {
+        match self {
+            Self::Playlist(playlist) => Some(CollectionSource::Playlist {
+                uid: playlist.uid,
+                kind: playlist.kind,
+            }),
+            Self::Album(album) => {
+                Some(CollectionSource::Album { id: album.id })
+            }
+            Self::Artist(_) | Self::Station(_) | Self::Feed { .. } => None,
+        }
+    }
+}
+
+/// A change to one of the user's playlists.
+pub enum PlaylistAction {
+    Create {
+        title: String,
+    },
+    Rename {
+        uid: i32,
+        kind: i32,
+        title: String,
+    },
+    Delete {
+        uid: i32,
+        kind: i32,
+    },
+    SetVisibility {
+        uid: i32,
+        kind: i32,
+        is_public: bool,
+    },
+    Edit {
+        uid: i32,
+        kind: i32,
+        edit: PlaylistEdit,
+    },
+}
+
+impl PlaylistAction {
+    /// Applies the action and returns the playlist it changed, if it still
+    /// exists.
+    async fn apply(
+        &self,
+        client: &YandexMusicClient,
+    ) -> anyhow::Result<Option<Playlist>> {
+        Ok(match self {
+            Self::Create { title } => {
+                let uid = fetch_uid(client).await?;
+                Some(create_playlist(client, uid, title).await?)
+            }
+            Self::Rename { uid, kind, title } => {
+                Some(rename_playlist(client, *uid, *kind, title).await?)
+            }
+            Self::Delete { uid, kind } => {
+                delete_playlist(client, *uid, *kind).await?;
+                None
+            }
+            Self::SetVisibility {
+                uid,
+                kind,
+                is_public,
+            } => Some(
+                set_playlist_visibility(client, *uid, *kind, *is_public)
+                    .await?,
+            ),
+            Self::Edit { uid, kind, edit } => {
+                Some(edit_playlist(client, *uid, *kind, edit).await?)
+            }
+        })
+    }
+}
+
+/// The tracks of a playlist, album or artist opened from the library.
+pub struct OpenedCollection {
+    pub title: String,
+    pub source: Option<CollectionSource>,
+    pub tracks: Vec<Track>,
+    pub selected: usize,
+}
+
+/// State of the library browser.
+#[derive(Default)]
+pub struct Library {
+    pub section: usize,
+    pub liked: Vec<Track>,
+    pub items: HashMap<LibrarySection, Vec<LibraryItem>>,
+    pub selected: [usize; LibrarySection::ALL.len()],
+    pub opened: Option<OpenedCollection>,
+}
+
+impl Library {
+    /// Loads every section except liked tracks in the background and sends
+    /// them as [`Event::LibraryLoaded`].
+    pub fn load(client: Arc<YandexMusicClient>, event_tx: Sender<Event>) {
+        Self::load_home(client.clone(), event_tx.clone());
+        tokio::spawn(async move {
+            if let Err(err) = Self::fetch_sections(&client, &event_tx).await {
+                warn!("Failed to load the library: {:#}", err);
+            }
+        });
+    }
+
+    /// Fills the sections with the collections available offline, since
+    /// nothing else can be loaded without a connection.
+    pub fn load_offline(&mut self, collections: Vec<OfflineCollection>) {
+        for section in LibrarySection::ALL {
+            self.items.entry(section).or_default();
+        }
+
+        for collection in collections {
+            let track_count = collection.track_ids.len() as i32;
+            let (section, item) = match collection.source {
+                CollectionSource::Liked => continue,
+                CollectionSource::Playlist { uid, kind } => (
+                    LibrarySection::Playlists,
+                    LibraryItem::Playlist(Playlist {
+                        uid,
+                        kind,
+                        title: collection.title,
+                        revision: 0,
+                        track_count,
+                        visibility: None,
+                        tracks: Vec::new(),
+                    }),
+                ),
+                CollectionSource::Album { id } => (
+                    LibrarySection::Albums,
+                    LibraryItem::Album(LibraryAlbum {
+                        id,
+                        title: Some(collection.title),
+                        album_type: None,
+                        year: None,
+                        artists: Vec::new(),
+                        track_count,
+                    }),
+                ),
+            };
+            self.items.entry(section).or_default().push(item);
+        }
+    }
+
+    /// Loads the landing blocks of the home view in the background and
+    /// sends them as [`Event::LibraryLoaded`].
+    pub fn load_home(client: Arc<YandexMusicClient>, event_tx: Sender<Event>) {
+        tokio::spawn(async move {
+            let blocks = fetch_landing(&client).await.unwrap_or_else(|err| {
+                warn!("Failed to load the home view: {:#}", err);
+                Vec::new()
+            });
+            let _ = event_tx.send(Event::LibraryLoaded(
+                LibrarySection::Home,
+                blocks
+                    .into_iter()
+                    .map(|block| LibraryItem::Feed {
+                        title: block.title,
+                        source: block.source,
+                        entities: block.entities,
+                    })
+                    .collect(),
+            ));
+        });
+    }
+
+    async fn fetch_sections(
+        client: &YandexMusicClient,
+        event_tx: &Sender<Event>,
+    ) -> anyhow::Result<()> {
+        let uid = fetch_uid(client).await?;
+
+        let playlists = fetch_playlists(client, uid).await?;
+        let _ = event_tx.send(Event::LibraryLoaded(
+            LibrarySection::Playlists,
+            playlists.into_iter().map(LibraryItem::Playlist).collect(),
+        ));
+
+        let (podcasts, albums): (Vec<_>, Vec<_>) =
+            fetch_liked_albums(client, uid)
+                .await?
+                .into_iter()
+                .partition(|album| album.is_long_form());
+        let _ = event_tx.send(Event::LibraryLoaded(
+            LibrarySection::Albums,
+            albums.into_iter().map(LibraryItem::Album).collect(),
+        ));
+        let _ = event_tx.send(Event::LibraryLoaded(
+            LibrarySection::Podcasts,
+            podcasts.into_iter().map(LibraryItem::Album).collect(),
+        ));
+
+        let artists = fetch_liked_artists(client, uid).await?;
+        let _ = event_tx.send(Event::LibraryLoaded(
+            LibrarySection::Artists,
+            artists.into_iter().map(LibraryItem::Artist).collect(),
+        ));
+
+        // My Wave is always available, even if the other stations fail to
+        // load.
+        let stations = fetch_stations(client).await.unwrap_or_else(|err| {
+            warn!("Failed to load radio stations: {:#}", err);
+            Vec::new()
+        });
+        let _ = event_tx.send(Event::LibraryLoaded(
+            LibrarySection::Stations,
+            std::iter::once(Station::my_wave())
+                .chain(stations)
+                .map(LibraryItem::Station)
+                .collect(),
+        ));
+
+        Ok(())
+    }
+
+    /// Fetches the tracks of `item` in the background and sends them as
+    /// [`Event::CollectionLoaded`].
+    pub fn load_item(
+        client: Arc<YandexMusicClient>,
+        event_tx: Sender<Event>,
+        item: LibraryItem,
+        append: bool,
+    ) {
+        tokio::spawn(async move {
+            let source = item.source();
+            let result = match (&item, &source) {
+                (LibraryItem::Artist(artist), _) => {
+                    fetch_artist_tracks(&client, artist.id).await
+                }
+                (
+                    LibraryItem::Feed {
+                        source, entities, ..
+                    },
+                    _,
+                ) => fetch_feed_tracks(&client, source, entities).await,
+                (_, Some(source)) => match fetch_uid(&client).await {
+                    Ok(uid) => {
+                        fetch_collection_tracks(&client, uid, source).await
+                    }
+                    Err(err) => Err(err),
+                },
+                (_, None) => Ok(Vec::new()),
+            };
+
+            match result {
+                Ok(tracks) => {
+                    let _ = event_tx.send(Event::CollectionLoaded {
+                        title: item.title(),
+                        source,
+                        tracks,
+                        append,
+                    });
+                }
+                Err(err) => {
+                    warn!("Failed to load \"{}\": {:#}", item.title(), err)
+                }
+            }
+        });
+    }
+
+    /// Applies `action` in the background, then sends the changed playlist
+    /// as [`Event::PlaylistUpdated`] and reloads the list of playlists.
+    ///
+    /// If an edit fails, the playlist is still sent as it is on the server,
+    /// so that changes shown in advance are reverted.
+    pub fn update_playlist(
+        client: Arc<YandexMusicClient>,
+        event_tx: Sender<Event>,
+        action: PlaylistAction,
+    ) {
+        tokio::spawn(async move {
+            let playlist = match action.apply(&client).await {
+                Ok(playlist) => playlist,
+                Err(err) => {
+                    warn!("Failed to update playlist: {:#}", err);
+                    match &action {
+                        PlaylistAction::Edit { uid, kind, .. } => {
+                            fetch_playlist(&client, *uid, *kind).await.ok()
+                        }
+                        _ => None,
+                    }
+                }
+            };
+            if let Some(playlist) = playlist {
+                let _ = event_tx.send(Event::PlaylistUpdated(playlist));
+            }
+
+            let playlists = match fetch_uid(&client).await {
+                Ok(uid) => fetch_playlists(&client, uid).await,
+                Err(err) => Err(err),
+            };
+            match playlists {
+                Ok(playlists) => {
+                    let _ = event_tx.send(Event::LibraryLoaded(
+                        LibrarySection::Playlists,
+                        playlists
+                            .into_iter()
+                            .map(LibraryItem::Playlist)
+                            .collect(),
+                    ));
+                }
+                Err(err) => warn!("Failed to reload playlists: {:#}", err),
+            }
+        });
+    }
+
+    /// Shows the latest state of a playlist if it is opened.
+    pub fn on_playlist_updated(&mut self, playlist: Playlist) {
+        let source = CollectionSource::Playlist {
+            uid: playlist.uid,
+            kind: playlist.kind,
+        };
+        let Some(opened) = self
+            .opened
+            .as_mut()
+            .filter(|opened| opened.source.as_ref() == Some(&source))
+        else {
+            return;
+        };
+
+        opened.title = playlist.title;
+        opened.tracks = playlist
+            .tracks
+            .into_iter()
+            .filter_map(|track| track.track)
+            .collect();
+        opened.selected =
+            opened.selected.min(opened.tracks.len().saturating_sub(1));
+    }
+
+    /// The user's own playlists.
+    pub fn playlists(&self) -> Vec<&Playlist> {
+        self.items
+            .get(&LibrarySection::Playlists)
+            .into_iter()
+            .flatten()
+            .filter_map(|item| match item {
+                LibraryItem::Playlist(playlist) => Some(playlist),
+                _ => None,
+            })
+            .collect()
+    }
+
+    /// The user's playlist that is opened, or selected in the list of
+    /// playlists. Playlists of other users, which can be opened from search
+    /// or the home view, are never returned, since they can't be edited.
+    pub fn selected_playlist(&self) -> Option<&Playlist> {
+        let (uid, kind) = match self.opened.as_ref() {
+            Some(opened) => match opened.source {
+                Some(CollectionSource::Playlist { uid, kind }) => (uid, kind),
+                _ => return None,
+            },
+            None => match self.selected_item() {
+                Some(LibraryItem::Playlist(playlist)) => return Some(playlist),
+                _ => return None,
+            },
+        };
+
+        self.playlists()
+            .into_iter()
+            .find(|playlist| playlist.uid == uid && playlist.kind == kind)
+    }
+
+    /// Moves the selected track of the opened collection by `offset` and
+    /// returns its old and new position.
+    pub fn move_selected(&mut self, offset: isize) -> Option<(usize, usize)> {
+        let opened = self.opened.as_mut()?;
+        let from = opened.selected;
+        let to = from.checked_add_signed(offset)?;
+        if to >= opened.tracks.len() {
+            return None;
+        }
+
+        let track = opened.tracks.remove(from);
+        opened.tracks.insert(to, track);
+        opened.selected = to;
+
+        Some((from, to))
+    }
+
+    /// Removes the selected track from the opened collection.
+    pub fn remove_selected(&mut self) -> Option<(usize, Track)> {
+        let opened = self.opened.as_mut()?;
+        if opened.selected >= opened.tracks.len() {
+            return None;
+        }
+
+        let index = opened.selected;
+        let track = opened.tracks.remove(index);
+        opened.selected =
+            opened.selected.min(opened.tracks.len().saturating_sub(1));
+
+        Some((index, track))
+    }
+
+    pub fn current_section(&self) -> LibrarySection {
+        LibrarySection::ALL[self.section]
+    }
+
+    pub fn next_section(&mut self) {
+        self.opened = None;
+        self.section = (self.section + 1) % LibrarySection::ALL.len();
+    }
+
+    pub fn previous_section(&mut self) {
+        self.opened = None;
+        self.section = (self.section + LibrarySection::ALL.len() - 1)
+            % LibrarySection::ALL.len();
+    }
+
+    pub fn select_next(&mut self) {
+        let len = self.len();
+        let selected = self.selected_mut();
+        if *selected + 1 < len {
+            *selected += 1;
+        }
+    }
+
+    pub fn select_previous(&mut self) {
+        let selected = self.selected_mut();
+        *selected = selected.saturating_sub(1);
+    }
+
+    pub fn selected(&self) -> usize {
+        match &self.opened {
+            Some(opened) => opened.selected,
+            None => self.selected[self.section],
+        }
+    }
+
+    pub fn selected_item(&self) -> Option<&LibraryItem> {
+        self.items
+            .get(&self.current_section())?
+            .get(self.selected[self.section])
+    }
+
+    /// The track list that is shown, if the current view lists tracks.
+    pub fn tracks(&self) -> Option<&[Track]> {
+        match &self.opened {
+            Some(opened) => Some(&opened.tracks),
+            None if self.current_section() == LibrarySection::LikedTracks => {
+                Some(&self.liked)
+            }
+            None => None,
+        }
+    }
+
+    /// What the shown track list was opened from.
+    pub fn context(&self) -> PlayContext {
+        let Some(opened) = &self.opened else {
+            return PlayContext::Liked;
+        };
+
+        match opened.source {
+            Some(CollectionSource::Liked) => PlayContext::Liked,
+            Some(CollectionSource::Playlist { uid, kind }) => {
+                PlayContext::Playlist { uid, kind }
+            }
+            Some(CollectionSource::Album { id }) => PlayContext::Album { id },
+            None => PlayContext::Other,
+        }
+    }
+
+    pub fn open(
+        &mut self,
+        title: String,
+        source: Option<CollectionSource>,
+        tracks: Vec<Track>,
+    ) {
+        self.opened = Some(OpenedCollection {
+            title,
+            source,
+            tracks,
+            selected: 0,
+        });
+    }
+
+    pub fn close(&mut self) {
+        self.opened = None;
+    }
+
+    fn len(&self) -> usize {
+        match self.tracks() {
+            Some(tracks) => tracks.len(),
+            None => self
+                .items
+                .get(&self.current_section())
+                .map_or(0, |items| items.len()),
+        }
+    }
+
+    fn selected_mut(&mut self) -> &mut usize {
+        match &mut self.opened {
+            Some(opened) => &mut opened.selected,
+            None => &mut self.selected[self.section],
+        }
+    }
+}
+
+/// Fetches the tracks of the entities at the top of a feed, in order. At
+/// most [`FEED_COLLECTIONS`] albums or playlists are fetched from the full
+/// list, and mixes are left out since they only link to more playlists.
+async fn fetch_feed_tracks(
+    client: &YandexMusicClient,
+    source: &Option<FeedSource>,
+    entities: &[LandingEntity],
+) -> anyhow::Result<Vec<Track>> {
+    let entities = match source {
+        Some(source) => {
+            let Feed { mut entities, ids } = fetch_feed(client, source).await?;
+            let ids = &ids[..ids.len().min(FEED_COLLECTIONS)];
+            match fetch_feed_page(client, ids).await {
+                Ok(page) => entities.extend(page),
+                Err(err) => warn!("Failed to load feed entities: {:#}", err),
+            }
+            entities
+        }
+        None => entities.to_vec(),
+    };
+
+    // An album or playlist that fails to load is skipped, so that the rest
+    // of the block can still be played.
+    let mut tracks = Vec::new();
+    for entity in entities {
+        match entity {
+            LandingEntity::Track { track, .. } => tracks.push(track),
+            LandingEntity::Album(album) => {
+                match fetch_album(client, album.id).await {
+                    Ok(album) => {
+                        tracks.extend(album.volumes.into_iter().flatten())
+                    }
+                    Err(err) => {
+                        warn!("Failed to load album {}: {:#}", album.id, err)
+                    }
+                }
+            }
+            LandingEntity::Playlist(playlist) => {
+                match fetch_playlist(client, playlist.uid, playlist.kind).await
+                {
+                    Ok(playlist) => tracks.extend(
+                        playlist
+                            .tracks
+                            .into_iter()
+                            .filter_map(|track| track.track),
+                    ),
+                    Err(err) => warn!(
+                        "Failed to load playlist {}:{}: {:#}",
+                        playlist.uid, playlist.kind, err
+                    ),
+                }
+            }
+            LandingEntity::Mix { .. } => {}
+        }
+    }
+    anyhow::ensure!(!tracks.is_empty(), "None of the entities could be loaded");
+
+    Ok(tracks)
+}
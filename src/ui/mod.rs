@@ -1,6 +1,10 @@
 pub mod app;
 pub mod components;
+pub mod details;
 pub mod hook;
+pub mod library;
 pub mod log;
+pub mod prompt;
+pub mod search;
 pub mod tui;
 pub mod utils;
@@ -0,0 +1,61 @@
+use yandex_music::model::track_model::track::Track;
+
+pub enum PromptKind {
+    CreatePlaylist,
+    RenamePlaylist { uid: i32, kind: i32 },
+    DeletePlaylist { uid: i32, kind: i32, title: String },
+    AddToPlaylist { tracks: Vec<Track> },
+    SwitchProfile,
+}
+
+/// A dialog shown on top of the main view, either for text input or to
+/// pick one of a list of options, such as a playlist.
+pub struct Prompt {
+    pub kind: PromptKind,
+    pub input: String,
+    pub selected: usize,
+}
+
+impl Prompt {
+    pub fn new(kind: PromptKind, input: String) -> Self {
+        Self {
+            kind,
+            input,
+            selected: 0,
+        }
+    }
+
+    pub fn title(&self) -> String {
+        match &self.kind {
+            PromptKind::CreatePlaylist => "New playlist".to_string(),
+            PromptKind::RenamePlaylist { .. } => "Rename playlist".to_string(),
+            PromptKind::DeletePlaylist { title, .. } => {
+                format!("Delete \"{}\"? Press Enter to confirm", title)
+            }
+            PromptKind::AddToPlaylist { tracks } => match tracks.as_slice() {
+                [track] => format!(
+                    "Add \"{}\" to",
+                    track.title.as_deref().unwrap_or("Unknown")
+                ),
+                tracks => format!("Add {} tracks to", tracks.len()),
+            },
+            PromptKind::SwitchProfile => "Switch profile".to_string(),
+        }
+    }
+
+    /// Whether an option is picked from a list.
+    pub fn has_options(&self) -> bool {
+        matches!(
+            self.kind,
+            PromptKind::AddToPlaylist { .. } | PromptKind::SwitchProfile
+        )
+    }
+
+    /// Whether typed characters go into `input`.
+    pub fn has_input(&self) -> bool {
+        matches!(
+            self.kind,
+            PromptKind::CreatePlaylist | PromptKind::RenamePlaylist { .. }
+        )
+    }
+}
@@ -0,0 +1,238 @@
+use std::{sync::Arc, time::Duration};
+
+use flume::Sender;
+use tokio_util::sync::CancellationToken;
+use tracing::warn;
+use yandex_music::{model::track_model::track::Track, YandexMusicClient};
+
+use crate::{
+    api::search::{search, SearchKind, SearchResponse},
+    event::events::Event,
+};
+
+use super::library::LibraryItem;
+
+/// How long typing has to pause before a search request is sent.
+const DEBOUNCE: Duration = Duration::from_millis(300);
+
+#[derive(Clone, Debug)]
+pub enum SearchResult {
+    Track(Track),
+    Item(LibraryItem),
+}
+
+impl SearchResult {
+    /// Collects the results of a response, along with the total number of
+    /// results across all pages.
+    fn from_response(response: SearchResponse) -> (usize, Vec<Self>) {
+        if let Some(tracks) = response.tracks {
+            let results = tracks.results.into_iter().map(Self::Track);
+            return (tracks.total, results.collect());
+        }
+
+        let (total, items): (usize, Vec<LibraryItem>) = if let Some(albums) =
+            response.albums.or(response.podcasts)
+        {
+            let items = albums.results.into_iter().map(LibraryItem::Album);
+            (albums.total, items.collect())
+        } else if let Some(artists) = response.artists {
+            let items = artists.results.into_iter().map(LibraryItem::Artist);
+            (artists.total, items.collect())
+        } else if let Some(playlists) = response.playlists {
+            let items =
+                playlists.results.into_iter().map(LibraryItem::Playlist);
+            (playlists.total, items.collect())
+        } else {
+            (0, Vec::new())
+        };
+
+        (total, items.into_iter().map(Self::Item).collect())
+    }
+}
+
+/// State of the search view.
+#[derive(Default)]
+pub struct Search {
+    pub query: String,
+    pub kind: usize,
+    pub results: Vec<SearchResult>,
+    pub total: usize,
+    pub selected: usize,
+    pub is_loading: bool,
+    page: usize,
+    request: Option<CancellationToken>,
+}
+
+impl Search {
+    pub fn current_kind(&self) -> SearchKind {
+        SearchKind::ALL[self.kind]
+    }
+
+    pub fn push(
+        &mut self,
+        c: char,
+        client: Arc<YandexMusicClient>,
+        event_tx: Sender<Event>,
+    ) {
+        self.query.push(c);
+        self.restart(client, event_tx, DEBOUNCE);
+    }
+
+    pub fn pop(
+        &mut self,
+        client: Arc<YandexMusicClient>,
+        event_tx: Sender<Event>,
+    ) {
+        self.query.pop();
+        self.restart(client, event_tx, DEBOUNCE);
+    }
+
+    pub fn next_kind(
+        &mut self,
+        client: Arc<YandexMusicClient>,
+        event_tx: Sender<Event>,
+    ) {
+        self.kind = (self.kind + 1) % SearchKind::ALL.len();
+        self.restart(client, event_tx, Duration::ZERO);
+    }
+
+    pub fn previous_kind(
+        &mut self,
+        client: Arc<YandexMusicClient>,
+        event_tx: Sender<Event>,
+    ) {
+        self.kind =
+            (self.kind + SearchKind::ALL.len() - 1) % SearchKind::ALL.len();
+        self.restart(client, event_tx, Duration::ZERO);
+    }
+
+    pub fn select_previous(&mut self) {
+        self.selected = self.selected.saturating_sub(1);
+    }
+
+    /// Moves the selection down, loading the next page of results once the
+    /// last one is reached.
+    pub fn select_next(
+        &mut self,
+        client: Arc<YandexMusicClient>,
+        event_tx: Sender<Event>,
+    ) {
+        if self.selected + 1 < self.results.len() {
+            self.selected += 1;
+        } else if !self.is_loading && self.results.len() < self.total {
+            self.page += 1;
+            self.fetch(client, event_tx, Duration::ZERO);
+        }
+    }
+
+    pub fn selected_result(&self) -> Option<&SearchResult> {
+        self.results.get(self.selected)
+    }
+
+    /// The tracks among the results, along with the position of the selected
+    /// one among them.
+    pub fn tracks(&self) -> (Vec<Track>, usize) {
+        let index = self.results[..self.selected.min(self.results.len())]
+            .iter()
+            .filter(|result| matches!(result, SearchResult::Track(_)))
+            .count();
+        let tracks = self
+            .results
+            .iter()
+            .filter_map(|result| match result {
+                SearchResult::Track(track) => Some(track.clone()),
+                SearchResult::Item(_) => None,
+            })
+            .collect();
+
+        (tracks, index)
+    }
+
+    /// Adds a page of results, unless it belongs to an outdated request.
+    pub fn on_loaded(
+        &mut self,
+        query: String,
+        kind: SearchKind,
+        page: usize,
+        total: usize,
+        results: Vec<SearchResult>,
+    ) {
+        if query != self.query
+            || kind != self.current_kind()
+            || page != self.page
+        {
+            return;
+        }
+
+        self.is_loading = false;
+        self.total = total;
+        self.results.extend(results);
+    }
+
+    pub fn cancel(&mut self) {
+        if let Some(request) = self.request.take() {
+            request.cancel();
+        }
+    }
+
+    fn restart(
+        &mut self,
+        client: Arc<YandexMusicClient>,
+        event_tx: Sender<Event>,
+        delay: Duration,
+    ) {
+        self.results.clear();
+        self.total = 0;
+        self.selected = 0;
+        self.page = 0;
+        self.fetch(client, event_tx, delay);
+    }
+
+    fn fetch(
+        &mut self,
+        client: Arc<YandexMusicClient>,
+        event_tx: Sender<Event>,
+        delay: Duration,
+    ) {
+        self.cancel();
+        self.is_loading = !self.query.trim().is_empty();
+        if !self.is_loading {
+            return;
+        }
+
+        let cancel = CancellationToken::new();
+        self.request = Some(cancel.clone());
+
+        let query = self.query.clone();
+        let kind = self.current_kind();
+        let page = self.page;
+        tokio::spawn(async move {
+            tokio::select! {
+                _ = cancel.cancelled() => return,
+                _ = tokio::time::sleep(delay) => {}
+            }
+
+            let result = tokio::select! {
+                _ = cancel.cancelled() => return,
+                result = search(&client, &query, kind, page) => result,
+            };
+
+            // A failed request is reported as the last page, so that the
+            // view stops waiting for it.
+            let (total, results) = match result {
+                Ok(response) => SearchResult::from_response(response),
+                Err(err) => {
+                    warn!("Failed to search for \"{}\": {:#}", query, err);
+                    (0, Vec::new())
+                }
+            };
+            let _ = event_tx.send(Event::SearchLoaded {
+                query,
+                kind,
+                page,
+                total,
+                results,
+            });
+        });
+    }
+}
@@ -1,3 +1,7 @@
+use std::time::Duration;
+
+use yandex_music::model::track_model::track::Track;
+
 #[macro_export]
 macro_rules! keymap {
     ($evt:expr, $($key:pat_param $(| $($mods:ident)-+)? => $action:expr),+ $(,)?) => {
@@ -17,3 +21,63 @@ macro_rules! keymap {
         $evt.modifiers == ($(event::KeyModifiers::$mods)|+)
     };
 }
+
+pub fn format_bytes(bytes: u64) -> String {
+    const UNITS: [&str; 4] = ["B", "KB", "MB", "GB"];
+
+    let mut value = bytes as f64;
+    let mut unit = 0;
+    while value >= 1024.0 && unit < UNITS.len() - 1 {
+        value /= 1024.0;
+        unit += 1;
+    }
+
+    if unit == 0 {
+        format!("{} {}", bytes, UNITS[unit])
+    } else {
+        format!("{:.1} {}", value, UNITS[unit])
+    }
+}
+
+/// Formats a duration as `mm:ss`, or as `h:mm:ss` from an hour on.
+pub fn format_duration(duration: Duration) -> String {
+    let total_seconds = duration.as_secs();
+    let hours = total_seconds / 3600;
+    let minutes = total_seconds % 3600 / 60;
+    let seconds = total_seconds % 60;
+    if hours > 0 {
+        format!("{}:{:02}:{:02}", hours, minutes, seconds)
+    } else {
+        format!("{:02}:{:02}", minutes, seconds)
+    }
+}
+
+/// Formats a track as its title followed by its artists, for track lists.
+pub fn format_track(track: &Track) -> String {
+    let artists = track
+        .artists
+        .iter()
+        .map(|a| a.name.as_deref().unwrap_or("Unknown"))
+        .collect::<Vec<&str>>()
+        .join(", ");
+
+    format!(
+        "{}  {}",
+        track.title.as_deref().unwrap_or("Unknown"),
+        artists
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn formats_durations_with_hours_only_when_needed() {
+        assert_eq!(format_duration(Duration::ZERO), "00:00");
+        assert_eq!(format_duration(Duration::from_millis(59_999)), "00:59");
+        assert_eq!(format_duration(Duration::from_secs(3599)), "59:59");
+        assert_eq!(format_duration(Duration::from_secs(3600)), "1:00:00");
+        assert_eq!(format_duration(Duration::from_secs(37_230)), "10:20:30");
+    }
+}
@@ -1,6 +1,12 @@
 use std::time::{SystemTime, UNIX_EPOCH};
 
+/// A pseudo-random number from `min` up to, but excluding, `max`. Returns
+/// `min` if the range is empty.
 pub fn random(min: i32, max: i32) -> i32 {
+    if max <= min {
+        return min;
+    }
+
     let current_time = SystemTime::now()
         .duration_since(UNIX_EPOCH)
         .expect("Time went backwards")
@@ -14,3 +20,74 @@ pub fn random(min: i32, max: i32) -> i32 {
 
     rng as i32
 }
+
+/// Formats `time` as an ISO 8601 UTC timestamp with milliseconds, e.g.
+/// `2024-06-01T12:30:00.000Z`.
+pub fn format_timestamp(time: SystemTime) -> String {
+    let since_epoch = time.duration_since(UNIX_EPOCH).unwrap_or_default();
+    let seconds = since_epoch.as_secs();
+    let (days, time_of_day) = (seconds / 86400, seconds % 86400);
+
+    // Converts days since the epoch to a civil date, see
+    // http://howardhinnant.github.io/date_algorithms.html#civil_from_days
+    let z = days as i64 + 719468;
+    let era = z.div_euclid(146097);
+    let day_of_era = z.rem_euclid(146097);
+    let year_of_era = (day_of_era - day_of_era / 1460 + day_of_era / 36524
+        - day_of_era / 146096)
+        / 365;
+    let day_of_year =
+        day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let mp = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = year_of_era + era * 400 + i64::from(month <= 2);
+
+    format!(
+        "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}.{:03}Z",
+        year,
+        month,
+        day,
+        time_of_day / 3600,
+        time_of_day % 3600 / 60,
+        time_of_day % 60,
+        since_epoch.subsec_millis()
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::*;
+
+    #[test]
+    fn picks_random_numbers_within_the_range() {
+        assert_eq!(random(0, 0), 0);
+        assert_eq!(random(3, 3), 3);
+        assert_eq!(random(0, 1), 0);
+        for _ in 0..100 {
+            assert!((5..10).contains(&random(5, 10)));
+        }
+    }
+
+    #[test]
+    fn formats_timestamps_in_utc() {
+        assert_eq!(format_timestamp(UNIX_EPOCH), "1970-01-01T00:00:00.000Z");
+        assert_eq!(
+            format_timestamp(
+                UNIX_EPOCH + Duration::from_millis(1_717_245_000_123)
+            ),
+            "2024-06-01T12:30:00.123Z"
+        );
+        // A leap day and the end of a year.
+        assert_eq!(
+            format_timestamp(UNIX_EPOCH + Duration::from_secs(951_782_400)),
+            "2000-02-29T00:00:00.000Z"
+        );
+        assert_eq!(
+            format_timestamp(UNIX_EPOCH + Duration::from_secs(1_704_067_199)),
+            "2023-12-31T23:59:59.000Z"
+        );
+    }
+}
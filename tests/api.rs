@@ -0,0 +1,346 @@
+//! Runs API requests and encrypted streaming against a local stand-in
+//! server, which is set as `YATUI_API_URL` before the configuration is read.
+
+use std::{
+    collections::HashMap,
+    io::{BufRead, BufReader, Read, Write},
+    net::{TcpListener, TcpStream},
+    sync::{Arc, Mutex, OnceLock},
+    thread,
+};
+
+use base64::{engine::general_purpose::STANDARD, Engine};
+use hmac::{Hmac, Mac};
+use sha2::Sha256;
+use yandex_music::YandexMusicClient;
+use yatui::{
+    api::{
+        playlist::{edit_playlist, PlaylistEdit},
+        track::fetch_file_info,
+    },
+    lyrics::LyricsCache,
+    stream::{
+        decrypt::{DecryptingSource, TrackKey},
+        source::{ByteSource, HttpSource, TrackLink},
+    },
+};
+
+const FIRST_KEY: &str = "000102030405060708090a0b0c0d0e0f";
+const SECOND_KEY: &str = "f0e0d0c0b0a090807060504030201000";
+const SIGN_KEY: &[u8] = b"p93jhgh689SBReK6ghtw62";
+
+struct StandIn {
+    url: String,
+    /// Query parameters of every `get-file-info` request.
+    requests: Mutex<Vec<HashMap<String, String>>>,
+    /// Revisions sent with every change to playlist 1.
+    playlist_changes: Mutex<Vec<String>>,
+}
+
+fn plain() -> Vec<u8> {
+    (0..=255).cycle().take(10_000).collect()
+}
+
+fn encrypted(key: &str) -> Vec<u8> {
+    let mut bytes = plain();
+    TrackKey::from_hex(key).unwrap().decrypt(0, &mut bytes);
+    bytes
+}
+
+/// Starts the stand-in server once for all tests. Track 1 is served
+/// encrypted with the first key, and track 2 with the second one. Links to
+/// `/expired` are rejected as expired. The first change to playlist 1 of
+/// user 5 fails as if the playlist was changed elsewhere.
+fn stand_in() -> &'static StandIn {
+    static STAND_IN: OnceLock<Arc<StandIn>> = OnceLock::new();
+    STAND_IN.get_or_init(|| {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        std::env::set_var("YATUI_API_URL", &url);
+
+        let stand_in = Arc::new(StandIn {
+            url,
+            requests: Mutex::new(Vec::new()),
+            playlist_changes: Mutex::new(Vec::new()),
+        });
+        let server = stand_in.clone();
+        thread::spawn(move || {
+            for stream in listener.incoming().flatten() {
+                let server = server.clone();
+                thread::spawn(move || server.respond(stream));
+            }
+        });
+
+        stand_in
+    })
+}
+
+impl StandIn {
+    fn respond(&self, mut stream: TcpStream) {
+        let mut reader = BufReader::new(stream.try_clone().unwrap());
+        let mut request_line = String::new();
+        reader.read_line(&mut request_line).unwrap();
+        let mut range = None;
+        let mut content_length = 0;
+        loop {
+            let mut line = String::new();
+            reader.read_line(&mut line).unwrap();
+            if line.trim().is_empty() {
+                break;
+            }
+            if let Some((name, value)) = line.split_once(':') {
+                if name.eq_ignore_ascii_case("content-length") {
+                    content_length = value.trim().parse().unwrap_or(0);
+                }
+                if name.eq_ignore_ascii_case("range") {
+                    range = value.trim().strip_prefix("bytes=").and_then(|r| {
+                        let (start, end) = r.split_once('-')?;
+                        Some((start.parse().ok()?, end.parse().ok()?))
+                    });
+                }
+            }
+        }
+
+        let mut content = vec![0; content_length];
+        reader.read_exact(&mut content).unwrap();
+
+        let mut parts = request_line.split_whitespace();
+        let method = parts.next().unwrap_or_default();
+        let target = parts.next().unwrap_or_default();
+        let (path, query) = target.split_once('?').unwrap_or((target, ""));
+
+        let (status, headers, body) = match path {
+            "/get-file-info" => {
+                let query = parse_query(query);
+                let (key, file) = match query.get("trackId").map(String::as_str)
+                {
+                    Some("1") => (FIRST_KEY, "1"),
+                    _ => (SECOND_KEY, "2"),
+                };
+                self.requests.lock().unwrap().push(query);
+                let body = serde_json::json!({
+                    "result": {
+                        "downloadInfo": {
+                            "quality": "lossless",
+                            "codec": "flac",
+                            "bitrate": 0,
+                            "transport": "encraw",
+                            "key": key,
+                            "url": format!("{}/files/{}", self.url, file),
+                        }
+                    }
+                });
+                ("200 OK", String::new(), body.to_string().into_bytes())
+            }
+            "/files/1" | "/files/2" => {
+                let file = if path == "/files/1" {
+                    encrypted(FIRST_KEY)
+                } else {
+                    encrypted(SECOND_KEY)
+                };
+                let total = file.len();
+                match range {
+                    Some((start, end)) => {
+                        let end = usize::min(end, total - 1);
+                        (
+                            "206 Partial Content",
+                            format!(
+                                "Content-Range: bytes {}-{}/{}\r\n",
+                                start, end, total
+                            ),
+                            file[start..=end].to_vec(),
+                        )
+                    }
+                    None => ("200 OK", String::new(), file),
+                }
+            }
+            "/users/5/playlists/1" => {
+                let revision = self.playlist_changes.lock().unwrap().len() + 1;
+                let body = serde_json::json!({
+                    "result": {
+                        "uid": 5,
+                        "kind": 1,
+                        "title": "Playlist",
+                        "revision": revision,
+                        "tracks": [],
+                    }
+                });
+                ("200 OK", String::new(), body.to_string().into_bytes())
+            }
+            "/users/5/playlists/1/change-relative" => {
+                let form = parse_query(std::str::from_utf8(&content).unwrap());
+                let mut changes = self.playlist_changes.lock().unwrap();
+                changes.push(form["revision"].clone());
+                if changes.len() == 1 {
+                    ("412 Precondition Failed", String::new(), Vec::new())
+                } else {
+                    let body = serde_json::json!({
+                        "result": {
+                            "uid": 5,
+                            "kind": 1,
+                            "title": "Playlist",
+                            "revision": changes.len() + 1,
+                        }
+                    });
+                    ("200 OK", String::new(), body.to_string().into_bytes())
+                }
+            }
+            "/expired" => ("410 Gone", String::new(), Vec::new()),
+            // Track 3 has no lyrics, and the lyrics of track 4 can't be
+            // loaded.
+            "/tracks/3/lyrics" => ("404 Not Found", String::new(), Vec::new()),
+            "/tracks/4/lyrics" => {
+                ("500 Internal Server Error", String::new(), Vec::new())
+            }
+            _ => ("404 Not Found", String::new(), Vec::new()),
+        };
+
+        let head = format!(
+            "HTTP/1.1 {}\r\n{}Content-Length: {}\r\nConnection: close\r\n\r\n",
+            status,
+            headers,
+            body.len()
+        );
+        let _ = stream.write_all(head.as_bytes());
+        if method != "HEAD" {
+            let _ = stream.write_all(&body);
+        }
+    }
+}
+
+fn parse_query(query: &str) -> HashMap<String, String> {
+    query
+        .split('&')
+        .filter_map(|pair| pair.split_once('='))
+        .map(|(name, value)| (name.to_string(), percent_decode(value)))
+        .collect()
+}
+
+fn percent_decode(value: &str) -> String {
+    let bytes = value.as_bytes();
+    let mut decoded = Vec::new();
+    let mut i = 0;
+    while i < bytes.len() {
+        match bytes[i] {
+            b'%' if i + 2 < bytes.len() => {
+                let hex = std::str::from_utf8(&bytes[i + 1..i + 3]).unwrap();
+                decoded.push(u8::from_str_radix(hex, 16).unwrap());
+                i += 3;
+            }
+            b'+' => {
+                decoded.push(b' ');
+                i += 1;
+            }
+            byte => {
+                decoded.push(byte);
+                i += 1;
+            }
+        }
+    }
+
+    String::from_utf8(decoded).unwrap()
+}
+
+fn client() -> Arc<YandexMusicClient> {
+    Arc::new(YandexMusicClient::new("token"))
+}
+
+#[tokio::test]
+async fn fetches_signed_file_info() {
+    let stand_in = stand_in();
+
+    let info = fetch_file_info(&client(), 1, "lossless").await.unwrap();
+    assert_eq!(info.codec, "flac");
+    assert_eq!(info.transport, "encraw");
+    assert_eq!(info.key.as_deref(), Some(FIRST_KEY));
+    assert_eq!(info.url, format!("{}/files/1", stand_in.url));
+
+    let requests = stand_in.requests.lock().unwrap();
+    let query = requests
+        .iter()
+        .find(|query| query.get("trackId").map(String::as_str) == Some("1"))
+        .unwrap();
+    assert_eq!(query["quality"], "lossless");
+    assert_eq!(query["transports"], "encraw");
+
+    let codecs = query["codecs"].split(',').collect::<String>();
+    let mut mac = Hmac::<Sha256>::new_from_slice(SIGN_KEY).unwrap();
+    mac.update(format!("{}1lossless{}encraw", query["ts"], codecs).as_bytes());
+    let sign = STANDARD.encode(mac.finalize().into_bytes());
+    assert_eq!(query["sign"], sign.trim_end_matches('='));
+}
+
+#[tokio::test]
+async fn streams_encrypted_files() {
+    stand_in();
+    let info = fetch_file_info(&client(), 1, "lossless").await.unwrap();
+    let key = TrackKey::from_hex(info.key.as_deref().unwrap()).unwrap();
+
+    let source = HttpSource::new(reqwest::Client::new(), info.url, None)
+        .await
+        .unwrap();
+    let mut source = DecryptingSource::new(source, Some(key));
+
+    let plain = plain();
+    assert_eq!(source.total_bytes(), Some(plain.len() as u64));
+    assert_eq!(source.read_range(0, 4096).await.unwrap(), plain[..4096]);
+    assert_eq!(source.read_range(7000, 4096).await.unwrap(), plain[7000..]);
+}
+
+#[tokio::test]
+async fn uses_the_key_of_a_refreshed_link() {
+    let stand_in = stand_in();
+    let first_key = TrackKey::from_hex(FIRST_KEY).unwrap();
+    let link = TrackLink {
+        client: client(),
+        track_id: 2,
+        codec: "flac".to_string(),
+        bitrate: 0,
+        encrypted: true,
+    };
+
+    // The link has expired, and the file behind the new one is encrypted
+    // with another key.
+    let source = HttpSource::with_length(
+        reqwest::Client::new(),
+        format!("{}/expired", stand_in.url),
+        Some(plain().len() as u64),
+        Some(link),
+    );
+    let mut source = DecryptingSource::new(source, Some(first_key));
+
+    let plain = plain();
+    assert_eq!(
+        source.read_range(100, 1000).await.unwrap(),
+        plain[100..1100]
+    );
+}
+
+#[tokio::test]
+async fn tells_missing_lyrics_from_failed_requests() {
+    stand_in();
+    let directory = std::env::temp_dir()
+        .join(format!("yatui-api-lyrics-{}", std::process::id()));
+    let _ = std::fs::remove_dir_all(&directory);
+    let cache = LyricsCache::open(directory.clone()).unwrap();
+    let http = reqwest::Client::new();
+
+    assert!(cache.load(&client(), &http, 3).await.unwrap().is_none());
+    assert!(cache.load(&client(), &http, 4).await.is_err());
+    assert!(cache.get(4).is_none());
+
+    std::fs::remove_dir_all(directory).unwrap();
+}
+
+#[tokio::test]
+async fn retries_playlist_edits_against_the_new_revision() {
+    let stand_in = stand_in();
+    let edit = PlaylistEdit::Insert {
+        at: None,
+        tracks: vec![(1, Some(2))],
+    };
+
+    let playlist = edit_playlist(&client(), 5, 1, &edit).await.unwrap();
+    assert_eq!(playlist.revision, 3);
+    assert_eq!(*stand_in.playlist_changes.lock().unwrap(), ["1", "2"]);
+}